@@ -1,16 +1,29 @@
 use querydown::*;
+use serde::Serialize;
 use wasm_bindgen::prelude::*;
 
+fn to_js(value: &impl Serialize) -> JsValue {
+    serde_wasm_bindgen::to_value(value).unwrap_or_else(|e| JsValue::from_str(&e.to_string()))
+}
+
+/// Returns a `CompileOutput` as a JS object. Compilation errors are thrown as objects with a code,
+/// message, span, notes and replacements.
 #[wasm_bindgen]
-pub fn compile(schema_json: &str, dialect: &str, input: String) -> Result<String, String> {
+pub fn compile(schema_json: &str, dialect: &str, input: String) -> Result<JsValue, JsValue> {
     let dialect = match dialect {
         "postgres" => Box::new(Postgres()),
-        _ => return Err("Invalid dialect".to_string()),
+        _ => return Err(JsValue::from_str("Invalid dialect")),
     };
     let options = Options {
         dialect,
         identifier_resolution: IdentifierResolution::Flexible,
+        module_resolver: None,
+        prelude: None,
+        custom_functions: vec![],
     };
-    let compiler = Compiler::new(schema_json, options)?;
-    compiler.compile(input.to_owned())
+    let compiler = Compiler::new(schema_json, options).map_err(|e| to_js(&e))?;
+    let output = compiler
+        .compile_output(input.to_owned())
+        .map_err(|e| to_js(&e))?;
+    Ok(to_js(&output))
 }
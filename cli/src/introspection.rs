@@ -0,0 +1,228 @@
+use std::collections::HashSet;
+
+use querydown::{
+    DataType, PrimitiveColumn, PrimitiveLink, PrimitiveReference, PrimitiveSchema, PrimitiveTable,
+};
+use rusqlite::{Connection, Result};
+
+/// A single-column foreign key, as reported by `pragma_foreign_key_list`
+struct ForeignKey {
+    from: String,
+    table: String,
+    /// `None` when the foreign key refers to the primary key of `table` implicitly
+    to: Option<String>,
+}
+
+/// Read the schema of a SQLite database
+pub fn introspect_sqlite(connection: &Connection) -> Result<PrimitiveSchema> {
+    let mut tables = vec![];
+    for table_name in get_table_names(connection)? {
+        let columns = get_columns(connection, &table_name)?;
+        tables.push(PrimitiveTable {
+            name: table_name,
+            columns,
+        });
+    }
+    let mut links = vec![];
+    for table in tables.iter() {
+        let unique_columns = get_unique_columns(connection, table)?;
+        for foreign_key in get_foreign_keys(connection, &table.name)? {
+            let Some(from) = find_column(table, &foreign_key.from) else {
+                continue;
+            };
+            // SQLite doesn't require the referenced table to exist
+            let Some(target) = tables
+                .iter()
+                .find(|t| t.name.eq_ignore_ascii_case(&foreign_key.table))
+            else {
+                continue;
+            };
+            let to = match &foreign_key.to {
+                Some(to) => find_column(target, to),
+                None => get_primary_key(target),
+            };
+            let Some(to) = to else {
+                continue;
+            };
+            links.push(PrimitiveLink {
+                from: PrimitiveReference {
+                    table: table.name.clone(),
+                    column: from.name.clone(),
+                },
+                to: PrimitiveReference {
+                    table: target.name.clone(),
+                    column: to.name.clone(),
+                },
+                unique: unique_columns.contains(&from.name),
+            });
+        }
+    }
+    Ok(PrimitiveSchema { tables, links })
+}
+
+fn get_table_names(connection: &Connection) -> Result<Vec<String>> {
+    let mut statement = connection.prepare(
+        "SELECT name FROM sqlite_master
+        WHERE type = 'table' AND substr(name, 1, 7) != 'sqlite_'
+        ORDER BY name",
+    )?;
+    let names = statement.query_map([], |row| row.get(0))?;
+    names.collect()
+}
+
+/// Declared types which Querydown doesn't recognize are omitted, leaving the column untyped
+fn get_columns(connection: &Connection, table_name: &str) -> Result<Vec<PrimitiveColumn>> {
+    let mut statement = connection
+        .prepare("SELECT name, type, \"notnull\", pk FROM pragma_table_info(?1) ORDER BY cid")?;
+    let columns = statement.query_map([table_name], |row| {
+        let declared_type: String = row.get(1)?;
+        let not_null: bool = row.get(2)?;
+        let primary_key = row.get::<_, i64>(3)? > 0;
+        Ok(PrimitiveColumn {
+            name: row.get(0)?,
+            data_type: DataType::parse(&declared_type).map(|t| t.to_string()),
+            // SQLite permits NULL within most primary keys, but other databases don't
+            nullable: Some(!not_null && !primary_key),
+            primary_key,
+        })
+    })?;
+    columns.collect()
+}
+
+/// Foreign keys which span multiple columns are omitted because links join on one column
+fn get_foreign_keys(connection: &Connection, table_name: &str) -> Result<Vec<ForeignKey>> {
+    let mut statement = connection.prepare(
+        "SELECT \"from\", \"table\", \"to\" FROM pragma_foreign_key_list(?1)
+        WHERE id IN (
+            SELECT id FROM pragma_foreign_key_list(?1) GROUP BY id HAVING count(*) = 1
+        )
+        ORDER BY id",
+    )?;
+    let foreign_keys = statement.query_map([table_name], |row| {
+        Ok(ForeignKey {
+            from: row.get(0)?,
+            table: row.get(1)?,
+            to: row.get(2)?,
+        })
+    })?;
+    foreign_keys.collect()
+}
+
+/// Columns whose values are unique on their own, either because they're the sole primary key or
+/// because of a unique index. Partial indexes don't count because they permit duplicates outside
+/// of their condition.
+fn get_unique_columns(connection: &Connection, table: &PrimitiveTable) -> Result<HashSet<String>> {
+    let mut statement = connection.prepare(
+        "SELECT min(info.name) FROM pragma_index_list(?1) AS list
+        JOIN pragma_index_info(list.name) AS info
+        WHERE list.\"unique\" = 1 AND list.partial = 0
+        GROUP BY list.name
+        HAVING count(*) = 1",
+    )?;
+    let names = statement.query_map([&table.name], |row| row.get::<_, Option<String>>(0))?;
+    let mut unique_columns = HashSet::new();
+    for name in names {
+        // Indexes on expressions have no column name
+        unique_columns.extend(name?);
+    }
+    unique_columns.extend(get_primary_key(table).map(|column| column.name.clone()));
+    Ok(unique_columns)
+}
+
+/// The primary key column, when the primary key isn't composite
+fn get_primary_key(table: &PrimitiveTable) -> Option<&PrimitiveColumn> {
+    let mut primary_key_columns = table.columns.iter().filter(|c| c.primary_key);
+    match (primary_key_columns.next(), primary_key_columns.next()) {
+        (Some(column), None) => Some(column),
+        _ => None,
+    }
+}
+
+/// SQLite compares names without regard to case, so foreign keys may refer to columns using a
+/// different case than the column's declaration
+fn find_column<'a>(table: &'a PrimitiveTable, name: &str) -> Option<&'a PrimitiveColumn> {
+    table
+        .columns
+        .iter()
+        .find(|column| column.name.eq_ignore_ascii_case(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use querydown::{Compiler, Options};
+    use rusqlite::Connection;
+
+    use super::*;
+
+    #[test]
+    fn test_introspect_sqlite() {
+        let connection = Connection::open_in_memory().unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE teams (id INTEGER PRIMARY KEY, name TEXT, area GEOMETRY);
+                CREATE TABLE users (
+                    id INTEGER PRIMARY KEY,
+                    username VARCHAR(50) NOT NULL,
+                    team INT REFERENCES Teams
+                );
+                CREATE TABLE profiles (
+                    id INTEGER PRIMARY KEY,
+                    user INTEGER NOT NULL UNIQUE REFERENCES users (id),
+                    bio
+                );
+                CREATE TABLE badges (id INTEGER PRIMARY KEY, owner INTEGER REFERENCES users (id));
+                CREATE UNIQUE INDEX badges_owner ON badges (owner) WHERE owner IS NOT NULL;
+                CREATE TABLE memberships (
+                    user INTEGER REFERENCES users (id),
+                    team INTEGER REFERENCES teams (id),
+                    PRIMARY KEY (user, team)
+                );",
+            )
+            .unwrap();
+        let schema = introspect_sqlite(&connection).unwrap();
+
+        let table_names = schema.tables.iter().map(|t| t.name.as_str());
+        assert_eq!(
+            table_names.collect::<Vec<_>>(),
+            vec!["badges", "memberships", "profiles", "teams", "users"]
+        );
+        let teams = &schema.tables[3];
+        let types = teams.columns.iter().map(|c| c.data_type.as_deref());
+        assert_eq!(
+            types.collect::<Vec<_>>(),
+            vec![Some("integer"), Some("text"), None]
+        );
+        let users = &schema.tables[4];
+        let nullable = users.columns.iter().map(|c| c.nullable);
+        assert_eq!(
+            nullable.collect::<Vec<_>>(),
+            vec![Some(false), Some(false), Some(true)]
+        );
+
+        let links = schema
+            .links
+            .iter()
+            .map(|l| {
+                let from = format!("{}.{}", l.from.table, l.from.column);
+                let to = format!("{}.{}", l.to.table, l.to.column);
+                (from, to, l.unique)
+            })
+            .collect::<Vec<_>>();
+        let link = |from: &str, to: &str, unique| (from.to_owned(), to.to_owned(), unique);
+        assert_eq!(
+            links,
+            vec![
+                link("badges.owner", "users.id", false),
+                link("memberships.team", "teams.id", false),
+                link("memberships.user", "users.id", false),
+                link("profiles.user", "users.id", true),
+                link("users.team", "teams.id", false),
+            ]
+        );
+
+        let schema_json = serde_json::to_string(&schema).unwrap();
+        let compiler = Compiler::new(&schema_json, Options::default()).unwrap();
+        let result = compiler.compile("#profiles $bio $user.team.name".to_owned());
+        assert!(result.is_ok(), "{:?}", result);
+    }
+}
@@ -1,6 +1,12 @@
 use clap::{Args, Parser, Subcommand};
 use querydown::*;
-use std::io::{self, Read};
+use rusqlite::{Connection, OpenFlags};
+use std::{
+    io::{self, Read},
+    path::PathBuf,
+};
+
+mod introspection;
 
 /// Querydown compiler
 #[derive(Parser, Debug)]
@@ -14,8 +20,8 @@ struct Cli {
 enum Command {
     /// Compile Querydown code to SQL
     Compile(CompileArgs),
-    /// Analyze a database to generate a schema JSON file
-    Introspect,
+    /// Analyze a SQLite database to generate a schema JSON file
+    Introspect(IntrospectArgs),
 }
 
 #[derive(Debug, Args)]
@@ -23,36 +29,70 @@ struct CompileArgs {
     /// Path to the schema JSON file
     #[arg(short, long)]
     schema: String,
+    /// Directory from which imported modules are read. The module `foo/bar` is read from the
+    /// file `foo/bar.qd` within this directory.
+    #[arg(short, long)]
+    modules: Option<PathBuf>,
+    /// Path to a file of definitions to make available within the query without importing them
+    #[arg(short, long)]
+    prelude: Option<PathBuf>,
     /// The querydown query to execute. If empty, stdin will be used.
     query: Option<String>,
 }
 
+#[derive(Debug, Args)]
+struct IntrospectArgs {
+    /// Path to the SQLite database file
+    database: PathBuf,
+}
+
 fn get_stdin() -> String {
     let mut buffer = String::new();
     io::stdin().read_to_string(&mut buffer).unwrap();
     buffer
 }
 
+struct DirectoryModuleResolver(PathBuf);
+
+impl ModuleResolver for DirectoryModuleResolver {
+    fn resolve(&self, path: &str) -> Result<String, String> {
+        let file_path = self.0.join(format!("{path}.qd"));
+        std::fs::read_to_string(file_path)
+            .map_err(|e| format!("Unable to read module `{path}`: {e}"))
+    }
+}
+
 fn compile(args: CompileArgs) {
     let querydown_code = args.query.unwrap_or_else(get_stdin);
     let schema_json = std::fs::read_to_string(args.schema).unwrap();
+    let prelude = args
+        .prelude
+        .map(|path| std::fs::read_to_string(path).unwrap());
     let options = Options {
         dialect: Box::new(Postgres()),
         identifier_resolution: IdentifierResolution::Flexible,
+        module_resolver: args
+            .modules
+            .map(|dir| Box::new(DirectoryModuleResolver(dir)) as Box<dyn ModuleResolver>),
+        prelude,
+        custom_functions: vec![],
     };
     let compiler = Compiler::new(&schema_json, options).unwrap();
     let sql_code = compiler.compile(querydown_code).unwrap();
     println!("{sql_code}");
 }
 
-fn introspect() {
-    todo!()
+fn introspect(args: IntrospectArgs) {
+    let connection =
+        Connection::open_with_flags(args.database, OpenFlags::SQLITE_OPEN_READ_ONLY).unwrap();
+    let schema = introspection::introspect_sqlite(&connection).unwrap();
+    println!("{}", serde_json::to_string_pretty(&schema).unwrap());
 }
 
 fn main() -> () {
     let args = Cli::parse();
     match args.command {
         Command::Compile(args) => compile(args),
-        Command::Introspect => introspect(),
+        Command::Introspect(args) => introspect(args),
     }
 }
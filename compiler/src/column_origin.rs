@@ -0,0 +1,44 @@
+use serde::Serialize;
+
+/// Where the values within a result column come from, so that hosts can tell which cells may be
+/// updated and how to locate the row to update.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ColumnOrigin {
+    /// Values of a column in the base table, or in a single record related to it
+    TableColumn(SourceColumn),
+    /// Values computed from an expression, e.g. `$(title|length)` or a computed column
+    Computed,
+    /// Values aggregated from many related records (or picked from among them) via a CTE, e.g.
+    /// `$#comments.id%count` or `$#comments%last(created_at).body`
+    Aggregated,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SourceColumn {
+    pub table: String,
+    pub column: String,
+    /// Whether the schema allows `NULL` within `column`, e.g. so that hosts can tell whether a cell
+    /// may be cleared
+    pub nullable: bool,
+    /// A unique column within `table` whose value identifies the row, when it can be determined
+    /// from the schema
+    pub key: Option<String>,
+    /// The links followed from the base table to reach `table`, empty when `table` is the base
+    /// table. The row's key is the value of the `from` column of the last link, which the query
+    /// only selects when asked to. E.g. for `$author.team.name`, also select `$author.team`, whose
+    /// origin is that `from` column, reached via the path without its last link.
+    pub path: Vec<LinkStep>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LinkStep {
+    pub from: ColumnReference,
+    pub to: ColumnReference,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ColumnReference {
+    pub table: String,
+    pub column: String,
+}
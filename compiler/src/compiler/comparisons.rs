@@ -2,7 +2,7 @@ use querydown_parser::ast::*;
 
 use crate::{
     compiler::expr::convert_expr,
-    errors::msg,
+    errors::{msg, Error},
     sql::{
         expr::build::*,
         tree::{CtePurpose, SqlExpr},
@@ -15,7 +15,7 @@ use super::{
     scope::Scope,
 };
 
-pub fn convert_comparison(c: Comparison, scope: &mut Scope) -> Result<SqlExpr, String> {
+pub fn convert_comparison(c: Comparison, scope: &mut Scope) -> Result<SqlExpr, Error> {
     use ComparisonSide::{Expansion as CmpExpansion, Expr as CmpExpr, Range as CmpRange};
 
     let mut simple = |l: &Expr, r: &Expr| convert_simple_comparison(l, c.operator, r, scope);
@@ -88,7 +88,7 @@ fn convert_simple_comparison(
     operator: Operator,
     right: &Expr,
     scope: &mut Scope,
-) -> Result<SqlExpr, String> {
+) -> Result<SqlExpr, Error> {
     use Operator::*;
 
     if left.is_zero() && operator == Eq {
@@ -145,7 +145,7 @@ fn convert_range_comparison(
     expr: &Expr,
     range: &Range,
     scope: &mut Scope,
-) -> Result<SqlExpr, String> {
+) -> Result<SqlExpr, Error> {
     let lower_op = match range.lower.exclusivity {
         Exclusivity::Inclusive => Operator::Gte,
         Exclusivity::Exclusive => Operator::Gt,
@@ -189,7 +189,7 @@ fn convert_expression_vs_zero(
     expr: &Expr,
     cmp: ComparisonVsZero,
     scope: &mut Scope,
-) -> Result<SqlExpr, String> {
+) -> Result<SqlExpr, Error> {
     let fallback = |scope: &mut Scope| {
         let op = match cmp {
             ComparisonVsZero::Eq => cmp::eq,
@@ -197,7 +197,9 @@ fn convert_expression_vs_zero(
         };
         Ok(op(convert_expr(expr.to_owned(), scope)?, value::zero()))
     };
-    let Expr::Path(path_parts) = &expr else { return fallback(scope) };
+    let Expr::Path(path_parts) = &expr else {
+        return fallback(scope);
+    };
     let Ok(clarified_path) = clarify_path(path_parts.to_owned(), scope) else {
         return fallback(scope);
     };
@@ -205,7 +207,9 @@ fn convert_expression_vs_zero(
         return fallback(scope);
     };
     let join_result = scope.join_chain_to_many(&clarified_path.head, chain, None, cmp.into());
-    let Ok(pk) = join_result else { return fallback(scope) };
+    let Ok(pk) = join_result else {
+        return fallback(scope);
+    };
     match cmp {
         ComparisonVsZero::Eq => Ok(cmp::is_null(pk)),
         ComparisonVsZero::Gt => Ok(cmp::is_not_null(pk)),
@@ -1,39 +1,83 @@
-use querydown_parser::parse;
+use querydown_parser::{ast::ImportItems, parse, parse_module};
 
 use crate::{
+    errors::{msg, Error},
     schema::{primitive_schema::PrimitiveSchema, Schema},
     sql::tree::Select,
-    Options,
+    ColumnOrigin, CompileOutput, Options, QueryMetadata, ResultColumn,
 };
 
 use super::{
-    expr::convert_condition_set, rendering::Render, result_columns::convert_result_columns,
-    scope::Scope,
+    definitions::Definitions,
+    expr::convert_condition_set,
+    functions::validate_custom_functions,
+    modules::ModuleLoader,
+    rendering::Render,
+    result_columns::convert_result_columns,
+    scope::{Gathered, Scope},
+    updates::{build_cell_update, CellUpdate},
 };
 
 pub struct Compiler {
     options: Options,
     schema: Schema,
+    prelude: Definitions,
 }
 
 impl Compiler {
-    pub fn new(schema_json: &str, options: Options) -> Result<Self, String> {
+    pub fn new(schema_json: &str, options: Options) -> Result<Self, Error> {
         let primitive_schema = serde_json::from_str::<PrimitiveSchema>(schema_json)
-            .map_err(|_| "Schema input is not valid JSON.")?;
+            .map_err(|_| msg::schema_not_json())?;
         let schema = Schema::try_from(primitive_schema)?;
-        Ok(Self { options, schema })
+        validate_custom_functions(&options.custom_functions)?;
+        let prelude = match &options.prelude {
+            Some(source) => {
+                let module = parse_module(source).map_err(|e| msg::invalid_prelude(&e))?;
+                ModuleLoader::new(&options, &schema, Definitions::default())
+                    .load(module.statements)
+                    .map_err(Error::without_location)?
+                    .into_prelude()
+            }
+            None => Definitions::default(),
+        };
+        Ok(Self {
+            options,
+            schema,
+            prelude,
+        })
     }
 
-    pub fn compile(&self, input: String) -> Result<String, String> {
+    /// Compile the input, returning only the SQL. String literals are rendered inline so that the
+    /// SQL can be run without binding any parameters.
+    pub fn compile(&self, input: String) -> Result<String, Error> {
+        self.convert(input, Gathered::default())
+            .map(|output| output.sql)
+    }
+
+    /// Compile the input, binding its string literals as parameters (see `CompileOutput::params`)
+    pub fn compile_output(&self, input: String) -> Result<CompileOutput, Error> {
+        self.convert(input, Gathered::binding_params())
+    }
+
+    fn convert(&self, input: String, gathered: Gathered) -> Result<CompileOutput, Error> {
         let query = parse(&input)?;
-        let mut scope = Scope::build(&self.options, &self.schema, &query.base_table)?;
+        let definitions = ModuleLoader::new(&self.options, &self.schema, self.prelude.clone())
+            .load(query.statements)?;
+        let mut scope = Scope::build(
+            &self.options,
+            &self.schema,
+            &definitions,
+            &gathered,
+            &query.base_table,
+        )
+        .map_err(|e| e.or_span(query.base_table_span))?;
         let mut select = Select::from(scope.get_base_table().name.clone());
 
         let mut transformations_iter = query.transformations.into_iter();
         let first_transformation = transformations_iter.next().unwrap_or_default();
         let second_transformation = transformations_iter.next();
         if second_transformation.is_some() {
-            return Err("Pipelines not yet supported".to_string());
+            return Err(msg::pipelines_not_supported());
         }
 
         select.conditions = convert_condition_set(first_transformation.conditions, &mut scope)?;
@@ -43,6 +87,74 @@ impl Compiler {
 
         (select.joins, select.ctes) = scope.decompose_join_tree();
 
-        Ok(format!("{};", select.render(&mut scope)))
+        let columns: Vec<ResultColumn> = select.columns.iter().map(ResultColumn::from).collect();
+        let metadata = QueryMetadata {
+            base_table: scope.get_base_table().name.clone(),
+            dialect: self.options.dialect.name(),
+        };
+        let sql = format!("{};", select.render(&mut scope));
+        let mut warnings = get_unused_import_warnings(&definitions, &gathered);
+        warnings.extend(get_untyped_column_warnings(&columns));
+        Ok(CompileOutput {
+            sql,
+            columns,
+            params: gathered.into_params(),
+            warnings,
+            metadata,
+        })
+    }
+
+    /// Produce a parameterized `UPDATE` statement for a cell edited within the results. The origin
+    /// comes from `compile_output`, and `key_value` identifies the row (see `SourceColumn`).
+    pub fn update_cell<K, V>(
+        &self,
+        origin: &ColumnOrigin,
+        key_value: K,
+        new_value: V,
+    ) -> Result<CellUpdate<K, V>, Error> {
+        build_cell_update(origin, key_value, new_value, self.options.dialect.as_ref())
     }
 }
+
+/// Warn about imports within the query whose definitions are never used
+fn get_unused_import_warnings(definitions: &Definitions, gathered: &Gathered) -> Vec<String> {
+    definitions
+        .get_root_imports()
+        .iter()
+        .flat_map(|root_import| {
+            let module = &root_import.import.module;
+            let unused = root_import
+                .definitions
+                .iter()
+                .filter(|key| !gathered.is_used(key))
+                .map(|(_, name)| name)
+                .collect::<Vec<_>>();
+            match root_import.import.items {
+                // Unused items are expected when importing everything, so only warn when nothing
+                // is used
+                ImportItems::All if unused.len() == root_import.definitions.len() => {
+                    vec![msg::unused_module(module)]
+                }
+                ImportItems::All => vec![],
+                ImportItems::Named(_) => unused
+                    .into_iter()
+                    .map(|name| msg::unused_import(name, module))
+                    .collect(),
+            }
+        })
+        .collect()
+}
+
+/// Warn about columns from the schema whose values can't be given a type
+fn get_untyped_column_warnings(columns: &[ResultColumn]) -> Vec<String> {
+    columns
+        .iter()
+        .filter(|column| column.data_type.is_none())
+        .filter_map(|column| match &column.origin {
+            ColumnOrigin::TableColumn(source) => {
+                Some(msg::untyped_column(&source.table, &source.column))
+            }
+            _ => None,
+        })
+        .collect()
+}
@@ -1,6 +1,13 @@
 pub const CTE_ALIAS_PREFIX: &str = "cte";
 pub const CTE_PK_COLUMN_ALIAS: &str = "pk";
 pub const CTE_VALUE_COLUMN_PREFIX: &str = "v";
+pub const CLOSURE_CTE_ALIAS: &str = "closure";
+pub const CLOSURE_RELATED_COLUMN_ALIAS: &str = "related";
+pub const CLOSURE_DEPTH_COLUMN_ALIAS: &str = "depth";
+pub const CLOSURE_VISITED_COLUMN_ALIAS: &str = "visited";
+
+/// The separator used by `%list` when none is given
+pub const LIST_SEPARATOR: &str = ", ";
 
 /// We may eventually make this configurable
 pub const INDENT_SPACER: &str = "  ";
@@ -0,0 +1,245 @@
+use std::collections::HashMap;
+
+use querydown_parser::ast::*;
+
+use crate::{
+    errors::{msg, Error},
+    schema::{Schema, TableId},
+    Options,
+};
+
+use super::suggestions::suggest_tables;
+
+pub type NamespaceId = usize;
+
+/// All user-defined constants, functions, and computed columns available to a query, grouped into
+/// one namespace per module.
+#[derive(Debug, Default, Clone)]
+pub struct Definitions {
+    namespaces: Vec<Namespace>,
+    /// The namespace of the statements which were loaded first (i.e. the query itself)
+    root: NamespaceId,
+    /// The namespace of the host-provided prelude, which is visible from every other namespace
+    prelude: Option<NamespaceId>,
+    /// The imports within the root namespace, in order
+    root_imports: Vec<RootImport>,
+}
+
+impl Definitions {
+    pub fn get_root(&self) -> NamespaceId {
+        self.root
+    }
+
+    pub fn set_root(&mut self, id: NamespaceId) {
+        self.root = id;
+    }
+
+    pub fn get_root_imports(&self) -> &[RootImport] {
+        &self.root_imports
+    }
+
+    pub fn set_root_imports(&mut self, imports: Vec<RootImport>) {
+        self.root_imports = imports;
+    }
+
+    /// Make the root namespace visible from every other namespace
+    pub fn into_prelude(self) -> Self {
+        Definitions {
+            prelude: Some(self.root),
+            ..self
+        }
+    }
+
+    /// Reserve an id for a namespace which will be filled in later via `set_namespace`.
+    pub fn reserve_namespace(&mut self) -> NamespaceId {
+        self.namespaces.push(Namespace::default());
+        self.namespaces.len() - 1
+    }
+
+    pub fn set_namespace(&mut self, id: NamespaceId, namespace: Namespace) {
+        self.namespaces[id] = namespace;
+    }
+
+    /// Look up a definition within the given namespace, falling back to the prelude.
+    fn lookup<'s>(
+        &'s self,
+        id: NamespaceId,
+        f: impl Fn(&'s Namespace) -> Option<&'s ScopedDefinition>,
+    ) -> Option<&'s ScopedDefinition> {
+        self.namespaces
+            .get(id)
+            .and_then(&f)
+            .or_else(|| f(self.namespaces.get(self.prelude?)?))
+    }
+
+    pub fn get_constant(&self, id: NamespaceId, name: &str) -> Option<&ScopedDefinition> {
+        self.lookup(id, |n| n.get_constant(name))
+    }
+
+    pub fn get_function(
+        &self,
+        id: NamespaceId,
+        table_id: TableId,
+        name: &str,
+    ) -> Option<&ScopedDefinition> {
+        self.lookup(id, |n| n.get_function(table_id, name))
+    }
+
+    /// Names of the user-defined functions visible within the namespace which can be called on
+    /// `table_id`, including those from the prelude
+    pub fn get_function_names(&self, id: NamespaceId, table_id: TableId) -> Vec<String> {
+        [Some(id), self.prelude]
+            .into_iter()
+            .flatten()
+            .filter_map(|id| self.namespaces.get(id))
+            .flat_map(|namespace| namespace.get_function_names(table_id))
+            .cloned()
+            .collect()
+    }
+
+    pub fn get_column(
+        &self,
+        id: NamespaceId,
+        table_id: TableId,
+        column_name: &str,
+        options: &Options,
+    ) -> Option<&ScopedDefinition> {
+        self.lookup(id, |n| n.get_column(table_id, column_name, options))
+    }
+}
+
+/// A definition along with the namespace in which it was written. Names within the body of the
+/// definition are resolved using that namespace, regardless of where the definition is used.
+#[derive(Debug, Clone)]
+pub struct ScopedDefinition {
+    pub definition: Definition,
+    pub namespace: NamespaceId,
+}
+
+/// An import statement within the root namespace, along with the definitions which it made visible
+#[derive(Debug, Clone)]
+pub struct RootImport {
+    pub import: Import,
+    /// Each definition keyed by its namespace and the name it's visible under
+    pub definitions: Vec<(NamespaceId, DefinitionName)>,
+}
+
+/// The definitions visible within (or exported from) one module
+#[derive(Debug, Default, Clone)]
+pub struct Namespace {
+    constants: HashMap<String, ScopedDefinition>,
+    functions: HashMap<String, ScopedDefinition>,
+    /// Computed columns, keyed by table and then by column name
+    columns: HashMap<TableId, HashMap<String, ScopedDefinition>>,
+    /// Table-scoped functions, keyed by table and then by function name
+    table_functions: HashMap<TableId, HashMap<String, ScopedDefinition>>,
+}
+
+impl Namespace {
+    pub fn insert(
+        &mut self,
+        scoped: ScopedDefinition,
+        options: &Options,
+        schema: &Schema,
+    ) -> Result<(), Error> {
+        let definition = &scoped.definition;
+        let is_function = matches!(
+            definition.name,
+            DefinitionName::Function(_) | DefinitionName::TableFunction { .. }
+        );
+        if !is_function && !definition.params.is_empty() {
+            return Err(msg::params_on_non_function(&definition.name));
+        }
+        let (map, key) = match &definition.name {
+            DefinitionName::Constant(name) => (&mut self.constants, name.clone()),
+            DefinitionName::Function(name) => (&mut self.functions, name.clone()),
+            DefinitionName::Column { table, column } => {
+                let table_id = resolve_table_id(table, options, schema)?;
+                (self.columns.entry(table_id).or_default(), column.clone())
+            }
+            DefinitionName::TableFunction { table, function } => {
+                let table_id = resolve_table_id(table, options, schema)?;
+                (
+                    self.table_functions.entry(table_id).or_default(),
+                    function.clone(),
+                )
+            }
+        };
+        if map.contains_key(&key) {
+            return Err(msg::duplicate_definition(&definition.name));
+        }
+        map.insert(key, scoped);
+        Ok(())
+    }
+
+    /// Look up a definition by the name it was written with in an import statement
+    pub fn get(
+        &self,
+        name: &DefinitionName,
+        options: &Options,
+        schema: &Schema,
+    ) -> Option<&ScopedDefinition> {
+        match name {
+            DefinitionName::Constant(name) => self.constants.get(name),
+            DefinitionName::Function(name) => self.functions.get(name),
+            DefinitionName::Column { table, column } => {
+                let table_id = resolve_table_id(table, options, schema).ok()?;
+                self.columns.get(&table_id)?.get(column)
+            }
+            DefinitionName::TableFunction { table, function } => {
+                let table_id = resolve_table_id(table, options, schema).ok()?;
+                self.table_functions.get(&table_id)?.get(function)
+            }
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ScopedDefinition> {
+        self.constants
+            .values()
+            .chain(self.functions.values())
+            .chain(self.columns.values().flat_map(|m| m.values()))
+            .chain(self.table_functions.values().flat_map(|m| m.values()))
+    }
+
+    pub fn get_constant(&self, name: &str) -> Option<&ScopedDefinition> {
+        self.constants.get(name)
+    }
+
+    /// Functions scoped to `table_id` take precedence over global functions of the same name.
+    pub fn get_function(&self, table_id: TableId, name: &str) -> Option<&ScopedDefinition> {
+        self.table_functions
+            .get(&table_id)
+            .and_then(|functions| functions.get(name))
+            .or_else(|| self.functions.get(name))
+    }
+
+    pub fn get_function_names(&self, table_id: TableId) -> impl Iterator<Item = &String> {
+        self.table_functions
+            .get(&table_id)
+            .into_iter()
+            .flat_map(|functions| functions.keys())
+            .chain(self.functions.keys())
+    }
+
+    pub fn get_column(
+        &self,
+        table_id: TableId,
+        column_name: &str,
+        options: &Options,
+    ) -> Option<&ScopedDefinition> {
+        options.resolve_identifier(self.columns.get(&table_id)?, column_name)
+    }
+}
+
+fn resolve_table_id(
+    table_name: &str,
+    options: &Options,
+    schema: &Schema,
+) -> Result<TableId, Error> {
+    options
+        .resolve_identifier(&schema.table_lookup, table_name)
+        .copied()
+        .ok_or_else(|| {
+            msg::unknown_table(table_name).with_suggestions(&suggest_tables(table_name, schema))
+        })
+}
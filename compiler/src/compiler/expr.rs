@@ -1,7 +1,9 @@
+use std::collections::HashMap;
+
 use querydown_parser::ast::*;
 
 use crate::{
-    errors::msg,
+    errors::{msg, Error},
     schema::links::Link,
     sql::expr::build::*,
     sql::tree::{CtePurpose, SqlExpr},
@@ -10,23 +12,35 @@ use crate::{
 use super::{
     comparisons::convert_comparison,
     constants::{VAR_FALSE, VAR_INFINITY, VAR_NOW, VAR_NULL, VAR_TRUE},
+    definitions::ScopedDefinition,
     functions::convert_call,
-    paths::{clarify_path, ClarifiedPathTail},
+    paths::{
+        clarify_path, ClarifiedPathTail, CteValueTemplate, PickTemplate, TransitiveLink,
+        TransitiveValueTemplate,
+    },
     scope::Scope,
+    type_inference::infer_type,
 };
 
 /// Convert a Querydown expression to an SQL expression
-pub fn convert_expr(expr: Expr, scope: &mut Scope) -> Result<SqlExpr, String> {
+pub fn convert_expr(expr: Expr, scope: &mut Scope) -> Result<SqlExpr, Error> {
     match expr {
         Expr::Number(n) => Ok(SqlExpr::atom(n)),
         Expr::Date(d) => Ok(SqlExpr::atom(scope.options.dialect.date(&d))),
         Expr::Duration(d) => Ok(SqlExpr::atom(scope.options.dialect.duration(&d))),
-        Expr::String(s) => Ok(SqlExpr::atom(scope.options.dialect.quote_string(&s))),
+        Expr::String(s) => Ok(scope.string_expr(&s)),
         Expr::Variable(v) => convert_variable(&v, scope),
         Expr::Path(p) => convert_path(p, scope),
-        Expr::ConditionSet(cs) => convert_condition_set(cs, scope),
-        Expr::HasQuantity(h) => convert_has_quantity(h, scope),
-        Expr::Call(c) => convert_call(c, scope),
+        Expr::ConditionSet(cs) => convert_conditions(cs, scope),
+        Expr::HasQuantity(h) => {
+            let span = h.span;
+            convert_has_quantity(h, scope).map_err(|e| e.or_span(span))
+        }
+        Expr::Outer(e) => scope.with_outer_record(|scope| convert_expr(*e, scope)),
+        Expr::Call(c) => {
+            let span = c.span;
+            convert_call(c, scope).map_err(|e| e.or_span(span))
+        }
         Expr::Product(a, b) => Ok(math::multiply(
             convert_expr(*a, scope)?,
             convert_expr(*b, scope)?,
@@ -43,37 +57,72 @@ pub fn convert_expr(expr: Expr, scope: &mut Scope) -> Result<SqlExpr, String> {
             convert_expr(*a, scope)?,
             convert_expr(*b, scope)?,
         )),
-        Expr::Comparison(c) => convert_comparison(*c, scope),
+        Expr::Comparison(c) => {
+            let span = c.span;
+            convert_comparison(*c, scope).map_err(|e| e.or_span(span))
+        }
     }
 }
 
-fn convert_variable(variable: &str, _: &Scope) -> Result<SqlExpr, String> {
+fn convert_variable(variable: &str, scope: &mut Scope) -> Result<SqlExpr, Error> {
     let sql = match variable {
         VAR_NOW => func::now(),
         VAR_INFINITY => value::infinity(),
         VAR_TRUE => value::true_(),
         VAR_FALSE => value::false_(),
         VAR_NULL => value::null(),
-        // TODO handle user-defined variables from scope
-        name => return Err(msg::unknown_variable(name)),
+        name => return convert_user_variable(name, scope),
     };
     Ok(SqlExpr::atom(sql.to_string()))
 }
 
-fn convert_path(parts: Vec<PathPart>, scope: &mut Scope) -> Result<SqlExpr, String> {
-    let prefixed_parts = scope.path_prefix.iter().cloned().chain(parts).collect();
+/// Convert a function parameter or a user-defined constant
+fn convert_user_variable(name: &str, scope: &mut Scope) -> Result<SqlExpr, Error> {
+    if let Some(param) = scope.get_param(name) {
+        return param.get_value(name);
+    }
+    let constant = scope
+        .get_constant(name)
+        .ok_or_else(|| msg::unknown_variable(name))?;
+    scope.expand_definition(constant, HashMap::new(), |scope| {
+        convert_expr(constant.definition.body.clone(), scope)
+    })
+}
+
+fn convert_path(parts: Vec<PathPart>, scope: &mut Scope) -> Result<SqlExpr, Error> {
+    let mut prefixed_parts: Vec<PathPart> =
+        scope.path_prefix.iter().cloned().chain(parts).collect();
+    if let Some((pick, rest)) = split_at_pick(&mut prefixed_parts) {
+        if rest.is_empty() {
+            return Err(msg::pick_without_value());
+        }
+        return convert_pick(prefixed_parts, pick, Expr::Path(rest), scope);
+    }
+    if let Some(rest) = split_at_transitive(&mut prefixed_parts) {
+        if rest.is_empty() {
+            return Err(msg::transitive_without_value());
+        }
+        let template = TransitiveValueTemplate::Farthest(Expr::Path(rest));
+        return convert_transitive(prefixed_parts, template, scope);
+    }
+    if let Some(expr) = pop_path_expr(&mut prefixed_parts) {
+        return scope.with_path_prefix(prefixed_parts, |scope| convert_expr(expr, scope));
+    }
+    if let Some(result) = convert_computed_column(&prefixed_parts, scope) {
+        return result;
+    }
     let clarified_path = clarify_path(prefixed_parts, scope)?;
     match (clarified_path.head, clarified_path.tail) {
         (None, None) => Ok(SqlExpr::empty()),
         (None, Some(ClarifiedPathTail::Column(column_name))) => {
-            let table_name = scope.get_base_table().name.clone();
+            let table_name = scope.get_base_alias();
             Ok(scope.table_column_expr(&table_name, &column_name))
         }
         (Some(chain_to_one), None) => {
             let (truncated_chain_to_one_opt, last_link) = chain_to_one.with_last_link_broken_off();
             let table_name = match truncated_chain_to_one_opt {
                 Some(truncated_chain_to_one) => scope.join_chain_to_one(&truncated_chain_to_one),
-                None => scope.get_base_table().name.clone(),
+                None => scope.get_base_alias(),
             };
             let column_reference = last_link.get_start();
             let column_name = scope.schema.get_referenced_column_name(&column_reference);
@@ -92,10 +141,154 @@ fn convert_path(parts: Vec<PathPart>, scope: &mut Scope) -> Result<SqlExpr, Stri
     }
 }
 
+/// Remove the parenthesized expression from the end of the path, if there is one
+pub fn pop_path_expr(parts: &mut Vec<PathPart>) -> Option<Expr> {
+    match parts.pop() {
+        Some(PathPart::Expr(expr)) => Some(*expr),
+        other => {
+            parts.extend(other);
+            None
+        }
+    }
+}
+
+/// Split the path at its first pick, leaving the parts before the pick in `parts` and returning the
+/// pick along with the parts after it
+pub fn split_at_pick(parts: &mut Vec<PathPart>) -> Option<(Pick, Vec<PathPart>)> {
+    let index = parts.iter().position(|p| matches!(p, PathPart::Pick(_)))?;
+    let rest = parts.split_off(index + 1);
+    match parts.pop() {
+        Some(PathPart::Pick(pick)) => Some((pick, rest)),
+        _ => unreachable!(),
+    }
+}
+
+/// Convert `value` relative to the record picked at the end of `parts`, e.g. `author.username`
+/// for `#comments%last(created_at).author.username`.
+pub fn convert_pick(
+    parts: Vec<PathPart>,
+    pick: Pick,
+    value: Expr,
+    scope: &mut Scope,
+) -> Result<SqlExpr, Error> {
+    let clarified_path = clarify_path(parts, scope)?;
+    let Some(ClarifiedPathTail::ChainToMany((chain_to_many, None))) = clarified_path.tail else {
+        return Err(msg::misplaced_pick().with_span(pick.span));
+    };
+    let template = CteValueTemplate::Pick(PickTemplate::new(value, pick));
+    scope.join_chain_to_many(
+        &clarified_path.head,
+        chain_to_many,
+        Some(template),
+        CtePurpose::AggregateValue,
+    )
+}
+
+/// Split the path at its transitive marker, leaving the parts through the self-referential link in
+/// `parts` and returning the parts after the marker
+pub fn split_at_transitive(parts: &mut Vec<PathPart>) -> Option<Vec<PathPart>> {
+    let index = parts
+        .iter()
+        .position(|p| matches!(p, PathPart::Transitive))?;
+    let rest = parts.split_off(index + 1);
+    parts.pop();
+    Some(rest)
+}
+
+/// Convert a path which follows the self-referential link at the end of `parts` repeatedly. A link
+/// to one reaches the ancestors of a record (e.g. `duplicate_of*`), while a link to many reaches its
+/// descendants (e.g. `#issues(duplicate_of)*`).
+pub fn convert_transitive<'a>(
+    parts: Vec<PathPart>,
+    template: TransitiveValueTemplate<'a>,
+    scope: &mut Scope<'a, '_>,
+) -> Result<SqlExpr, Error> {
+    let clarified_path = clarify_path(parts, scope)?;
+    let (head, link) = match (clarified_path.head, clarified_path.tail) {
+        (Some(chain_to_one), None) => {
+            let (head, last_link) = chain_to_one.with_last_link_broken_off();
+            let link = TransitiveLink::try_new(last_link, ConditionSet::default())?;
+            (head, link)
+        }
+        (head, Some(ClarifiedPathTail::ChainToMany((chain_to_many, None)))) => {
+            if chain_to_many.len() != 1 {
+                return Err(msg::transitive_link_not_self_referential());
+            }
+            if matches!(template, TransitiveValueTemplate::Farthest(_)) {
+                return Err(msg::transitive_path_to_many_without_aggregate());
+            }
+            // This unwrap is safe because we've checked that the chain has exactly one link
+            let filtered_link = chain_to_many.into_iter().next().unwrap();
+            let link = TransitiveLink::try_new(&filtered_link.link, filtered_link.condition_set)?;
+            (head, link)
+        }
+        _ => return Err(msg::transitive_link_not_self_referential()),
+    };
+    scope.join_transitive(&head, link, template)
+}
+
+/// When the path ends with a user-defined computed column, convert the column's definition with
+/// its paths resolved relative to the table on which the column is defined. Returns `None` when
+/// the path doesn't refer to a computed column.
+fn convert_computed_column(
+    parts: &[PathPart],
+    scope: &mut Scope,
+) -> Option<Result<SqlExpr, Error>> {
+    let (column, head) = get_computed_column(parts, scope)?;
+    Some(scope.expand_definition(column, HashMap::new(), |scope| {
+        scope.with_path_prefix(head.to_vec(), |scope| {
+            convert_expr(column.definition.body.clone(), scope)
+        })
+    }))
+}
+
+/// The user-defined computed column at the end of the path, along with the parts of the path
+/// which lead to the table on which the column is defined. Returns `None` when the path doesn't
+/// refer to a computed column.
+pub fn get_computed_column<'a, 'p>(
+    parts: &'p [PathPart],
+    scope: &Scope<'a, '_>,
+) -> Option<(&'a ScopedDefinition, &'p [PathPart])> {
+    let (PathPart::Column(column), head) = parts.split_last()? else {
+        return None;
+    };
+    let column_name = &column.name;
+    let schema = scope.schema;
+    let table = if head.is_empty() {
+        schema.tables.get(&scope.get_base_table().id)?
+    } else {
+        let clarified_path = clarify_path(head.to_vec(), scope).ok()?;
+        if clarified_path.tail.is_some() {
+            return None;
+        }
+        schema
+            .tables
+            .get(&clarified_path.head?.get_ending_table_id())?
+    };
+    if scope
+        .options
+        .resolve_identifier(&table.column_lookup, column_name)
+        .is_some()
+    {
+        return None;
+    }
+    let column = scope.get_computed_column(table.id, column_name)?;
+    Some((column, head))
+}
+
+/// Check the types within a top-level condition set (nested condition sets are checked along with
+/// it) and convert it
 pub fn convert_condition_set(
     condition_set: ConditionSet,
     scope: &mut Scope,
-) -> Result<SqlExpr, String> {
+) -> Result<SqlExpr, Error> {
+    for entry in condition_set.entries.iter() {
+        infer_type(entry, scope)?;
+    }
+    convert_conditions(condition_set, scope)
+}
+
+fn convert_conditions(condition_set: ConditionSet, scope: &mut Scope) -> Result<SqlExpr, Error> {
     let conditions = condition_set
         .entries
         .into_iter()
@@ -104,15 +297,41 @@ pub fn convert_condition_set(
     Ok(cmp::condition_set(conditions, &condition_set.conjunction))
 }
 
-fn convert_has_quantity(has_quantity: HasQuantity, scope: &mut Scope) -> Result<SqlExpr, String> {
-    let operator = match has_quantity.quantity {
-        Quantity::AtLeastOne => Operator::Gt,
-        Quantity::Zero => Operator::Eq,
+fn convert_has_quantity(has_quantity: HasQuantity, scope: &mut Scope) -> Result<SqlExpr, Error> {
+    let (operator, count) = match has_quantity.quantity {
+        Quantity::AtLeastOne => (Operator::Gt, Expr::zero()),
+        Quantity::Zero => (Operator::Eq, Expr::zero()),
+        Quantity::ExactlyOne => (Operator::Eq, Expr::Number("1".to_string())),
+        Quantity::All => return convert_quantity_all(has_quantity.path_parts, scope),
     };
     let comparison = Comparison {
         left: ComparisonSide::Expr(Expr::Path(has_quantity.path_parts)),
         operator,
-        right: ComparisonSide::Expr(Expr::zero()),
+        right: ComparisonSide::Expr(count),
+        span: has_quantity.span,
     };
     convert_comparison(comparison, scope)
 }
+
+/// Every related record satisfies the conditions on the final table in the path. We find the
+/// related records which _don't_ satisfy them via a CTE and keep the base records which have none.
+fn convert_quantity_all(parts: Vec<PathPart>, scope: &mut Scope) -> Result<SqlExpr, Error> {
+    let parts: Vec<PathPart> = scope.path_prefix.iter().cloned().chain(parts).collect();
+    let Some(PathPart::TableWithMany(final_table)) = parts.last() else {
+        return Err(msg::quantity_all_applied_to_path_to_one());
+    };
+    if final_table.condition_set.is_empty() {
+        return Err(msg::quantity_all_without_conditions());
+    }
+    let clarified_path = clarify_path(parts, scope)?;
+    let Some(ClarifiedPathTail::ChainToMany((chain, None))) = clarified_path.tail else {
+        return Err(msg::quantity_all_applied_to_path_to_one());
+    };
+    let pk = scope.join_chain_to_many(
+        &clarified_path.head,
+        chain,
+        None,
+        CtePurpose::Counterexample,
+    )?;
+    Ok(cmp::is_null(pk))
+}
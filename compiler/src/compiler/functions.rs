@@ -1,42 +1,223 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use itertools::Itertools;
-use querydown_parser::ast::{Call, Expr, FunctionDimension};
+use querydown_parser::ast::{Call, Expr, FunctionDimension, PathPart};
 
 use crate::{
     compiler::{
-        expr::convert_expr,
-        paths::{clarify_path, AggregateExprTemplate, ClarifiedPathTail},
-        scope::Scope,
+        constants::LIST_SEPARATOR,
+        definitions::ScopedDefinition,
+        expr::{
+            convert_expr, convert_pick, convert_transitive, pop_path_expr, split_at_pick,
+            split_at_transitive,
+        },
+        paths::{
+            clarify_path, AggWrapper, AggregateExprTemplate, ClarifiedPathTail, CteValueTemplate,
+            TransitiveValueTemplate,
+        },
+        scope::{Param, Scope},
+        types::{sig, Accepts, ArithmeticOperator, Returns, Signature},
     },
-    errors::msg::{self, unknown_aggregate_function, unknown_scalar_function},
-    sql::expr::build::{agg::*, cond::*, date_time::*, func::*, math::*, strings::*},
+    errors::{
+        msg::{self, unknown_aggregate_function, unknown_scalar_function},
+        Error,
+    },
+    schema::data_type::DataType,
+    sql::expr::build::{agg::*, cond::*, date_time::*, func::*, math::*, strings::*, value::one},
     sql::tree::{CtePurpose, SqlExpr},
+    sql::{DatePart, DateUnit, Dialect, PadSide},
+    utils::rank_suggestions,
+    CustomFunction, CustomFunctionKind,
 };
 
-pub fn convert_call(call: Call, scope: &mut Scope) -> Result<SqlExpr, String> {
+pub fn convert_call(call: Call, scope: &mut Scope) -> Result<SqlExpr, Error> {
     match call.dimension {
         FunctionDimension::Scalar => convert_scalar_call(&call.name, call.args, scope),
-        FunctionDimension::Aggregate => convert_aggregate_call(&call.name, call.args, scope),
+        FunctionDimension::Aggregate => convert_aggregate_call_through_pick(call, scope),
+    }
+}
+
+/// When the aggregated path passes through a pick (e.g. `#issues%last(created_at).#comments%count`),
+/// the aggregate is computed relative to the picked record.
+fn convert_aggregate_call_through_pick(
+    mut call: Call,
+    scope: &mut Scope,
+) -> Result<SqlExpr, Error> {
+    if let Some(Expr::Path(parts)) = call.args.first() {
+        let mut prefixed_parts: Vec<PathPart> = scope
+            .path_prefix
+            .iter()
+            .cloned()
+            .chain(parts.clone())
+            .collect();
+        if let Some((pick, rest)) = split_at_pick(&mut prefixed_parts) {
+            call.args[0] = Expr::Path(rest);
+            return convert_pick(prefixed_parts, pick, Expr::Call(call), scope);
+        }
+    }
+    convert_aggregate_call(&call.name, call.args, scope)
+}
+
+fn convert_scalar_call(name: &str, e: Vec<Expr>, s: &mut Scope) -> Result<SqlExpr, Error> {
+    if let Some(definition) = s.get_user_function(name) {
+        return convert_user_function_call(definition, e, s);
+    }
+    if let Some(func) = s.get_scalar_function(name) {
+        return (func.convert)(e, s);
     }
+    let custom = s
+        .get_custom_function(name, CustomFunctionKind::Scalar)
+        .ok_or_else(|| {
+            let names = s.get_function_names(CustomFunctionKind::Scalar);
+            unknown_scalar_function(name).with_suggestions(&rank_suggestions(name, names))
+        })?;
+    convert_custom_scalar_call(custom, e, s)
 }
 
-fn convert_scalar_call(name: &str, e: Vec<Expr>, s: &mut Scope) -> Result<SqlExpr, String> {
-    let func = s
-        .get_scalar_function(name)
-        .ok_or_else(|| unknown_scalar_function(name))?;
-    func(e, s)
+fn convert_aggregate_call(name: &str, e: Vec<Expr>, s: &mut Scope) -> Result<SqlExpr, Error> {
+    if let Some(func) = s.get_aggregate_function(name) {
+        return (func.convert)(e, s);
+    }
+    let custom = s
+        .get_custom_function(name, CustomFunctionKind::Aggregate)
+        .ok_or_else(|| {
+            let names = s.get_function_names(CustomFunctionKind::Aggregate);
+            unknown_aggregate_function(name).with_suggestions(&rank_suggestions(name, names))
+        })?;
+    convert_custom_aggregate_call(custom, e, s)
 }
 
-fn convert_aggregate_call(name: &str, e: Vec<Expr>, s: &mut Scope) -> Result<SqlExpr, String> {
-    let func = s
-        .get_aggregate_function(name)
-        .ok_or_else(|| unknown_aggregate_function(name))?;
-    func(e, s)
+fn convert_custom_scalar_call(
+    func: &CustomFunction,
+    args: Vec<Expr>,
+    scope: &mut Scope,
+) -> Result<SqlExpr, Error> {
+    if !func.arity.accepts(args.len()) {
+        return Err(msg::wrong_arg_count(&func.name, &func.arity, args.len()));
+    }
+    let sql_args = args
+        .into_iter()
+        .map(|arg| convert_expr(arg, scope))
+        .collect::<Result<Vec<_>, _>>()?;
+    (func.render)(sql_args, scope.options.dialect.as_ref()).map_err(msg::host)
 }
 
-pub type FuncMap = HashMap<String, Func>;
-pub type Func = fn(Vec<Expr>, &mut Scope) -> Result<SqlExpr, String>;
+/// The first argument is aggregated within a CTE. Any remaining arguments are converted within the
+/// scope of the aggregated table and passed to the custom function after the aggregated value.
+fn convert_custom_aggregate_call<'a>(
+    func: &'a CustomFunction,
+    args: Vec<Expr>,
+    scope: &mut Scope<'a, '_>,
+) -> Result<SqlExpr, Error> {
+    if !func.arity.accepts(args.len()) {
+        return Err(msg::wrong_arg_count(&func.name, &func.arity, args.len()));
+    }
+    let mut args_iter = args.into_iter();
+    let value_arg = args_iter.next().ok_or_else(msg::expected_one_arg)?;
+    let extra_args = args_iter.collect();
+    let dialect = scope.options.dialect.as_ref();
+    let wrapper = move |value: SqlExpr, extra_args: Vec<SqlExpr>| {
+        let sql_args = std::iter::once(value).chain(extra_args).collect();
+        (func.render)(sql_args, dialect).map_err(msg::host)
+    };
+    aggregate_path(value_arg, extra_args, scope, Box::new(wrapper))
+}
+
+/// Ensure that custom functions don't conflict with built-in functions or with each other.
+pub fn validate_custom_functions(functions: &[CustomFunction]) -> Result<(), Error> {
+    let scalar_functions = get_standard_scalar_functions();
+    let aggregate_functions = get_standard_aggregate_functions();
+    let mut seen = HashSet::new();
+    for func in functions {
+        let builtins = match func.kind {
+            CustomFunctionKind::Scalar => &scalar_functions,
+            CustomFunctionKind::Aggregate => &aggregate_functions,
+        };
+        if builtins.contains_key(&func.name) {
+            return Err(msg::custom_function_conflicts_with_builtin(&func.name));
+        }
+        if !seen.insert((func.kind, func.name.as_str())) {
+            return Err(msg::duplicate_custom_function(&func.name));
+        }
+        if func.kind == CustomFunctionKind::Aggregate && func.arity.min() == 0 {
+            return Err(msg::custom_aggregate_without_value(&func.name));
+        }
+    }
+    Ok(())
+}
+
+/// Arguments are converted within the calling scope and then bound to the function's parameters
+/// while converting the function's body. Arguments which refer to the calling record can't be
+/// used within paths to many in the body (see `Param`).
+fn convert_user_function_call(
+    scoped: &ScopedDefinition,
+    args: Vec<Expr>,
+    scope: &mut Scope,
+) -> Result<SqlExpr, Error> {
+    let definition = &scoped.definition;
+    if args.len() != definition.params.len() {
+        let name = definition.name.to_string();
+        return Err(msg::wrong_arg_count(
+            &name,
+            &definition.params.len(),
+            args.len(),
+        ));
+    }
+    let mut params = HashMap::new();
+    for (param, arg) in definition.params.iter().zip(args) {
+        let value = convert_expr(arg.clone(), scope)?;
+        let refers_to_record = refers_to_record(&arg, scope);
+        params.insert(param.clone(), Param::new(value, refers_to_record));
+    }
+    scope.expand_definition(scoped, params, |scope| {
+        convert_expr(definition.body.clone(), scope)
+    })
+}
+
+/// True when the value of the expression depends on the record it's evaluated against. This errs
+/// on the side of true for expressions which are costly to analyze, such as condition sets and
+/// calls to user-defined functions.
+fn refers_to_record(expr: &Expr, scope: &mut Scope) -> bool {
+    match expr {
+        Expr::Number(_) | Expr::Date(_) | Expr::Duration(_) | Expr::String(_) => false,
+        Expr::Variable(name) => {
+            if let Some(param) = scope.get_param(name) {
+                return param.refers_to_record();
+            }
+            // Anything other than a constant is a built-in variable like `@now`
+            let Some(constant) = scope.get_constant(name) else {
+                return false;
+            };
+            scope
+                .expand_definition(constant, HashMap::new(), |scope| {
+                    Ok(refers_to_record(&constant.definition.body, scope))
+                })
+                .unwrap_or(true)
+        }
+        Expr::Call(call) => {
+            call.dimension == FunctionDimension::Aggregate
+                || scope.get_user_function(&call.name).is_some()
+                || call.args.iter().any(|arg| refers_to_record(arg, scope))
+        }
+        Expr::Product(a, b) | Expr::Quotient(a, b) | Expr::Sum(a, b) | Expr::Difference(a, b) => {
+            refers_to_record(a, scope) || refers_to_record(b, scope)
+        }
+        Expr::Path(_)
+        | Expr::ConditionSet(_)
+        | Expr::HasQuantity(_)
+        | Expr::Outer(_)
+        | Expr::Comparison(_) => true,
+    }
+}
+
+pub type FuncMap = HashMap<String, Function>;
+pub type Func = fn(Vec<Expr>, &mut Scope) -> Result<SqlExpr, Error>;
+
+/// A built-in function
+pub struct Function {
+    pub convert: Func,
+    pub signature: Signature,
+}
 
 /// Get the first item out of an Iterator, ensuring it has no more
 fn iter_one<T>(items: impl IntoIterator<Item = T>) -> Option<T> {
@@ -60,7 +241,7 @@ fn args_v(
     args: Vec<Expr>,
     scope: &mut Scope,
     f: fn(Vec<SqlExpr>) -> SqlExpr,
-) -> Result<SqlExpr, String> {
+) -> Result<SqlExpr, Error> {
     let mut sql_args = Vec::<SqlExpr>::new();
     for arg in args {
         sql_args.push(convert_expr(arg, scope)?);
@@ -69,11 +250,7 @@ fn args_v(
 }
 
 /// Used for a scalar function that takes one argument
-fn args_1(
-    args: Vec<Expr>,
-    scope: &mut Scope,
-    f: fn(SqlExpr) -> SqlExpr,
-) -> Result<SqlExpr, String> {
+fn args_1(args: Vec<Expr>, scope: &mut Scope, f: fn(SqlExpr) -> SqlExpr) -> Result<SqlExpr, Error> {
     let arg0 = iter_one(args).ok_or_else(msg::expected_one_arg)?;
     let a = convert_expr(arg0, scope)?;
     Ok(f(a))
@@ -84,42 +261,153 @@ fn args_2(
     args: Vec<Expr>,
     scope: &mut Scope,
     f: fn(SqlExpr, SqlExpr) -> SqlExpr,
-) -> Result<SqlExpr, String> {
+) -> Result<SqlExpr, Error> {
     let (a, b) = iter_two(args).ok_or_else(msg::expected_two_args)?;
     Ok(f(convert_expr(a, scope)?, convert_expr(b, scope)?))
 }
 
+/// Used for a scalar function that takes one argument and is rendered by the SQL dialect
+fn args_1_d(
+    args: Vec<Expr>,
+    scope: &mut Scope,
+    f: fn(&dyn Dialect, SqlExpr) -> SqlExpr,
+) -> Result<SqlExpr, Error> {
+    let arg0 = iter_one(args).ok_or_else(msg::expected_one_arg)?;
+    let a = convert_expr(arg0, scope)?;
+    Ok(f(scope.options.dialect.as_ref(), a))
+}
+
+/// Used for a scalar function that takes one or more arguments as a vector and is rendered by the
+/// SQL dialect
+fn args_v_d(
+    args: Vec<Expr>,
+    scope: &mut Scope,
+    f: fn(&dyn Dialect, Vec<SqlExpr>) -> SqlExpr,
+) -> Result<SqlExpr, Error> {
+    if args.is_empty() {
+        return Err(msg::expected_at_least_one_arg());
+    }
+    let mut sql_args = Vec::<SqlExpr>::new();
+    for arg in args {
+        sql_args.push(convert_expr(arg, scope)?);
+    }
+    Ok(f(scope.options.dialect.as_ref(), sql_args))
+}
+
+/// Used for a scalar function that takes two arguments and is rendered by the SQL dialect
+fn args_2_d(
+    args: Vec<Expr>,
+    scope: &mut Scope,
+    f: fn(&dyn Dialect, SqlExpr, SqlExpr) -> SqlExpr,
+) -> Result<SqlExpr, Error> {
+    let (a, b) = iter_two(args).ok_or_else(msg::expected_two_args)?;
+    let (a, b) = (convert_expr(a, scope)?, convert_expr(b, scope)?);
+    Ok(f(scope.options.dialect.as_ref(), a, b))
+}
+
+/// Used for a scalar function that takes two arguments, plus an optional third argument, and is
+/// rendered by the SQL dialect
+fn args_2_3_d(
+    args: Vec<Expr>,
+    scope: &mut Scope,
+    f: fn(&dyn Dialect, SqlExpr, SqlExpr, Option<SqlExpr>) -> SqlExpr,
+) -> Result<SqlExpr, Error> {
+    if args.len() < 2 || args.len() > 3 {
+        return Err(msg::expected_args_between(2, 3));
+    }
+    let mut iter = args.into_iter();
+    let a = convert_expr(iter.next().unwrap(), scope)?;
+    let b = convert_expr(iter.next().unwrap(), scope)?;
+    let c = iter.next().map(|c| convert_expr(c, scope)).transpose()?;
+    Ok(f(scope.options.dialect.as_ref(), a, b, c))
+}
+
+/// Used for a scalar function that takes three arguments
+fn args_3(
+    args: Vec<Expr>,
+    scope: &mut Scope,
+    f: fn(SqlExpr, SqlExpr, SqlExpr) -> SqlExpr,
+) -> Result<SqlExpr, Error> {
+    let (a, b, c) = args
+        .into_iter()
+        .collect_tuple()
+        .ok_or_else(|| msg::expected_args_between(3, 3))?;
+    Ok(f(
+        convert_expr(a, scope)?,
+        convert_expr(b, scope)?,
+        convert_expr(c, scope)?,
+    ))
+}
+
+/// Used for a scalar function that takes three arguments and is rendered by the SQL dialect
+fn args_3_d(
+    args: Vec<Expr>,
+    scope: &mut Scope,
+    f: fn(&dyn Dialect, SqlExpr, SqlExpr, SqlExpr) -> SqlExpr,
+) -> Result<SqlExpr, Error> {
+    let (a, b, c) = args
+        .into_iter()
+        .collect_tuple()
+        .ok_or_else(|| msg::expected_args_between(3, 3))?;
+    let a = convert_expr(a, scope)?;
+    let b = convert_expr(b, scope)?;
+    let c = convert_expr(c, scope)?;
+    Ok(f(scope.options.dialect.as_ref(), a, b, c))
+}
+
 pub fn get_standard_scalar_functions() -> FuncMap {
+    use {Accepts::*, ArithmeticOperator::*, Returns::*};
     #[rustfmt::skip]
-    let templates: [(&str, Func); 24] = [
-        ("abs",         |e, s| args_1(e, s, abs)),
-        ("age",         |e, s| args_1(e, s, |a| subtract(now(), a))),
-        ("ago",         |e, s| args_1(e, s, |a| subtract(now(), a))),
-        ("away",        |e, s| args_1(e, s, |a| add(now(), a))),
-        ("ceil",        |e, s| args_1(e, s, ceil)),
-        ("days",        |e, s| args_1(e, s, days)),
-        ("divide",      |e, s| args_2(e, s, divide)),
-        ("else",        |e, s| args_1(e, s, coalesce)),
-        ("floor",       |e, s| args_1(e, s, floor)),
-        ("hours",       |e, s| args_1(e, s, hours)),
-        ("keep_above",  |e, s| args_v(e, s, greatest)),
-        ("keep_below",  |e, s| args_v(e, s, least)),
-        ("length",      |e, s| args_1(e, s, char_length)),
-        ("lowercase",   |e, s| args_1(e, s, lower)),
-        ("max",         |e, s| args_v(e, s, greatest)),
-        ("min",         |e, s| args_v(e, s, least)),
-        ("minus",       |e, s| args_2(e, s, subtract)),
-        ("minutes",     |e, s| args_1(e, s, minutes)),
-        ("mod",         |e, s| args_2(e, s, modulo)),
-        ("not",         |e, s| args_1(e, s, not)),
-        ("plus",        |e, s| args_2(e, s, add)),
-        ("seconds",     |e, s| args_1(e, s, seconds)),
-        ("times",       |e, s| args_2(e, s, multiply)),
-        ("uppercase",   |e, s| args_1(e, s, upper)),
+    let templates: [(&str, Signature, Func); 45] = [
+        ("abs",           sig(&[Number], FirstArg),                         |e, s| args_1(e, s, abs)),
+        ("age",           sig(&[TemporalOrInterval], RelativeToNow),        |e, s| args_1(e, s, |a| subtract(now(), a))),
+        ("ago",           sig(&[TemporalOrInterval], RelativeToNow),        |e, s| args_1(e, s, |a| subtract(now(), a))),
+        ("away",          sig(&[TemporalOrInterval], RelativeToNow),        |e, s| args_1(e, s, |a| add(now(), a))),
+        ("ceil",          sig(&[Number], FirstArg),                         |e, s| args_1(e, s, ceil)),
+        ("concat",        sig(&[Any], Type(DataType::Text)),                |e, s| args_v_d(e, s, |d, a| d.concat(a))),
+        ("day_of_week",   sig(&[Temporal], Type(DataType::Integer)),        |e, s| args_1_d(e, s, |d, a| d.date_part(DatePart::DayOfWeek, a))),
+        ("days",          sig(&[Interval], Type(DataType::Decimal)),        |e, s| args_1(e, s, days)),
+        ("divide",        sig(&[Any], Arithmetic(Divide)),                  |e, s| args_2(e, s, divide)),
+        ("else",          sig(&[Any], CommonArg),                           |e, s| args_1(e, s, coalesce)),
+        ("floor",         sig(&[Number], FirstArg),                         |e, s| args_1(e, s, floor)),
+        ("hour",          sig(&[Temporal], Type(DataType::Integer)),        |e, s| args_1_d(e, s, |d, a| d.date_part(DatePart::Hour, a))),
+        ("hours",         sig(&[Interval], Type(DataType::Decimal)),        |e, s| args_1(e, s, hours)),
+        ("keep_above",    sig(&[Any], CommonArg),                           |e, s| args_v(e, s, greatest)),
+        ("keep_below",    sig(&[Any], CommonArg),                           |e, s| args_v(e, s, least)),
+        ("left",          sig(&[Text, Number], Type(DataType::Text)),       |e, s| args_2_d(e, s, |d, a, n| d.substring(a, one(), Some(n)))),
+        ("length",        sig(&[Text], Type(DataType::Integer)),            |e, s| args_1(e, s, char_length)),
+        ("lowercase",     sig(&[Text], Type(DataType::Text)),               |e, s| args_1(e, s, lower)),
+        ("max",           sig(&[Any], CommonArg),                           |e, s| args_v(e, s, greatest)),
+        ("min",           sig(&[Any], CommonArg),                           |e, s| args_v(e, s, least)),
+        ("minus",         sig(&[Any], Arithmetic(Subtract)),                |e, s| args_2(e, s, subtract)),
+        ("minutes",       sig(&[Interval], Type(DataType::Decimal)),        |e, s| args_1(e, s, minutes)),
+        ("mod",           sig(&[Number], CommonArg),                        |e, s| args_2(e, s, modulo)),
+        ("month",         sig(&[Temporal], Type(DataType::Integer)),        |e, s| args_1_d(e, s, |d, a| d.date_part(DatePart::Month, a))),
+        ("not",           sig(&[Boolean], Type(DataType::Boolean)),         |e, s| args_1(e, s, not)),
+        ("pad_end",       sig(&[Text, Number, Text], Type(DataType::Text)), |e, s| args_2_3_d(e, s, |d, a, n, f| d.pad(a, n, f, PadSide::End))),
+        ("pad_start",     sig(&[Text, Number, Text], Type(DataType::Text)), |e, s| args_2_3_d(e, s, |d, a, n, f| d.pad(a, n, f, PadSide::Start))),
+        ("plus",          sig(&[Any], Arithmetic(Add)),                     |e, s| args_2(e, s, add)),
+        ("quarter",       sig(&[Temporal], Type(DataType::Integer)),        |e, s| args_1_d(e, s, |d, a| d.date_part(DatePart::Quarter, a))),
+        ("replace",       sig(&[Text], Type(DataType::Text)),               |e, s| args_3(e, s, replace)),
+        ("seconds",       sig(&[Interval], Type(DataType::Decimal)),        |e, s| args_1(e, s, seconds)),
+        ("split_part",    sig(&[Text, Text, Number], Type(DataType::Text)), |e, s| args_3_d(e, s, |d, a, b, n| d.split_part(a, b, n))),
+        ("starts_with",   sig(&[Text], Type(DataType::Boolean)),            |e, s| args_2_d(e, s, |d, a, b| d.starts_with(a, b))),
+        ("substring",     sig(&[Text, Number], Type(DataType::Text)),       |e, s| args_2_3_d(e, s, |d, a, b, n| d.substring(a, b, n))),
+        ("times",         sig(&[Any], Arithmetic(Multiply)),                |e, s| args_2(e, s, multiply)),
+        ("trim",          sig(&[Text], Type(DataType::Text)),               |e, s| args_1(e, s, trim)),
+        ("trunc_day",     sig(&[Temporal], FirstArg),                       |e, s| args_1_d(e, s, |d, a| d.date_trunc(DateUnit::Day, a))),
+        ("trunc_month",   sig(&[Temporal], FirstArg),                       |e, s| args_1_d(e, s, |d, a| d.date_trunc(DateUnit::Month, a))),
+        ("trunc_quarter", sig(&[Temporal], FirstArg),                       |e, s| args_1_d(e, s, |d, a| d.date_trunc(DateUnit::Quarter, a))),
+        ("trunc_week",    sig(&[Temporal], FirstArg),                       |e, s| args_1_d(e, s, |d, a| d.date_trunc(DateUnit::Week, a))),
+        ("trunc_year",    sig(&[Temporal], FirstArg),                       |e, s| args_1_d(e, s, |d, a| d.date_trunc(DateUnit::Year, a))),
+        ("uppercase",     sig(&[Text], Type(DataType::Text)),               |e, s| args_1(e, s, upper)),
+        ("week",          sig(&[Temporal], Type(DataType::Integer)),        |e, s| args_1_d(e, s, |d, a| d.date_part(DatePart::Week, a))),
+        ("year",          sig(&[Temporal], Type(DataType::Integer)),        |e, s| args_1_d(e, s, |d, a| d.date_part(DatePart::Year, a))),
+        ("year_month",    sig(&[Temporal], Type(DataType::Text)),           |e, s| args_1_d(e, s, |d, a| d.year_month(a))),
     ];
     templates
         .into_iter()
-        .map(|(s, f)| (s.to_string(), f))
+        .map(|(name, signature, convert)| (name.to_string(), Function { convert, signature }))
         .collect()
 }
 
@@ -128,42 +416,216 @@ fn agg_1(
     args: Vec<Expr>,
     scope: &mut Scope,
     agg_wrapper: fn(SqlExpr) -> SqlExpr,
-) -> Result<SqlExpr, String> {
+) -> Result<SqlExpr, Error> {
     let arg0 = iter_one(args).ok_or_else(msg::expected_one_arg)?;
-    let Expr::Path(path_parts) = arg0 else {
+    aggregate_path(
+        arg0,
+        vec![],
+        scope,
+        Box::new(move |a, _| Ok(agg_wrapper(a))),
+    )
+}
+
+/// Used for an aggregate function that takes one argument and is rendered by the SQL dialect
+fn agg_1_d(
+    args: Vec<Expr>,
+    scope: &mut Scope,
+    f: fn(&dyn Dialect, SqlExpr) -> Result<SqlExpr, Error>,
+) -> Result<SqlExpr, Error> {
+    let arg0 = iter_one(args).ok_or_else(msg::expected_one_arg)?;
+    let dialect = scope.options.dialect.as_ref();
+    aggregate_path(arg0, vec![], scope, Box::new(move |a, _| f(dialect, a)))
+}
+
+/// Used for `%percentile`, which takes the aggregated value plus a fraction between 0 and 1
+fn agg_percentile(args: Vec<Expr>, scope: &mut Scope) -> Result<SqlExpr, Error> {
+    let (arg0, fraction) = iter_two(args).ok_or_else(msg::expected_two_args)?;
+    if let Expr::Number(n) = &fraction {
+        if !n.parse::<f64>().is_ok_and(|v| (0.0..=1.0).contains(&v)) {
+            return Err(msg::percentile_out_of_range(n));
+        }
+    }
+    let dialect = scope.options.dialect.as_ref();
+    let wrapper =
+        move |a, mut extra_args: Vec<SqlExpr>| dialect.percentile(a, extra_args.remove(0));
+    aggregate_path(arg0, vec![fraction], scope, Box::new(wrapper))
+}
+
+/// Used for an aggregate function that accepts up to `max_extra_args` optional arguments after the
+/// aggregated value, rendered by the SQL dialect
+fn agg_v_d(
+    args: Vec<Expr>,
+    scope: &mut Scope,
+    max_extra_args: usize,
+    f: fn(&dyn Dialect, SqlExpr, Vec<SqlExpr>) -> Result<SqlExpr, Error>,
+) -> Result<SqlExpr, Error> {
+    if args.is_empty() || args.len() > max_extra_args + 1 {
+        return Err(msg::expected_args_between(1, max_extra_args + 1));
+    }
+    let mut args_iter = args.into_iter();
+    let value_arg = args_iter.next().unwrap();
+    let extra_args = args_iter.collect();
+    let dialect = scope.options.dialect.as_ref();
+    let wrapper = move |a, extra_args| f(dialect, a, extra_args);
+    aggregate_path(value_arg, extra_args, scope, Box::new(wrapper))
+}
+
+/// `%list(separator order_by)`, where both arguments are optional
+fn list(dialect: &dyn Dialect, value: SqlExpr, args: Vec<SqlExpr>) -> Result<SqlExpr, Error> {
+    let mut args_iter = args.into_iter();
+    let separator = args_iter
+        .next()
+        .unwrap_or_else(|| SqlExpr::atom(dialect.quote_string(LIST_SEPARATOR)));
+    let order_by = args_iter.next();
+    Ok(dialect.list(value, separator, false, order_by))
+}
+
+/// `%list_distinct(separator)`, which sorts the values
+fn list_distinct(
+    dialect: &dyn Dialect,
+    value: SqlExpr,
+    args: Vec<SqlExpr>,
+) -> Result<SqlExpr, Error> {
+    let separator = args
+        .into_iter()
+        .next()
+        .unwrap_or_else(|| SqlExpr::atom(dialect.quote_string(LIST_SEPARATOR)));
+    let order_by = Some(value.clone());
+    Ok(dialect.list(value, separator, true, order_by))
+}
+
+/// `%array(order_by)`, where the argument is optional
+fn array(dialect: &dyn Dialect, value: SqlExpr, args: Vec<SqlExpr>) -> Result<SqlExpr, Error> {
+    dialect.array_agg(value, args.into_iter().next())
+}
+
+/// `%json(order_by)`, where the argument is optional
+fn json(dialect: &dyn Dialect, value: SqlExpr, args: Vec<SqlExpr>) -> Result<SqlExpr, Error> {
+    Ok(dialect.json_agg(value, args.into_iter().next()))
+}
+
+/// Aggregate the values at the end of a path to many, within a CTE
+fn aggregate_path<'a>(
+    arg: Expr,
+    extra_args: Vec<Expr>,
+    scope: &mut Scope<'a, '_>,
+    agg_wrapper: AggWrapper<'a>,
+) -> Result<SqlExpr, Error> {
+    let Expr::Path(parts) = arg else {
         return Err(msg::aggregate_fn_applied_to_a_non_path());
     };
+    let mut path_parts: Vec<PathPart> = scope.path_prefix.iter().cloned().chain(parts).collect();
+    if let Some(rest) = split_at_transitive(&mut path_parts) {
+        if rest.is_empty() {
+            return Err(msg::aggregate_fn_applied_to_a_path_without_a_column());
+        }
+        let template = AggregateExprTemplate::new(Expr::Path(rest), extra_args, agg_wrapper);
+        let template = TransitiveValueTemplate::Aggregate(template);
+        return convert_transitive(path_parts, template, scope);
+    }
+    let value_expr_opt = pop_path_expr(&mut path_parts);
     let clarified_path = clarify_path(path_parts, scope)?;
-    let Some(ClarifiedPathTail::ChainToMany((chain_to_many, column_name_opt))) = clarified_path.tail else {
+    let Some(ClarifiedPathTail::ChainToMany((chain_to_many, column_name_opt))) =
+        clarified_path.tail
+    else {
         return Err(msg::aggregate_fn_applied_to_path_to_one());
     };
-    let Some(column_name) = column_name_opt else {
-        return Err(msg::aggregate_fn_applied_to_a_path_without_a_column());
+    let value_expr = match (value_expr_opt, column_name_opt) {
+        (Some(expr), None) => expr,
+        (None, Some(column_name)) => Expr::Path(vec![PathPart::column(column_name)]),
+        (Some(_), Some(_)) => return Err(msg::path_expr_not_at_end()),
+        (None, None) => return Err(msg::aggregate_fn_applied_to_a_path_without_a_column()),
     };
-    let aggregate_expr_template = AggregateExprTemplate::new(column_name, agg_wrapper);
+    let aggregate_expr_template = AggregateExprTemplate::new(value_expr, extra_args, agg_wrapper);
     scope.join_chain_to_many(
         &clarified_path.head,
         chain_to_many,
-        Some(aggregate_expr_template),
+        Some(CteValueTemplate::Aggregate(aggregate_expr_template)),
         CtePurpose::AggregateValue,
     )
 }
 
 pub fn get_standard_aggregate_functions() -> FuncMap {
+    use {Accepts::*, Returns::*};
     #[rustfmt::skip]
-    let templates: [(&str, Func); 9] = [
-        ("all_true", |e, s| agg_1(e, s, bool_and)),
-        ("any_true", |e, s| agg_1(e, s, bool_or)),
-        ("avg",      |e, s| agg_1(e, s, avg)),
-        ("count",    |e, s| agg_1(e, s, count)),
-        ("distinct", |e, s| agg_1(e, s, count_distinct)),
-        ("list",     |e, s| agg_1(e, s, string_agg)),
-        ("max",      |e, s| agg_1(e, s, max)),
-        ("min",      |e, s| agg_1(e, s, min)),
-        ("sum",      |e, s| agg_1(e, s, sum)),
+    let templates: [(&str, Signature, Func); 17] = [
+        ("all_true",      sig(&[Boolean], Type(DataType::Boolean)), |e, s| agg_1(e, s, bool_and)),
+        ("any_true",      sig(&[Boolean], Type(DataType::Boolean)), |e, s| agg_1(e, s, bool_or)),
+        ("array",         sig(&[Any], Unknown),                     |e, s| agg_v_d(e, s, 1, array)),
+        ("avg",           sig(&[NumberOrInterval], Average),        |e, s| agg_1(e, s, avg)),
+        ("count",         sig(&[Any], Type(DataType::Integer)),     |e, s| agg_1(e, s, count)),
+        ("distinct",      sig(&[Any], Type(DataType::Integer)),     |e, s| agg_1(e, s, count_distinct)),
+        ("json",          sig(&[Any], Type(DataType::Json)),        |e, s| agg_v_d(e, s, 1, json)),
+        ("list",          sig(&[Any], Type(DataType::Text)),        |e, s| agg_v_d(e, s, 2, list)),
+        ("list_distinct", sig(&[Any], Type(DataType::Text)),        |e, s| agg_v_d(e, s, 1, list_distinct)),
+        ("max",           sig(&[Any], FirstArg),                    |e, s| agg_1(e, s, max)),
+        ("median",        sig(&[NumberOrInterval], Average),        |e, s| agg_1_d(e, s, |d, a| d.median(a))),
+        ("min",           sig(&[Any], FirstArg),                    |e, s| agg_1(e, s, min)),
+        ("mode",          sig(&[Any], FirstArg),                    |e, s| agg_1_d(e, s, |d, a| d.mode(a))),
+        ("percentile",    sig(&[NumberOrInterval], Average),        |e, s| agg_percentile(e, s)),
+        ("stddev",        sig(&[Number], Type(DataType::Decimal)),  |e, s| agg_1_d(e, s, |d, a| d.stddev(a))),
+        ("sum",           sig(&[NumberOrInterval], FirstArg),       |e, s| agg_1(e, s, sum)),
+        ("variance",      sig(&[Number], Type(DataType::Decimal)),  |e, s| agg_1_d(e, s, |d, a| d.variance(a))),
     ];
     templates
         .into_iter()
-        .map(|(s, f)| (s.to_string(), f))
+        .map(|(name, signature, convert)| (name.to_string(), Function { convert, signature }))
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{errors::msg, tests::get_test_resource, Compiler, Error, Options};
+
+    fn compile(input: &str) -> Result<String, Error> {
+        let schema = get_test_resource("issue_schema.json");
+        let compiler = Compiler::new(&schema, Options::default()).unwrap();
+        // Errors are compared without their locations, which are tested separately
+        compiler
+            .compile(input.to_owned())
+            .map_err(Error::without_location)
+    }
+
+    #[test]
+    fn test_percentile_out_of_range() {
+        assert_eq!(
+            compile("#issues $#comments.id%percentile(95)"),
+            Err(msg::percentile_out_of_range("95"))
+        );
+        assert!(compile("#issues $#comments.id%percentile(1)").is_ok());
+    }
+
+    #[test]
+    fn test_record_param_within_path_to_many() {
+        let definition = "@@assigned_to = @u; ++#assignments{user.username:@u}\n";
+        let with_definition = |query: &str| compile(&format!("{definition}{query}"));
+        assert_eq!(
+            with_definition("#issues @@assigned_to(author.username)"),
+            Err(msg::record_param_within_path_to_many("u"))
+        );
+        assert_eq!(
+            with_definition("#issues @@assigned_to(author.username|trim)"),
+            Err(msg::record_param_within_path_to_many("u"))
+        );
+        assert!(with_definition("#issues @@assigned_to(\"alice\")").is_ok());
+        assert!(with_definition("@name = \"alice\"\n#issues @@assigned_to(@name)").is_ok());
+        assert!(compile("@@by = @u; author.username:@u\n#issues @@by(title)").is_ok());
+    }
+
+    #[test]
+    fn test_recursive_path_errors() {
+        assert_eq!(
+            compile("#issues $author*.username"),
+            Err(msg::transitive_link_not_self_referential())
+        );
+        assert_eq!(
+            compile("#issues $#comments*.id%count"),
+            Err(msg::transitive_link_not_self_referential())
+        );
+        assert_eq!(
+            compile("#issues $#issues(duplicate_of)*.title"),
+            Err(msg::transitive_path_to_many_without_aggregate())
+        );
+        assert!(compile("#issues $#issues(duplicate_of)*.title%list").is_ok());
+    }
+}
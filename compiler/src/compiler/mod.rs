@@ -1,12 +1,20 @@
 mod comparisons;
 mod compiler;
 mod constants;
+mod definitions;
 mod expr;
 mod functions;
 mod join_tree;
+mod modules;
 mod paths;
 mod rendering;
 mod result_columns;
 mod scope;
+mod suggestions;
+mod type_inference;
+mod types;
+mod updates;
 
 pub use compiler::Compiler;
+pub use modules::ModuleResolver;
+pub use updates::CellUpdate;
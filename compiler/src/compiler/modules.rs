@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+
+use querydown_parser::{
+    ast::{Import, ImportItems, Statement},
+    parse_module,
+};
+
+use crate::{
+    errors::{msg, Error},
+    schema::Schema,
+    Options,
+};
+
+use super::definitions::{Definitions, Namespace, NamespaceId, RootImport, ScopedDefinition};
+
+/// Supplies the source code of modules which are imported via `<<<`.
+///
+/// Querydown doesn't assign any meaning to module paths. The host application decides where
+/// modules come from (e.g. files on disk, rows in a database, or an in-memory map).
+pub trait ModuleResolver {
+    /// Return the Querydown source code for the module at `path`, exactly as the path was written
+    /// in the import statement.
+    fn resolve(&self, path: &str) -> Result<String, String>;
+}
+
+/// Resolves modules from an in-memory map of module paths to source code.
+impl ModuleResolver for HashMap<String, String> {
+    fn resolve(&self, path: &str) -> Result<String, String> {
+        self.get(path)
+            .cloned()
+            .ok_or_else(|| msg::module_not_found(path).message)
+    }
+}
+
+/// Follows the imports of a query to gather all the definitions visible within it.
+pub struct ModuleLoader<'a> {
+    options: &'a Options,
+    schema: &'a Schema,
+    definitions: Definitions,
+    /// Paths of the modules which are currently being loaded, for detecting circular imports
+    stack: Vec<String>,
+    /// Exports of the modules which have already been loaded, keyed by module path
+    exports: HashMap<String, Namespace>,
+    /// Imports of the statements being loaded, excluding those within imported modules
+    root_imports: Vec<RootImport>,
+}
+
+impl<'a> ModuleLoader<'a> {
+    /// `definitions` are those which were loaded previously (e.g. the prelude).
+    pub fn new(options: &'a Options, schema: &'a Schema, definitions: Definitions) -> Self {
+        Self {
+            options,
+            schema,
+            definitions,
+            stack: vec![],
+            exports: HashMap::new(),
+            root_imports: vec![],
+        }
+    }
+
+    /// Load the statements of the query (or prelude) along with all modules they import. Any
+    /// exports from the top-level statements are ignored.
+    pub fn load(mut self, statements: Vec<Statement>) -> Result<Definitions, Error> {
+        let root = self.definitions.reserve_namespace();
+        self.load_namespace(root, statements)?;
+        self.definitions.set_root(root);
+        self.definitions.set_root_imports(self.root_imports);
+        Ok(self.definitions)
+    }
+
+    /// Fill the given namespace with the definitions visible within the statements, returning the
+    /// definitions which the statements export.
+    fn load_namespace(
+        &mut self,
+        namespace_id: NamespaceId,
+        statements: Vec<Statement>,
+    ) -> Result<Namespace, Error> {
+        let mut visible = Namespace::default();
+        let mut exported = Namespace::default();
+        for statement in statements {
+            let span = statement.span();
+            let locate = |e: Error| e.or_span(span);
+            let (scoped_definitions, is_exported) = match statement {
+                Statement::Import(import) => {
+                    let is_exported = import.is_exported;
+                    let scoped_definitions = self.import(&import).map_err(locate)?;
+                    if self.stack.is_empty() {
+                        let definitions = scoped_definitions
+                            .iter()
+                            .map(|scoped| (scoped.namespace, scoped.definition.name.clone()))
+                            .collect();
+                        self.root_imports.push(RootImport {
+                            import,
+                            definitions,
+                        });
+                    }
+                    (scoped_definitions, is_exported)
+                }
+                Statement::Definition(definition) => {
+                    let is_exported = definition.is_exported;
+                    let scoped = ScopedDefinition {
+                        definition,
+                        namespace: namespace_id,
+                    };
+                    (vec![scoped], is_exported)
+                }
+            };
+            for scoped in scoped_definitions {
+                if is_exported {
+                    exported
+                        .insert(scoped.clone(), self.options, self.schema)
+                        .map_err(locate)?;
+                }
+                visible
+                    .insert(scoped, self.options, self.schema)
+                    .map_err(locate)?;
+            }
+        }
+        self.definitions.set_namespace(namespace_id, visible);
+        Ok(exported)
+    }
+
+    fn import(&mut self, import: &Import) -> Result<Vec<ScopedDefinition>, Error> {
+        let options = self.options;
+        let schema = self.schema;
+        let exports = self.load_module(&import.module)?;
+        match &import.items {
+            ImportItems::All => Ok(exports.iter().cloned().collect()),
+            ImportItems::Named(items) => items
+                .iter()
+                .map(|item| {
+                    let mut scoped = exports
+                        .get(&item.name, options, schema)
+                        .ok_or_else(|| msg::missing_export(&item.name, &import.module))?
+                        .clone();
+                    if let Some(alias) = &item.alias {
+                        let name = scoped.definition.name.with_alias(alias.clone());
+                        scoped.definition.name = name;
+                    }
+                    Ok(scoped)
+                })
+                .collect(),
+        }
+    }
+
+    fn load_module(&mut self, path: &str) -> Result<&Namespace, Error> {
+        if self.stack.iter().any(|p| p == path) {
+            let mut paths = self.stack.clone();
+            paths.push(path.to_owned());
+            return Err(msg::circular_import(&paths));
+        }
+        if !self.exports.contains_key(path) {
+            let resolver = self
+                .options
+                .module_resolver
+                .as_ref()
+                .ok_or_else(msg::no_module_resolver)?;
+            let source = resolver.resolve(path).map_err(msg::unresolved_module)?;
+            let module = parse_module(&source).map_err(|e| msg::invalid_module(path, &e))?;
+            let namespace_id = self.definitions.reserve_namespace();
+            self.stack.push(path.to_owned());
+            // Locations within the module don't refer to the query, so errors are located at the
+            // importing statement instead
+            let result = self
+                .load_namespace(namespace_id, module.statements)
+                .map_err(Error::without_location);
+            self.stack.pop();
+            self.exports.insert(path.to_owned(), result?);
+        }
+        Ok(self.exports.get(path).unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{tests::get_test_resource, Compiler};
+
+    use super::*;
+
+    fn compile(modules: &[(&str, &str)], input: &str) -> Result<String, String> {
+        let modules: HashMap<String, String> = modules
+            .iter()
+            .map(|(path, source)| (path.to_string(), source.to_string()))
+            .collect();
+        let options = Options {
+            module_resolver: Some(Box::new(modules)),
+            ..Default::default()
+        };
+        let compiler = Compiler::new(&get_test_resource("issue_schema.json"), options).unwrap();
+        compiler.compile(input.to_owned()).map_err(|e| e.message)
+    }
+
+    #[test]
+    fn test_circular_import() {
+        let modules = [("a", "<<< b (*)"), ("b", "<<< c (*)"), ("c", "<<< a (*)")];
+        assert_eq!(
+            compile(&modules, "<<< a (*) #issues"),
+            Err("Circular module import: `a` -> `b` -> `c` -> `a`.".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_missing_export() {
+        let modules = [("a", "@foo = 1 === @bar = 2")];
+        assert_eq!(
+            compile(&modules, "<<< a (@foo) #issues"),
+            Err("Module `a` does not export `@foo`.".to_owned())
+        );
+        assert!(compile(&modules, "<<< a (@bar) #issues $@bar").is_ok());
+    }
+
+    #[test]
+    fn test_module_not_found() {
+        assert_eq!(
+            compile(&[], "<<< a (*) #issues"),
+            Err("Module `a` not found.".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_shared_module_is_not_circular() {
+        let modules = [
+            ("a", "=== @one = 1"),
+            ("b", "<<< a (*) === @two = @one + 1"),
+        ];
+        assert!(compile(&modules, "<<< a (*) <<< b (*) #issues $@one $@two").is_ok());
+    }
+
+    #[test]
+    fn test_private_definitions_are_resolved_within_their_module() {
+        let modules = [("a", "@rate = 2 === @@double = @x; @x * @rate")];
+        assert!(compile(&modules, "<<< a (@@double) #issues $id|double").is_ok());
+        assert_eq!(
+            compile(&modules, "<<< a (@@double) #issues $@rate"),
+            Err("Unknown variable `rate`.".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_circular_definition() {
+        assert_eq!(
+            compile(&[], "@a = @b + 1 @b = @a #issues $@a"),
+            Err("`@a` is defined in terms of itself.".to_owned())
+        );
+    }
+}
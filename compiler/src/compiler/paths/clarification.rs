@@ -1,14 +1,19 @@
+use std::cmp::Ordering;
+
 use itertools::Itertools;
 
-use querydown_parser::ast::{ConditionSet, PathPart, TableWithMany};
+use querydown_parser::ast::{ConditionSet, PathColumn, PathPart, TableWithMany};
 
 use crate::{
-    compiler::scope::Scope,
-    errors::msg,
+    compiler::{
+        scope::Scope,
+        suggestions::{get_related_tables, suggest_columns, suggest_path_parts, suggest_tables},
+    },
+    errors::{msg, Error},
     schema::{
         chain::{Chain, ChainIntersecting},
         links::{FilteredLink, Link, LinkToOne, MultiLink},
-        ChainSearchBase, Table,
+        ChainSearchBase, Schema, Table,
     },
 };
 
@@ -29,15 +34,17 @@ pub enum ClarifiedPathTail {
     ChainToMany((Chain<FilteredLink>, Option<String>)),
 }
 
-pub fn clarify_path(parts: Vec<PathPart>, scope: &Scope) -> Result<ClarifiedPath, String> {
+pub fn clarify_path(parts: Vec<PathPart>, scope: &Scope) -> Result<ClarifiedPath, Error> {
     let linked_path = build_linked_path(parts, scope)?;
     let chain_opt = linked_path.chain;
     let column_name_opt = linked_path.column;
     let Some(chain) = chain_opt else {
-        return column_name_opt.map(|column_name| ClarifiedPath {
-            head: None,
-            tail: Some(ClarifiedPathTail::Column(column_name)),
-        }).ok_or_else(msg::no_path_parts)
+        return column_name_opt
+            .map(|column_name| ClarifiedPath {
+                head: None,
+                tail: Some(ClarifiedPathTail::Column(column_name)),
+            })
+            .ok_or_else(msg::no_path_parts);
     };
     let mut head: Option<Chain<LinkToOne>> = None;
     let mut chain_to_many_opt: Option<Chain<FilteredLink>> = None;
@@ -86,19 +93,24 @@ struct LinkedPath {
     pub column: Option<String>,
 }
 
-fn build_linked_path(parts: Vec<PathPart>, scope: &Scope) -> Result<LinkedPath, String> {
+fn build_linked_path(parts: Vec<PathPart>, scope: &Scope) -> Result<LinkedPath, Error> {
     let mut current_table_opt: Option<&Table> = Some(scope.get_base_table());
     let mut chain_opt: Option<Chain<FilteredLink>> = None;
     let mut final_column_name: Option<String> = None;
     for part in parts {
         let current_table = current_table_opt.ok_or_else(msg::no_current_table)?;
         match part {
-            PathPart::Column(column_name) => {
+            PathPart::Column(PathColumn { name, span }) => {
                 let column_id = scope
                     .options
-                    .resolve_identifier(&current_table.column_lookup, &column_name)
+                    .resolve_identifier(&current_table.column_lookup, &name)
                     .copied()
-                    .ok_or_else(|| msg::col_not_in_table(&column_name, &current_table.name))?;
+                    .ok_or_else(|| {
+                        let suggestions = suggest_path_parts(&name, current_table, scope.schema);
+                        msg::col_not_in_table(&name, &current_table.name)
+                            .with_suggestions(&suggestions)
+                            .with_span(span)
+                    })?;
                 if let Some(link) = current_table.forward_links_to_one.get(&column_id).copied() {
                     current_table_opt = scope.schema.tables.get(&link.get_end().table_id);
                     let link = FilteredLink {
@@ -107,10 +119,13 @@ fn build_linked_path(parts: Vec<PathPart>, scope: &Scope) -> Result<LinkedPath,
                     };
                     chain_opt = match chain_opt {
                         Some(mut chain) => {
-                            chain.try_append(link)?;
+                            chain.try_append(link).map_err(msg::invalid_path)?;
                             Some(chain)
                         }
-                        None => Some(Chain::try_new(link, ChainIntersecting::Allowed)?),
+                        None => Some(
+                            Chain::try_new(link, ChainIntersecting::Allowed)
+                                .map_err(msg::invalid_path)?,
+                        ),
                     };
                 } else {
                     let column = current_table.columns.get(&column_id).unwrap();
@@ -121,17 +136,27 @@ fn build_linked_path(parts: Vec<PathPart>, scope: &Scope) -> Result<LinkedPath,
             PathPart::TableWithOne(_) => {
                 todo!()
             }
+            PathPart::Pick(pick) => {
+                return Err(msg::misplaced_pick().with_span(pick.span));
+            }
+            PathPart::Expr(_) => {
+                return Err(msg::path_expr_not_at_end());
+            }
+            PathPart::Transitive => {
+                return Err(msg::misplaced_transitive());
+            }
             PathPart::TableWithMany(mut table_with_many) => {
                 let base = ChainSearchBase::TableId(current_table.id);
                 let condition_set = std::mem::take(&mut table_with_many.condition_set);
                 let mut new_chain =
-                    get_chain_to_table_with_many(base, &table_with_many, None, scope)?;
+                    get_chain_to_table_with_many(base, &table_with_many, None, scope)
+                        .map_err(|e| e.or_span(table_with_many.span))?;
                 new_chain.set_final_condition_set(condition_set);
                 new_chain.allow_intersecting();
                 current_table_opt = scope.schema.tables.get(&new_chain.get_ending_table_id());
                 chain_opt = match chain_opt {
                     Some(mut chain) => {
-                        chain.try_connect(new_chain)?;
+                        chain.try_connect(new_chain).map_err(msg::invalid_path)?;
                         Some(chain)
                     }
                     None => Some(new_chain),
@@ -151,15 +176,20 @@ fn get_chain_to_table_with_many(
     target: &TableWithMany,
     max_chain_length: Option<usize>,
     scope: &Scope,
-) -> Result<Chain<FilteredLink>, String> {
+) -> Result<Chain<FilteredLink>, Error> {
     let max_chain_len = max_chain_length.unwrap_or(usize::MAX);
-    if base.len() >= max_chain_len {
-        // I don't think this should never happen, but I put it here just in case
-        return Err("Chain search base already too long before searching.".to_string());
-    }
-    let target_table = scope
-        .get_table_by_name(&target.table)
-        .ok_or_else(|| "Target table not found.".to_string())?;
+    let target_table = scope.get_table_by_name(&target.table).ok_or_else(|| {
+        let error = msg::unknown_table(&target.table);
+        let suggestions = suggest_tables(&target.table, scope.schema);
+        if !suggestions.is_empty() {
+            return error.with_suggestions(&suggestions);
+        }
+        let Some(base_table) = scope.schema.tables.get(&base.get_base_table_id()) else {
+            return error;
+        };
+        let related = get_related_tables(base_table, scope.schema);
+        error.with_optional_note(msg::related_tables(&base_table.name, &related))
+    })?;
 
     // Success case where the base is already at the target
     if base.get_ending_table_id() == Some(target_table.id) {
@@ -168,16 +198,51 @@ fn get_chain_to_table_with_many(
         }
     }
 
+    // A chain which ties with the shortest one found so far can still be found above, but the base
+    // can't be extended any further
+    if base.len() >= max_chain_len {
+        return Err(msg::no_path_found(&describe_table_with_many(target)));
+    }
+
     let base_table = scope
         .schema
         .tables
         .get(&base.get_base_table_id())
-        .ok_or_else(|| "Base table not found.".to_string())?;
+        .ok_or_else(|| msg::internal("Base table not found."))?;
 
-    // Success case where we can directly find the target from the base
+    // Success case where we can directly find the target from the base. A linking column
+    // chooses between multiple direct links.
     if let Some(links) = base_table.reverse_links_to_many.get(&target_table.id) {
-        if let Ok(link) = links.iter().exactly_one() {
+        let linking_column_id = match &target.linking_column {
+            Some(column_name) => Some(
+                *scope
+                    .options
+                    .resolve_identifier(&target_table.column_lookup, column_name)
+                    .ok_or_else(|| {
+                        msg::col_not_in_table(column_name, &target_table.name)
+                            .with_suggestions(&suggest_columns(column_name, target_table))
+                    })?,
+            ),
+            None => None,
+        };
+        let candidates = links
+            .iter()
+            .filter(|link| linking_column_id.is_none_or(|id| link.get_base().column_id == id))
+            .collect_vec();
+        if let (Some(column_name), true) = (&target.linking_column, candidates.is_empty()) {
+            return Err(msg::linking_column_not_a_link(
+                column_name,
+                &target_table.name,
+            ));
+        }
+        if let Ok(link) = candidates.into_iter().exactly_one() {
             let multi_link = MultiLink::ReverseLinkToMany(*link);
+            let is_self_referential = link.get_start().table_id == link.get_end().table_id;
+            if is_self_referential && matches!(base, ChainSearchBase::TableId(_)) {
+                let chain = Chain::try_new(multi_link, ChainIntersecting::Allowed)
+                    .map_err(msg::invalid_path)?;
+                return Ok(Chain::<FilteredLink>::from(chain));
+            }
             if let Ok(multi_link_chain) = base.clone().try_append_into_chain(multi_link) {
                 return Ok(Chain::<FilteredLink>::from(multi_link_chain));
             }
@@ -185,21 +250,25 @@ fn get_chain_to_table_with_many(
     }
 
     if base.len() + 1 >= max_chain_len {
-        return Err("Max chain length reached.".to_string());
+        return Err(msg::no_path_found(&describe_table_with_many(target)));
     }
 
     let get_transitive_chain = |link: MultiLink, max: usize| {
-        let chain = base.clone().try_append_into_chain(link)?;
+        let chain = base
+            .clone()
+            .try_append_into_chain(link)
+            .map_err(msg::invalid_path)?;
         get_chain_to_table_with_many(ChainSearchBase::Chain(chain), target, Some(max), scope)
     };
     enum ChainSearchResult {
         Winner(Chain<FilteredLink>),
-        Tie(usize),
+        /// All the chains which tie for being the shortest, of which there are at least two
+        Tie(Vec<Chain<FilteredLink>>),
         NoneFound,
     }
     let get_max_len = |result: &ChainSearchResult| match result {
         ChainSearchResult::Winner(chain) => chain.len(),
-        ChainSearchResult::Tie(len) => *len,
+        ChainSearchResult::Tie(chains) => chains[0].len(),
         ChainSearchResult::NoneFound => max_chain_len,
     };
 
@@ -207,20 +276,93 @@ fn get_chain_to_table_with_many(
     let mut result = ChainSearchResult::NoneFound;
     for link in base_table.get_links() {
         let max_len = get_max_len(&result);
-        let Ok(chain) = get_transitive_chain(link, max_len) else {continue};
-        if let ChainSearchResult::Winner(winner) = &result {
-            if chain.len() == winner.len() {
-                result = ChainSearchResult::Tie(chain.len());
-            } else if chain.len() < winner.len() {
-                result = ChainSearchResult::Winner(chain);
-            }
-        } else {
-            result = ChainSearchResult::Winner(chain);
-        }
+        let Ok(chain) = get_transitive_chain(link, max_len) else {
+            continue;
+        };
+        result = match result {
+            ChainSearchResult::NoneFound => ChainSearchResult::Winner(chain),
+            ChainSearchResult::Winner(winner) => match chain.len().cmp(&winner.len()) {
+                Ordering::Less => ChainSearchResult::Winner(chain),
+                Ordering::Equal => ChainSearchResult::Tie(vec![winner, chain]),
+                Ordering::Greater => ChainSearchResult::Winner(winner),
+            },
+            ChainSearchResult::Tie(mut chains) => match chain.len().cmp(&chains[0].len()) {
+                Ordering::Less => ChainSearchResult::Winner(chain),
+                Ordering::Equal => {
+                    chains.push(chain);
+                    ChainSearchResult::Tie(chains)
+                }
+                Ordering::Greater => ChainSearchResult::Tie(chains),
+            },
+        };
     }
     match result {
         ChainSearchResult::Winner(chain) => Ok(chain),
-        ChainSearchResult::Tie(_) => Err("Two chains tie for the same length".to_string()),
-        ChainSearchResult::NoneFound => Err("No chain found.".to_string()),
+        ChainSearchResult::Tie(chains) => {
+            let paths = chains
+                .iter()
+                .map(|chain| describe_chain(&chain.get_links()[base.len()..], scope.schema))
+                .sorted()
+                .dedup()
+                .collect();
+            Err(msg::ambiguous_path(
+                &describe_table_with_many(target),
+                paths,
+            ))
+        }
+        ChainSearchResult::NoneFound => {
+            let related = get_related_tables(base_table, scope.schema);
+            Err(msg::no_path_found(&describe_table_with_many(target))
+                .with_optional_note(msg::related_tables(&base_table.name, &related)))
+        }
+    }
+}
+
+/// The table with many as it would be written in querydown code, without its condition set
+fn describe_table_with_many(target: &TableWithMany) -> String {
+    match &target.linking_column {
+        Some(column) => format!("#{}({})", target.table, column),
+        None => format!("#{}", target.table),
+    }
+}
+
+/// Write the links of a chain as querydown code, so that the code follows exactly those links
+fn describe_chain(links: &[FilteredLink], schema: &Schema) -> String {
+    links
+        .iter()
+        .map(|filtered_link| describe_link(&filtered_link.link, schema))
+        .join(".")
+}
+
+/// Write one link as a path part. The `unwrap` calls are safe because we know all links within the
+/// schema are valid.
+fn describe_link(link: &MultiLink, schema: &Schema) -> String {
+    let base = link.get_base();
+    if let MultiLink::ForwardLinkToOne(_) = link {
+        return schema.get_referenced_column_name(&base);
+    }
+    let start_table = schema.tables.get(&link.get_start().table_id).unwrap();
+    let link_count = [
+        start_table
+            .reverse_links_to_many
+            .get(&base.table_id)
+            .map(Vec::len),
+        start_table
+            .reverse_links_to_one
+            .get(&base.table_id)
+            .map(Vec::len),
+    ]
+    .into_iter()
+    .flatten()
+    .sum::<usize>();
+    let table_name = &schema.tables.get(&base.table_id).unwrap().name;
+    if link_count > 1 {
+        format!(
+            "#{}({})",
+            table_name,
+            schema.get_referenced_column_name(&base)
+        )
+    } else {
+        format!("#{}", table_name)
     }
 }
@@ -1,11 +1,14 @@
+use querydown_parser::ast::{Expr, NullsSort, Pick, PickPosition, SortDirection};
+
 use crate::{
     compiler::{
         constants::{CTE_PK_COLUMN_ALIAS, CTE_VALUE_COLUMN_PREFIX},
-        expr::convert_condition_set,
+        expr::{convert_condition_set, convert_expr},
         join_tree::make_join_from_link,
         scope::Scope,
     },
-    errors::msg,
+    errors::Error,
+    schema::Table,
     schema::{
         chain::Chain,
         links::{FilteredLink, Link},
@@ -13,7 +16,7 @@ use crate::{
     sql::expr::build,
     sql::{
         expr::{build::cmp, SqlExpr},
-        tree::{Column, CtePurpose, JoinType, Select},
+        tree::{Column, CtePurpose, Join, JoinType, Select, SortEntry},
     },
 };
 
@@ -22,66 +25,109 @@ pub struct ValueViaCte {
     pub value_alias: String,
 }
 
-pub struct AggregateExprTemplate {
-    column_name: String,
-    /// This is a function that accepts a table.column expression and returns a wrapped expression
-    /// that is used as the aggregate expression. E.g. it might be:
+/// Describes how a CTE which supplies a value to the query obtains that value from the related
+/// records
+pub enum CteValueTemplate<'a> {
+    Aggregate(AggregateExprTemplate<'a>),
+    Pick(PickTemplate),
+}
+
+pub struct AggregateExprTemplate<'a> {
+    /// The value to aggregate. It's converted within the scope of the table at the end of the
+    /// CTE's chain, e.g. `body|length` for `#comments.(body|length)%avg`.
+    value: Expr,
+    /// Arguments to the aggregate function beyond the aggregated value, e.g. the fraction in
+    /// `%percentile(0.95)`. Like the value, these are converted within the scope of the table at
+    /// the end of the CTE's chain, so they can refer to its columns.
+    extra_args: Vec<Expr>,
+    /// This is a function that accepts the converted value (along with the extra args) and returns
+    /// a wrapped expression that is used as the aggregate expression. E.g. it might be:
     ///
     /// ```rs
-    /// fn max(a: SqlExpr) -> SqlExpr {
-    ///     SqlExpr::atom(format!("max({})", a))
-    /// }
+    /// |a: SqlExpr, _| Ok(SqlExpr::atom(format!("max({})", a)))
     /// ```
     ///
-    /// When this AggregateExprTemplate instance is rendered within a CTE, the column_name is
-    /// resolved to a table.column expression, and then the agg_wrapper is applied to that
-    /// expression.
-    agg_wrapper: fn(SqlExpr) -> SqlExpr,
+    /// When this AggregateExprTemplate instance is rendered within a CTE, the value is converted to
+    /// an SQL expression, and then the agg_wrapper is applied to that expression.
+    agg_wrapper: AggWrapper<'a>,
 }
 
-impl AggregateExprTemplate {
-    pub fn new(column_name: String, agg_wrapper: fn(SqlExpr) -> SqlExpr) -> Self {
+pub type AggWrapper<'a> = Box<dyn FnOnce(SqlExpr, Vec<SqlExpr>) -> Result<SqlExpr, Error> + 'a>;
+
+impl<'a> AggregateExprTemplate<'a> {
+    pub fn new(value: Expr, extra_args: Vec<Expr>, agg_wrapper: AggWrapper<'a>) -> Self {
         Self {
-            column_name,
+            value,
+            extra_args,
             agg_wrapper,
         }
     }
+
+    /// Convert the value and extra args within `scope`, and wrap them in the aggregate expression
+    pub(super) fn convert(self, scope: &mut Scope) -> Result<SqlExpr, Error> {
+        let value = convert_expr(self.value, scope)?;
+        let extra_args = self
+            .extra_args
+            .into_iter()
+            .map(|arg| convert_expr(arg, scope))
+            .collect::<Result<Vec<_>, _>>()?;
+        (self.agg_wrapper)(value, extra_args)
+    }
 }
 
-pub fn build_cte_select(
+/// Selects a value from one related record, chosen via `%first` or `%last`
+pub struct PickTemplate {
+    /// The value to select from the picked record. Like the pick's ordering, it's converted within
+    /// the scope of the table at the end of the CTE's chain.
+    value: Expr,
+    pick: Pick,
+}
+
+impl PickTemplate {
+    pub fn new(value: Expr, pick: Pick) -> Self {
+        Self { value, pick }
+    }
+}
+
+pub fn build_cte_select<'a>(
     chain: Chain<FilteredLink>,
-    aggregate_expr_template_opt: Option<AggregateExprTemplate>,
-    parent_scope: &Scope,
+    value_template_opt: Option<CteValueTemplate<'a>>,
+    parent_scope: &Scope<'a, '_>,
     purpose: CtePurpose,
-) -> Result<ValueViaCte, String> {
+) -> Result<ValueViaCte, Error> {
     let schema = parent_scope.schema;
-    let mut links_iter = chain.into_iter();
-    let first_link = links_iter.next().unwrap();
+    let link_count = chain.len();
+    let mut links_iter = chain.into_iter().enumerate();
+    let (_, first_link) = links_iter.next().unwrap();
     let end = first_link.get_end();
     let base_table = schema.tables.get(&end.table_id).unwrap();
     let base_column = base_table.columns.get(&end.column_id).unwrap();
+    let start = first_link.get_start();
+    let outer_table = schema.tables.get(&start.table_id).unwrap();
+    let outer_column = outer_table.columns.get(&start.column_id).unwrap();
     let mut cte_scope = parent_scope.spawn(&base_table);
+    // Reserve the base table alias so that other joins within the CTE avoid it
+    cte_scope.get_alias(&base_table.name);
+    cte_scope.set_outer_table(outer_table);
     let mut select = Select::from(cte_scope.get_base_table().name.clone());
     let pk_expr = cte_scope.table_column_expr(&base_table.name, &base_column.name);
     select.grouping.push(pk_expr.clone());
     let pr_expr_col = Column::new(pk_expr, Some(CTE_PK_COLUMN_ALIAS.to_owned()));
     select.columns.push(pr_expr_col);
-    select.conditions = convert_condition_set(first_link.condition_set.clone(), &mut cte_scope)?;
+    let first_conditions = convert_condition_set(first_link.condition_set.clone(), &mut cte_scope)?;
+    select.conditions = adjust_conditions(first_conditions, link_count == 1, purpose);
+    let (joins, ctes) = cte_scope.decompose_join_tree();
+    select.joins.extend(joins);
+    select.ctes.extend(ctes);
     let mut starting_alias = base_table.name.clone();
     let mut ending_table = schema.tables.get(&first_link.get_end().table_id).unwrap();
-    for link in links_iter {
+    for (index, link) in links_iter {
         ending_table = schema.tables.get(&link.get_end().table_id).unwrap();
         let ideal_ending_alias = ending_table.name.as_str();
         let ending_alias = cte_scope.get_alias(ideal_ending_alias);
         let join_type = JoinType::Inner;
         let link_start = link.get_start();
         let link_end = link.get_end();
-        if !link.condition_set.is_empty() {
-            let link_table = schema.tables.get(&link.get_end().table_id).unwrap();
-            let mut link_scope = cte_scope.spawn(&link_table);
-            let converted = convert_condition_set(link.condition_set, &mut link_scope)?;
-            select.conditions = cmp::and([select.conditions, converted]);
-        }
         let join = make_join_from_link(
             link_start,
             &starting_alias,
@@ -91,21 +137,62 @@ pub fn build_cte_select(
             &cte_scope,
         );
         select.joins.push(join);
+        if !link.condition_set.is_empty() {
+            let converted = convert_at_link_end(
+                &mut select,
+                &mut cte_scope,
+                ending_table,
+                &ending_alias,
+                |scope| convert_condition_set(link.condition_set, scope),
+            )?;
+            let converted = adjust_conditions(converted, index == link_count - 1, purpose);
+            select.conditions = cmp::and([select.conditions, converted]);
+        }
         starting_alias = ending_alias;
     }
 
-    if purpose == CtePurpose::AggregateValue {
-        let value_expr = match aggregate_expr_template_opt {
-            Some(template) => {
-                let column_name = template.column_name;
-                let column_id = cte_scope
-                    .options
-                    .resolve_identifier(&ending_table.column_lookup, &column_name)
-                    .ok_or_else(|| msg::col_not_in_table(&column_name, &ending_table.name))?;
-                let column = ending_table.columns.get(column_id).unwrap();
-                let reference = cte_scope.table_column_expr(&ending_table.name, &column.name);
-                let wrapper = template.agg_wrapper;
-                wrapper(reference)
+    let value_alias = if purpose == CtePurpose::AggregateValue {
+        let value_expr = match value_template_opt {
+            Some(CteValueTemplate::Aggregate(template)) => convert_at_link_end(
+                &mut select,
+                &mut cte_scope,
+                ending_table,
+                &starting_alias,
+                |scope| template.convert(scope),
+            )?,
+            Some(CteValueTemplate::Pick(template)) => {
+                let (value, order_by) = convert_at_link_end(
+                    &mut select,
+                    &mut cte_scope,
+                    ending_table,
+                    &starting_alias,
+                    |scope| {
+                        let value = convert_expr(template.value, scope)?;
+                        let order_by = convert_expr(*template.pick.order_by, scope)?;
+                        Ok((value, order_by))
+                    },
+                )?;
+                let direction = match template.pick.position {
+                    PickPosition::First => SortDirection::Asc,
+                    PickPosition::Last => SortDirection::Desc,
+                };
+                // Instead of grouping by the pk, we select only the first row for each pk
+                let pk_expr = select.grouping.remove(0);
+                let dialect = &cte_scope.options.dialect;
+                select.distinct_on = Some(dialect.select_distinct_on(vec![pk_expr.clone()])?);
+                select.sorting = vec![
+                    SortEntry {
+                        expr: pk_expr,
+                        direction: SortDirection::Asc,
+                        nulls_sort: NullsSort::Last,
+                    },
+                    SortEntry {
+                        expr: order_by,
+                        direction,
+                        nulls_sort: NullsSort::Last,
+                    },
+                ];
+                value
             }
             None => build::agg::count_star(),
         };
@@ -113,13 +200,80 @@ pub fn build_cte_select(
         select
             .columns
             .push(Column::new(value_expr, Some(value_alias.clone())));
-        return Ok(ValueViaCte {
-            select,
-            value_alias,
-        });
-    }
+        value_alias
+    } else {
+        CTE_PK_COLUMN_ALIAS.to_owned()
+    };
+    let linked_expr = cte_scope.table_column_expr(&base_table.name, &base_column.name);
+    join_outer_record(
+        &mut select,
+        &mut cte_scope,
+        outer_table,
+        &outer_column.name,
+        linked_expr,
+    );
     Ok(ValueViaCte {
         select,
-        value_alias: CTE_PK_COLUMN_ALIAS.to_owned(),
+        value_alias,
     })
 }
+
+/// Convert expressions within the scope of the table at the end of one of the CTE's links (aliased
+/// as `ending_alias`), adding any joins (or nested CTEs) which they require to the CTE's select.
+pub(super) fn convert_at_link_end<'a, T>(
+    select: &mut Select,
+    cte_scope: &mut Scope<'a, '_>,
+    ending_table: &'a Table,
+    ending_alias: &str,
+    f: impl FnOnce(&mut Scope) -> Result<T, Error>,
+) -> Result<T, Error> {
+    let state = cte_scope.take_cte_state();
+    let (result, state) = {
+        let mut link_scope = cte_scope.spawn_with_alias(ending_table, ending_alias.to_owned());
+        link_scope.put_cte_state(state);
+        let result = f(&mut link_scope);
+        let (joins, ctes) = link_scope.decompose_join_tree();
+        select.joins.extend(joins);
+        select.ctes.extend(ctes);
+        (result, link_scope.take_cte_state())
+    };
+    cte_scope.put_cte_state(state);
+    result
+}
+
+/// When `&` was used within the CTE, join the outer record (and any tables reached from it) via the
+/// column which links it to `linked_expr`.
+pub(super) fn join_outer_record(
+    select: &mut Select,
+    cte_scope: &mut Scope,
+    outer_table: &Table,
+    outer_column_name: &str,
+    linked_expr: SqlExpr,
+) {
+    let Some(join_tree) = cte_scope.take_outer_join_tree() else {
+        return;
+    };
+    let outer_alias = join_tree.get_alias().to_owned();
+    select.joins.push(Join {
+        table: outer_table.name.clone(),
+        alias: outer_alias.clone(),
+        conditions: cmp::eq(
+            cte_scope.table_column_expr(&outer_alias, outer_column_name),
+            linked_expr,
+        ),
+        join_type: JoinType::Inner,
+    });
+    let (joins, ctes) = join_tree.decompose(cte_scope);
+    select.joins.extend(joins);
+    select.ctes.extend(ctes);
+}
+
+/// For a `Counterexample` CTE, negate the conditions on the final link so that the CTE finds the
+/// related records which fail to satisfy them.
+fn adjust_conditions(conditions: SqlExpr, is_final_link: bool, purpose: CtePurpose) -> SqlExpr {
+    if is_final_link && purpose == CtePurpose::Counterexample && !conditions.is_empty() {
+        cmp::is_not_true(conditions)
+    } else {
+        conditions
+    }
+}
@@ -1,5 +1,7 @@
 mod clarification;
 mod ctes;
+mod transitive;
 
 pub use clarification::*;
 pub use ctes::*;
+pub use transitive::*;
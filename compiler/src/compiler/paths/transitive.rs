@@ -0,0 +1,232 @@
+use querydown_parser::ast::{ConditionSet, Expr, NullsSort, SortDirection};
+
+use crate::{
+    compiler::{
+        constants::{
+            CLOSURE_CTE_ALIAS, CLOSURE_DEPTH_COLUMN_ALIAS, CLOSURE_RELATED_COLUMN_ALIAS,
+            CLOSURE_VISITED_COLUMN_ALIAS, CTE_PK_COLUMN_ALIAS, CTE_VALUE_COLUMN_PREFIX,
+        },
+        expr::{convert_condition_set, convert_expr},
+        scope::Scope,
+    },
+    errors::{msg, Error},
+    schema::{
+        links::{Link, LinkDirection},
+        ColumnId, TableId,
+    },
+    sql::{
+        expr::build::{cmp, cond, math},
+        tree::{Column, Cte, CtePurpose, Join, JoinType, Select, SortEntry, SqlExpr},
+    },
+};
+
+use super::{convert_at_link_end, join_outer_record, AggregateExprTemplate, ValueViaCte};
+
+/// A self-referential link which is followed repeatedly, e.g. the `duplicate_of*` in
+/// `duplicate_of*.title`
+pub struct TransitiveLink {
+    table_id: TableId,
+    /// The column which holds the foreign key
+    fk_column_id: ColumnId,
+    /// The column which the foreign key references. The CTE is joined to the query on this column.
+    referenced_column_id: ColumnId,
+    /// `Forward` reaches the ancestors of a record, and `Reverse` reaches its descendants
+    direction: LinkDirection,
+    /// Conditions on the records reached, e.g. from `#issues(duplicate_of){status:"open"}*`. The
+    /// records which fail them are still followed to reach others.
+    condition_set: ConditionSet,
+}
+
+impl TransitiveLink {
+    pub fn try_new(link: &impl Link, condition_set: ConditionSet) -> Result<Self, Error> {
+        let base = link.get_base();
+        let target = link.get_target();
+        if base.table_id != target.table_id {
+            return Err(msg::transitive_link_not_self_referential());
+        }
+        Ok(Self {
+            table_id: base.table_id,
+            fk_column_id: base.column_id,
+            referenced_column_id: target.column_id,
+            direction: link.get_direction(),
+            condition_set,
+        })
+    }
+
+    pub fn get_table_id(&self) -> TableId {
+        self.table_id
+    }
+
+    pub fn get_referenced_column_id(&self) -> ColumnId {
+        self.referenced_column_id
+    }
+}
+
+/// Describes how the CTE for a recursive path obtains its value from the records reached
+pub enum TransitiveValueTemplate<'a> {
+    Aggregate(AggregateExprTemplate<'a>),
+    /// A value from the farthest record reached, e.g. the root of a tree of duplicates for
+    /// `duplicate_of*.title`
+    Farthest(Expr),
+}
+
+/// Build a CTE which supplies a value from the records reachable via a transitive link. The CTE
+/// selects from a recursive closure CTE, which pairs each record with every record reachable from
+/// it.
+pub fn build_transitive_cte_select<'a>(
+    link: TransitiveLink,
+    value_template: TransitiveValueTemplate<'a>,
+    parent_scope: &Scope<'a, '_>,
+) -> Result<ValueViaCte, Error> {
+    let table = parent_scope.schema.tables.get(&link.table_id).unwrap();
+    let fk_column = &table.columns.get(&link.fk_column_id).unwrap().name;
+    let referenced_column = &table.columns.get(&link.referenced_column_id).unwrap().name;
+    // A record identified by `from_column` reaches the record identified by `to_column` in one step
+    let (from_column, to_column) = match link.direction {
+        LinkDirection::Forward => (referenced_column, fk_column),
+        LinkDirection::Reverse => (fk_column, referenced_column),
+    };
+    let mut cte_scope = parent_scope.spawn(table);
+    let closure_alias = cte_scope.get_alias(CLOSURE_CTE_ALIAS);
+    let closure = build_closure(
+        &cte_scope,
+        &table.name,
+        &closure_alias,
+        (from_column, to_column),
+        fk_column,
+    )?;
+    let alias = cte_scope.get_alias(&table.name);
+    cte_scope.set_outer_table(table);
+    let pk_expr = cte_scope.table_column_expr(&closure_alias, CTE_PK_COLUMN_ALIAS);
+    let mut select = Select::from(closure_alias.clone());
+    select.ctes.push(closure);
+    select.columns.push(Column::new(
+        pk_expr.clone(),
+        Some(CTE_PK_COLUMN_ALIAS.to_owned()),
+    ));
+    select.joins.push(Join {
+        table: table.name.clone(),
+        alias: alias.clone(),
+        conditions: cmp::eq(
+            cte_scope.table_column_expr(&alias, referenced_column),
+            cte_scope.table_column_expr(&closure_alias, CLOSURE_RELATED_COLUMN_ALIAS),
+        ),
+        join_type: JoinType::Inner,
+    });
+    select.conditions = convert_at_link_end(&mut select, &mut cte_scope, table, &alias, |scope| {
+        convert_condition_set(link.condition_set, scope)
+    })?;
+    let value_expr = match value_template {
+        TransitiveValueTemplate::Aggregate(template) => {
+            select.grouping.push(pk_expr.clone());
+            convert_at_link_end(&mut select, &mut cte_scope, table, &alias, |scope| {
+                template.convert(scope)
+            })?
+        }
+        TransitiveValueTemplate::Farthest(value) => {
+            let value = convert_at_link_end(&mut select, &mut cte_scope, table, &alias, |scope| {
+                convert_expr(value, scope)
+            })?;
+            // Select only the deepest row for each pk
+            let dialect = &cte_scope.options.dialect;
+            select.distinct_on = Some(dialect.select_distinct_on(vec![pk_expr.clone()])?);
+            select.sorting = vec![
+                SortEntry {
+                    expr: pk_expr.clone(),
+                    direction: SortDirection::Asc,
+                    nulls_sort: NullsSort::Last,
+                },
+                SortEntry {
+                    expr: cte_scope.table_column_expr(&closure_alias, CLOSURE_DEPTH_COLUMN_ALIAS),
+                    direction: SortDirection::Desc,
+                    nulls_sort: NullsSort::Last,
+                },
+            ];
+            value
+        }
+    };
+    let value_alias = format!("{}{}", CTE_VALUE_COLUMN_PREFIX, 1);
+    select
+        .columns
+        .push(Column::new(value_expr, Some(value_alias.clone())));
+    join_outer_record(
+        &mut select,
+        &mut cte_scope,
+        table,
+        referenced_column,
+        pk_expr,
+    );
+    Ok(ValueViaCte {
+        select,
+        value_alias,
+    })
+}
+
+/// Build the recursive CTE which pairs each record (as `pk`) with every record reachable from it
+/// (as `related`). Each row also tracks its depth, along with the records visited so far so that
+/// cycles within the data end the recursion.
+fn build_closure(
+    scope: &Scope,
+    table_name: &str,
+    alias: &str,
+    (from_column, to_column): (&str, &str),
+    fk_column: &str,
+) -> Result<Cte, Error> {
+    let dialect = &scope.options.dialect;
+    let col = |column_name: &str| scope.table_column_expr(table_name, column_name);
+    let closure_col = |column_name: &str| scope.table_column_expr(alias, column_name);
+
+    let mut select = Select::from(table_name.to_owned());
+    select.columns = vec![
+        Column::new(col(from_column), Some(CTE_PK_COLUMN_ALIAS.to_owned())),
+        Column::new(
+            col(to_column),
+            Some(CLOSURE_RELATED_COLUMN_ALIAS.to_owned()),
+        ),
+        Column::new(
+            SqlExpr::atom("1".to_string()),
+            Some(CLOSURE_DEPTH_COLUMN_ALIAS.to_owned()),
+        ),
+        Column::new(
+            dialect.visited_list(vec![col(from_column), col(to_column)])?,
+            Some(CLOSURE_VISITED_COLUMN_ALIAS.to_owned()),
+        ),
+    ];
+    select.conditions = cmp::is_not_null(col(fk_column));
+
+    let mut step = Select::from(alias.to_owned());
+    let visited = closure_col(CLOSURE_VISITED_COLUMN_ALIAS);
+    step.columns = vec![
+        Column::new(closure_col(CTE_PK_COLUMN_ALIAS), None),
+        Column::new(col(to_column), None),
+        Column::new(
+            math::add(
+                closure_col(CLOSURE_DEPTH_COLUMN_ALIAS),
+                SqlExpr::atom("1".to_string()),
+            ),
+            None,
+        ),
+        Column::new(
+            dialect.visited_list_append(visited.clone(), col(to_column))?,
+            None,
+        ),
+    ];
+    step.joins.push(Join {
+        table: table_name.to_owned(),
+        alias: table_name.to_owned(),
+        conditions: cmp::eq(col(from_column), closure_col(CLOSURE_RELATED_COLUMN_ALIAS)),
+        join_type: JoinType::Inner,
+    });
+    step.conditions = cmp::and([
+        cmp::is_not_null(col(fk_column)),
+        cond::not(dialect.visited_list_contains(visited, col(to_column))?),
+    ]);
+    select.union_all.push(step);
+
+    Ok(Cte {
+        alias: alias.to_owned(),
+        select,
+        purpose: CtePurpose::Closure,
+        join_column_name: CTE_PK_COLUMN_ALIAS.to_owned(),
+    })
+}
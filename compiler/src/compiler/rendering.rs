@@ -19,7 +19,10 @@ impl Render for Select {
     fn render(&self, scope: &mut Scope) -> String {
         let base_table_name = scope.options.dialect.quote_identifier(&self.base_table);
 
-        let select = "SELECT".to_string();
+        let select = self
+            .distinct_on
+            .clone()
+            .unwrap_or_else(|| "SELECT".to_string());
         let columns = indent(self.columns.render(scope));
         let from = format!("FROM {}", base_table_name);
         let joins = self.joins.render(scope);
@@ -52,7 +55,12 @@ impl Render for Select {
             let sorting = indent(self.sorting.render(scope));
             format!("ORDER BY\n{sorting}")
         };
-        [ctes, main, where_, group, order]
+        let unions = self
+            .union_all
+            .iter()
+            .map(|s| format!("UNION ALL\n{}", s.render(scope)))
+            .join("\n");
+        [ctes, main, where_, group, order, unions]
             .into_iter()
             .filter(|s| !s.is_empty())
             .join("\n")
@@ -70,7 +78,13 @@ impl Render for Vec<Cte> {
                 .filter(|s| !s.is_empty())
                 .join(",\n"),
         );
-        format!("WITH\n{ctes}")
+        let is_recursive = self.iter().any(|cte| cte.purpose == CtePurpose::Closure);
+        let with = if is_recursive {
+            "WITH RECURSIVE"
+        } else {
+            "WITH"
+        };
+        format!("{with}\n{ctes}")
     }
 }
 
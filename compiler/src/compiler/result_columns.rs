@@ -4,31 +4,48 @@ use itertools::Itertools;
 use querydown_parser::ast::*;
 
 use crate::{
-    compiler::paths::{clarify_path, ClarifiedPathTail},
-    errors::msg,
+    compiler::paths::{clarify_path, AggregateExprTemplate, ClarifiedPathTail, CteValueTemplate},
+    errors::{msg, Error},
+    schema::{
+        chain::Chain,
+        data_type::DataType,
+        links::{FilteredLink, Link, LinkToOne},
+        Table,
+    },
     sql::{
         expr::SqlExpr,
-        tree::{Column, SortEntry},
+        tree::{Column, CtePurpose, SortEntry},
     },
+    ColumnOrigin, ColumnReference, LinkStep, SourceColumn,
 };
 
 use self::sorting::SortingStack;
 
-use super::{expr::convert_expr, scope::Scope};
+use super::{
+    expr::convert_expr, scope::Scope, suggestions::suggest_columns, type_inference::infer_type,
+};
 
 pub fn convert_result_columns(
     result_columns: Vec<ResultColumnStatement>,
     scope: &mut Scope,
-) -> Result<(Vec<Column>, Vec<SortEntry>), String> {
+) -> Result<(Vec<Column>, Vec<SortEntry>), Error> {
     let mut columns = Vec::<Column>::new();
     let mut sorting_stack = SortingStack::new();
     for column_statement in result_columns {
         match column_statement {
             ResultColumnStatement::Spec(spec) => {
-                handle_spec(spec, &mut columns, &mut sorting_stack, scope)?;
+                let span = spec.span;
+                handle_spec(spec, &mut columns, &mut sorting_stack, scope)
+                    .map_err(|e| e.or_span(span))?;
             }
             ResultColumnStatement::Glob(glob) => {
-                handle_glob(glob, &mut columns, &mut sorting_stack, scope)?;
+                let span = glob.span;
+                handle_glob(glob, &mut columns, &mut sorting_stack, scope)
+                    .map_err(|e| e.or_span(span))?;
+            }
+            ResultColumnStatement::Nested(nested) => {
+                let span = nested.span;
+                handle_nested(nested, &mut columns, scope).map_err(|e| e.or_span(span))?;
             }
         }
     }
@@ -40,7 +57,9 @@ fn handle_spec(
     columns: &mut Vec<Column>,
     sorting_stack: &mut SortingStack,
     scope: &mut Scope,
-) -> Result<(), String> {
+) -> Result<(), Error> {
+    let origin = get_origin(&spec.expr, scope);
+    let data_type = infer_type(&spec.expr, scope)?.data_type();
     let expr = convert_expr(spec.expr, scope)?;
     let alias = spec.alias;
     if let Some(sort_spec) = spec.column_control.sort {
@@ -50,7 +69,7 @@ fn handle_spec(
             .unwrap_or_else(|| expr.clone());
         sorting_stack.push(sorting_expr, sort_spec);
     }
-    columns.push(Column { expr, alias });
+    columns.push(Column::with_origin(expr, alias, origin).with_data_type(data_type));
     // TODO convert GroupSpec into GROUP BY
     Ok(())
 }
@@ -60,8 +79,20 @@ fn handle_glob(
     columns: &mut Vec<Column>,
     sorting_stack: &mut SortingStack,
     scope: &mut Scope,
-) -> Result<(), String> {
-    scope.with_path_prefix(glob.head.clone(), |scope| -> Result<(), String> {
+) -> Result<(), Error> {
+    if !glob.head.is_empty() {
+        let clarified_path = clarify_path(glob.head.clone(), scope)?;
+        if let Some(ClarifiedPathTail::ChainToMany((chain, None))) = clarified_path.tail {
+            return handle_glob_on_path_to_many(
+                clarified_path.head,
+                chain,
+                glob.specs,
+                columns,
+                scope,
+            );
+        }
+    }
+    scope.with_path_prefix(glob.head.clone(), |scope| -> Result<(), Error> {
         for spec in glob.specs.iter() {
             if let Some(ref sort_spec) = spec.column_control.sort {
                 let sql_expr = convert_expr(spec.expr.clone(), scope)?;
@@ -72,42 +103,71 @@ fn handle_glob(
         Ok(())
     })?;
 
-    let (table, table_alias) = if glob.head.len() == 0 {
+    let (table, table_alias, head) = if glob.head.len() == 0 {
         let base_table = scope.get_base_table();
-        (scope.get_base_table(), base_table.name.clone())
+        (scope.get_base_table(), base_table.name.clone(), None)
     } else {
         let clarified_path = clarify_path(glob.head, scope)?;
         if let Some(tail) = clarified_path.tail {
             let err_msg = match tail {
                 ClarifiedPathTail::Column(col) => msg::column_glob_after_non_fk_column(&col),
-                ClarifiedPathTail::ChainToMany(_) => msg::column_glob_on_path_to_many(),
+                ClarifiedPathTail::ChainToMany((_, Some(col))) => {
+                    msg::column_glob_after_non_fk_column(&col)
+                }
+                // Handled by `handle_glob_on_path_to_many` above
+                ClarifiedPathTail::ChainToMany((_, None)) => {
+                    msg::internal("Column glob on a path to many was not handled.")
+                }
             };
             return Err(err_msg);
         }
         let Some(chain_to_one) = clarified_path.head else {
-                return Err(msg::empty_path());
-            };
+            return Err(msg::empty_path());
+        };
         let table = scope
             .schema
             .tables
             .get(&chain_to_one.get_ending_table_id())
             .unwrap();
         let table_alias = scope.join_chain_to_one(&chain_to_one);
-        (table, table_alias)
+        (table, table_alias, Some(chain_to_one))
     };
 
+    let (hidden_columns, column_aliases) = get_glob_adjustments(glob.specs, table, scope)?;
+
+    for column in table.columns.values().sorted_by_key(|c| c.id) {
+        let expr = scope.table_column_expr(&table_alias, &column.name);
+        let alias = column_aliases.get(&column.id).cloned();
+        let origin = get_table_column_origin(head.as_ref(), &column.name, scope);
+        if !hidden_columns.contains(&column.id) {
+            columns.push(Column::with_origin(expr, alias, origin).with_data_type(column.data_type));
+        }
+    }
+    Ok(())
+}
+
+/// The columns hidden and aliased by the specs within a glob's parentheses, keyed by column id
+fn get_glob_adjustments(
+    specs: Vec<ColumnSpec>,
+    table: &Table,
+    scope: &Scope,
+) -> Result<(HashSet<usize>, HashMap<usize, String>), Error> {
     let mut hidden_columns: HashSet<usize> = HashSet::new();
     let mut column_aliases: HashMap<usize, String> = HashMap::new();
 
-    for spec in glob.specs {
+    for spec in specs {
         if let Expr::Path(ref path) = spec.expr {
             if let Ok(first_path_part) = path.iter().exactly_one() {
-                if let PathPart::Column(column_name) = first_path_part {
+                if let PathPart::Column(column) = first_path_part {
                     let column_id = scope
                         .options
-                        .resolve_identifier(&table.column_lookup, &column_name)
+                        .resolve_identifier(&table.column_lookup, &column.name)
                         .copied()
-                        .ok_or_else(|| msg::col_not_in_table(&column_name, &table.name))?;
+                        .ok_or_else(|| {
+                            msg::col_not_in_table(&column.name, &table.name)
+                                .with_suggestions(&suggest_columns(&column.name, table))
+                                .with_span(column.span)
+                        })?;
                     if spec.column_control.is_hidden {
                         hidden_columns.insert(column_id);
                     }
@@ -118,17 +178,209 @@ fn handle_glob(
             }
         }
     }
+    Ok((hidden_columns, column_aliases))
+}
 
-    for column in table.columns.values().sorted_by_key(|c| c.id) {
-        let expr = scope.table_column_expr(&table_alias, &column.name);
-        let alias = column_aliases.get(&column.id).cloned();
-        if !hidden_columns.contains(&column.id) {
-            columns.push(Column { expr, alias });
-        }
+/// A glob on a path to many (e.g. `$#comments.*`) produces one column holding a JSON array, with an
+/// object for each related record
+fn handle_glob_on_path_to_many(
+    head: Option<Chain<LinkToOne>>,
+    chain: Chain<FilteredLink>,
+    specs: Vec<ColumnSpec>,
+    columns: &mut Vec<Column>,
+    scope: &mut Scope,
+) -> Result<(), Error> {
+    if specs
+        .iter()
+        .any(|spec| spec.column_control.sort.is_some() || spec.column_control.group.is_some())
+    {
+        return Err(msg::column_control_within_nested_columns());
     }
+    let table = scope
+        .schema
+        .tables
+        .get(&chain.get_ending_table_id())
+        .unwrap();
+    let (hidden_columns, column_aliases) = get_glob_adjustments(specs, table, scope)?;
+    let fields = table
+        .columns
+        .values()
+        .sorted_by_key(|c| c.id)
+        .filter(|column| !hidden_columns.contains(&column.id))
+        .map(|column| {
+            let key = column_aliases
+                .get(&column.id)
+                .cloned()
+                .unwrap_or_else(|| column.name.clone());
+            (key, Expr::Path(vec![PathPart::column(column.name.clone())]))
+        })
+        .collect();
+    let expr = join_nested_columns(&head, chain, fields, scope)?;
+    let column = Column::with_origin(expr, Some(table.name.clone()), ColumnOrigin::Aggregated);
+    columns.push(column.with_data_type(Some(DataType::Json)));
+    Ok(())
+}
+
+/// E.g. `$#comments(body author.username)`
+fn handle_nested(
+    nested: NestedColumns,
+    columns: &mut Vec<Column>,
+    scope: &mut Scope,
+) -> Result<(), Error> {
+    let clarified_path = clarify_path(nested.path, scope)?;
+    let Some(ClarifiedPathTail::ChainToMany((chain, None))) = clarified_path.tail else {
+        return Err(msg::nested_columns_on_path_to_one());
+    };
+    let fields = nested
+        .specs
+        .into_iter()
+        .map(|spec| {
+            if spec.column_control != ColumnControl::default() {
+                return Err(msg::column_control_within_nested_columns().with_span(spec.span));
+            }
+            let key = match (spec.alias, &spec.expr) {
+                (Some(alias), _) => alias,
+                (None, Expr::Path(parts)) => match parts.last() {
+                    Some(PathPart::Column(column)) => column.name.clone(),
+                    _ => return Err(msg::nested_column_without_name().with_span(spec.span)),
+                },
+                (None, _) => return Err(msg::nested_column_without_name().with_span(spec.span)),
+            };
+            Ok((key, spec.expr))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let table = scope
+        .schema
+        .tables
+        .get(&chain.get_ending_table_id())
+        .unwrap();
+    let alias = nested.alias.unwrap_or_else(|| table.name.clone());
+    let expr = join_nested_columns(&clarified_path.head, chain, fields, scope)?;
+    let column = Column::with_origin(expr, Some(alias), ColumnOrigin::Aggregated);
+    columns.push(column.with_data_type(Some(DataType::Json)));
     Ok(())
 }
 
+/// Aggregate the fields (keyed by name) into a JSON array with an object for each record at the
+/// end of the chain. This happens within a CTE, so the base table's row count is preserved.
+fn join_nested_columns(
+    head: &Option<Chain<LinkToOne>>,
+    chain: Chain<FilteredLink>,
+    fields: Vec<(String, Expr)>,
+    scope: &mut Scope,
+) -> Result<SqlExpr, Error> {
+    let (keys, mut values): (Vec<String>, Vec<Expr>) = fields.into_iter().unzip();
+    if values.is_empty() {
+        return Err(msg::nested_columns_all_hidden());
+    }
+    let first_value = values.remove(0);
+    let dialect = scope.options.dialect.as_ref();
+    let wrapper = move |first_value, other_values: Vec<SqlExpr>| {
+        let values = std::iter::once(first_value).chain(other_values);
+        let object = dialect.json_object(keys.into_iter().zip(values).collect());
+        Ok(dialect.json_agg(object, None))
+    };
+    let template = AggregateExprTemplate::new(first_value, values, Box::new(wrapper));
+    scope.join_chain_to_many(
+        head,
+        chain,
+        Some(CteValueTemplate::Aggregate(template)),
+        CtePurpose::AggregateValue,
+    )
+}
+
+/// Determine where the values of a result column will come from, before its expression is converted
+fn get_origin(expr: &Expr, scope: &Scope) -> ColumnOrigin {
+    match expr {
+        Expr::Path(parts) => get_path_origin(parts, scope),
+        Expr::Call(call) if matches!(call.dimension, FunctionDimension::Aggregate) => {
+            ColumnOrigin::Aggregated
+        }
+        _ => ColumnOrigin::Computed,
+    }
+}
+
+fn get_path_origin(parts: &[PathPart], scope: &Scope) -> ColumnOrigin {
+    let parts = scope.path_prefix.iter().chain(parts).cloned().collect_vec();
+    if parts
+        .iter()
+        .any(|p| matches!(p, PathPart::Pick(_) | PathPart::Transitive))
+    {
+        return ColumnOrigin::Aggregated;
+    }
+    if matches!(parts.last(), Some(PathPart::Expr(_))) {
+        return ColumnOrigin::Computed;
+    }
+    let Ok(clarified_path) = clarify_path(parts, scope) else {
+        return ColumnOrigin::Computed;
+    };
+    match (clarified_path.head, clarified_path.tail) {
+        (head, Some(ClarifiedPathTail::Column(column_name))) => {
+            get_table_column_origin(head.as_ref(), &column_name, scope)
+        }
+        (Some(chain_to_one), None) => {
+            let (truncated_chain_to_one_opt, last_link) = chain_to_one.with_last_link_broken_off();
+            let column_name = scope
+                .schema
+                .get_referenced_column_name(&last_link.get_start());
+            get_table_column_origin(truncated_chain_to_one_opt.as_ref(), &column_name, scope)
+        }
+        (_, Some(ClarifiedPathTail::ChainToMany(_))) => ColumnOrigin::Aggregated,
+        (None, None) => ColumnOrigin::Computed,
+    }
+}
+
+/// The origin of a column within the table at the end of `head`, or within the base table. Names
+/// which don't match a column in that table refer to computed columns.
+fn get_table_column_origin(
+    head: Option<&Chain<LinkToOne>>,
+    column_name: &str,
+    scope: &Scope,
+) -> ColumnOrigin {
+    let schema = scope.schema;
+    let table = match head {
+        Some(chain) => schema.tables.get(&chain.get_ending_table_id()).unwrap(),
+        None => scope.get_base_table(),
+    };
+    let Some(column_id) = scope
+        .options
+        .resolve_identifier(&table.column_lookup, column_name)
+    else {
+        return ColumnOrigin::Computed;
+    };
+    let reference = |table_id, column_id| {
+        let table = schema.tables.get(&table_id).unwrap();
+        ColumnReference {
+            table: table.name.clone(),
+            column: table.columns.get(&column_id).unwrap().name.clone(),
+        }
+    };
+    let path = head
+        .map(|chain| chain.get_links())
+        .unwrap_or_default()
+        .iter()
+        .map(|link| {
+            let (start, end) = (link.get_start(), link.get_end());
+            LinkStep {
+                from: reference(start.table_id, start.column_id),
+                to: reference(end.table_id, end.column_id),
+            }
+        })
+        .collect_vec();
+    let key = match path.last() {
+        Some(step) => Some(step.to.column.clone()),
+        None => table.get_key_column().map(|c| c.name.clone()),
+    };
+    let column = table.columns.get(column_id).unwrap();
+    ColumnOrigin::TableColumn(SourceColumn {
+        table: table.name.clone(),
+        column: column.name.clone(),
+        nullable: column.nullable,
+        key,
+        path,
+    })
+}
+
 mod sorting {
     use querydown_parser::ast::SortSpec;
 
@@ -172,3 +424,139 @@ mod sorting {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        errors::msg, tests::get_test_resource, ColumnOrigin, ColumnReference, Compiler, Error,
+        LinkStep, Options, SourceColumn,
+    };
+
+    fn compile(input: &str) -> Result<String, Error> {
+        let schema = get_test_resource("issue_schema.json");
+        let compiler = Compiler::new(&schema, Options::default()).unwrap();
+        // Errors are compared without their locations, which are tested separately
+        compiler
+            .compile(input.to_owned())
+            .map_err(Error::without_location)
+    }
+
+    #[test]
+    fn test_nested_column_errors() {
+        assert_eq!(
+            compile("#issues $author(username email)"),
+            Err(msg::nested_columns_on_path_to_one())
+        );
+        assert_eq!(
+            compile("#issues $#comments((body|length) created_at)"),
+            Err(msg::nested_column_without_name())
+        );
+        assert_eq!(
+            compile(r"#issues $#comments(id \s created_at)"),
+            Err(msg::column_control_within_nested_columns())
+        );
+        assert_eq!(
+            compile(r"#issues $#comments.*(id \h issue \h user \h body \h created_at \h)"),
+            Err(msg::nested_columns_all_hidden())
+        );
+        assert_eq!(
+            compile("#issues $#comments(body)"),
+            Err(msg::linking_column_not_a_link("body", "comments"))
+        );
+    }
+
+    #[test]
+    fn test_column_origins() {
+        let origins_in = |schema: &str, input: &str| {
+            let schema = get_test_resource(schema);
+            let compiler = Compiler::new(&schema, Options::default()).unwrap();
+            let output = compiler.compile_output(input.to_owned()).unwrap();
+            output
+                .columns
+                .into_iter()
+                .map(|c| c.origin)
+                .collect::<Vec<_>>()
+        };
+        let origins = |input: &str| origins_in("issue_schema.json", input);
+        let reference = |table: &str, column: &str| ColumnReference {
+            table: table.to_owned(),
+            column: column.to_owned(),
+        };
+        let author_step = LinkStep {
+            from: reference("issues", "author"),
+            to: reference("users", "id"),
+        };
+        assert_eq!(
+            origins("#issues $title $author.username $author.team.name"),
+            vec![
+                ColumnOrigin::TableColumn(SourceColumn {
+                    table: "issues".to_owned(),
+                    column: "title".to_owned(),
+                    nullable: false,
+                    key: Some("id".to_owned()),
+                    path: vec![],
+                }),
+                ColumnOrigin::TableColumn(SourceColumn {
+                    table: "users".to_owned(),
+                    column: "username".to_owned(),
+                    nullable: false,
+                    key: Some("id".to_owned()),
+                    path: vec![author_step.clone()],
+                }),
+                ColumnOrigin::TableColumn(SourceColumn {
+                    table: "teams".to_owned(),
+                    column: "name".to_owned(),
+                    nullable: false,
+                    key: Some("id".to_owned()),
+                    path: vec![
+                        author_step.clone(),
+                        LinkStep {
+                            from: reference("users", "team"),
+                            to: reference("teams", "id"),
+                        },
+                    ],
+                }),
+            ]
+        );
+        assert_eq!(
+            origins("#issues $(title|length) $#comments.id%count $#comments%last(created_at).body"),
+            vec![
+                ColumnOrigin::Computed,
+                ColumnOrigin::Aggregated,
+                ColumnOrigin::Aggregated,
+            ]
+        );
+        assert_eq!(
+            origins(r"#issues $author.*(id \h email \h team \h)"),
+            vec![ColumnOrigin::TableColumn(SourceColumn {
+                table: "users".to_owned(),
+                column: "username".to_owned(),
+                nullable: false,
+                key: Some("id".to_owned()),
+                path: vec![author_step],
+            })]
+        );
+        // `comments` isn't referenced by any links, but its primary key identifies its rows
+        assert_eq!(
+            origins("#comments $body"),
+            vec![ColumnOrigin::TableColumn(SourceColumn {
+                table: "comments".to_owned(),
+                column: "body".to_owned(),
+                nullable: false,
+                key: Some("id".to_owned()),
+                path: vec![],
+            })]
+        );
+        // Without a primary key in the schema, the rows of `Checkouts` can't be identified
+        assert_eq!(
+            origins_in("library_schema.json", "#Checkouts $Patron"),
+            vec![ColumnOrigin::TableColumn(SourceColumn {
+                table: "Checkouts".to_owned(),
+                column: "Patron".to_owned(),
+                nullable: true,
+                key: None,
+                path: vec![],
+            })]
+        );
+    }
+}
@@ -1,22 +1,33 @@
-use std::collections::{HashMap, HashSet};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+};
 
-use querydown_parser::ast::PathPart;
+use querydown_parser::ast::{DefinitionName, PathPart};
 
 use crate::{
+    errors::{msg, Error},
     schema::{
         chain::Chain,
         links::{FilteredLink, Link, LinkToOne},
-        Schema, Table,
+        Schema, Table, TableId,
     },
     sql::tree::{Cte, CtePurpose, Join, SqlExpr},
-    Options,
+    CustomFunction, CustomFunctionKind, Options,
 };
 
 use super::{
     constants::*,
-    functions::{get_standard_aggregate_functions, get_standard_scalar_functions, Func, FuncMap},
+    definitions::{Definitions, NamespaceId, ScopedDefinition},
+    functions::{
+        get_standard_aggregate_functions, get_standard_scalar_functions, FuncMap, Function,
+    },
     join_tree::JoinTree,
-    paths::{build_cte_select, AggregateExprTemplate, ValueViaCte},
+    paths::{
+        build_cte_select, build_transitive_cte_select, CteValueTemplate, TransitiveLink,
+        TransitiveValueTemplate, ValueViaCte,
+    },
+    suggestions::suggest_tables,
 };
 
 pub struct Scope<'a, 'b> {
@@ -24,33 +35,135 @@ pub struct Scope<'a, 'b> {
     pub options: &'a Options,
     pub schema: &'a Schema,
     base_table: &'a Table,
+    definitions: &'a Definitions,
+    gathered: &'a Gathered,
+    /// The namespace used to resolve user-defined names. This changes while expanding a
+    /// definition from another module.
+    namespace: NamespaceId,
+    /// Values of the parameters of the user-defined function currently being expanded
+    params: HashMap<String, Param>,
+    /// Definitions currently being expanded, for detecting circular definitions
+    expanding: Vec<(NamespaceId, DefinitionName)>,
     join_tree: JoinTree,
     pub path_prefix: Vec<PathPart>,
     aliases: HashSet<String>,
     cte_naming_index: usize,
     scalar_functions: FuncMap,
     aggregate_functions: FuncMap,
+    /// Within a CTE, the table of the record which the CTE's records are related to. This is what
+    /// `&` refers to.
+    outer_table: Option<&'a Table>,
+    /// Joins needed to convert `&` references, rooted at the alias given to the outer record
+    outer_join_tree: Option<JoinTree>,
+}
+
+/// The value given to a parameter of a user-defined function. It's converted within the caller's
+/// scope, so a value which refers to the caller's record is only valid there and not within the
+/// CTEs spawned beneath it, where the caller's tables aren't joined.
+#[derive(Clone)]
+pub struct Param {
+    value: SqlExpr,
+    /// True when the argument depends on the caller's record, e.g. `author.username`
+    refers_to_record: bool,
+    /// False within scopes spawned beneath the caller when `refers_to_record` is true
+    is_reachable: bool,
+}
+
+impl Param {
+    pub fn new(value: SqlExpr, refers_to_record: bool) -> Self {
+        Self {
+            value,
+            refers_to_record,
+            is_reachable: true,
+        }
+    }
+
+    pub fn refers_to_record(&self) -> bool {
+        self.refers_to_record
+    }
+
+    pub fn get_value(&self, name: &str) -> Result<SqlExpr, Error> {
+        if self.is_reachable {
+            Ok(self.value.clone())
+        } else {
+            Err(msg::record_param_within_path_to_many(name))
+        }
+    }
+
+    fn spawned(&self) -> Self {
+        Self {
+            is_reachable: self.is_reachable && !self.refers_to_record,
+            ..self.clone()
+        }
+    }
+}
+
+/// What the query uses which is reported alongside the SQL rather than within it. Every scope of
+/// the query shares the same instance.
+#[derive(Default)]
+pub struct Gathered {
+    /// Values of the string literals within the query, in the order of their parameter positions.
+    /// When this is `None`, string literals are rendered inline instead.
+    params: RefCell<Option<Vec<String>>>,
+    /// Definitions which have been expanded, keyed as in `Scope::expanding`
+    used_definitions: RefCell<HashSet<(NamespaceId, DefinitionName)>>,
+}
+
+impl Gathered {
+    /// Bind string literals as parameters instead of rendering them inline
+    pub fn binding_params() -> Self {
+        Self {
+            params: RefCell::new(Some(vec![])),
+            ..Default::default()
+        }
+    }
+
+    pub fn into_params(self) -> Vec<String> {
+        self.params.into_inner().unwrap_or_default()
+    }
+
+    pub fn is_used(&self, key: &(NamespaceId, DefinitionName)) -> bool {
+        self.used_definitions.borrow().contains(key)
+    }
+}
+
+/// State shared between the scopes which contribute to the same CTE, so that table aliases remain
+/// unique within the CTE and the joins for `&` references accumulate in one place.
+pub struct CteState {
+    aliases: HashSet<String>,
+    outer_join_tree: Option<JoinTree>,
 }
 
 impl<'a, 'b> Scope<'a, 'b> {
     pub fn build(
         options: &'a Options,
         schema: &'a Schema,
+        definitions: &'a Definitions,
+        gathered: &'a Gathered,
         base_table_name: &'a str,
-    ) -> Result<Self, String> {
-        let base_table = get_table_by_name(options, schema, base_table_name)
-            .ok_or(format!("Base table `{}` does not exist.", base_table_name))?;
+    ) -> Result<Self, Error> {
+        let base_table = get_table_by_name(options, schema, base_table_name).ok_or_else(|| {
+            let suggestions = suggest_tables(base_table_name, schema);
+            msg::unknown_base_table(base_table_name).with_suggestions(&suggestions)
+        })?;
         Ok(Self {
             parent: None,
             options,
             schema,
             base_table,
+            definitions,
+            gathered,
+            namespace: definitions.get_root(),
+            params: HashMap::new(),
+            expanding: vec![],
             join_tree: JoinTree::new(base_table.name.to_owned()),
             path_prefix: vec![],
             aliases: HashSet::new(),
             cte_naming_index: 0,
             scalar_functions: get_standard_scalar_functions(),
             aggregate_functions: get_standard_aggregate_functions(),
+            outer_table: None,
+            outer_join_tree: None,
         })
     }
 
@@ -58,43 +171,127 @@ impl<'a, 'b> Scope<'a, 'b> {
         self.base_table
     }
 
+    /// The alias of the base table, which is the table name unless the scope was spawned with a
+    /// different alias
+    pub fn get_base_alias(&self) -> String {
+        self.join_tree.get_alias().to_owned()
+    }
+
     pub fn decompose_join_tree(&mut self) -> (Vec<Join>, Vec<Cte>) {
-        let join_tree = std::mem::replace(
-            &mut self.join_tree,
-            JoinTree::new(self.base_table.name.to_owned()),
-        );
+        let alias = self.get_base_alias();
+        let join_tree = std::mem::replace(&mut self.join_tree, JoinTree::new(alias));
         join_tree.decompose(self)
     }
 
     pub fn spawn(&'b self, base_table: &'a Table) -> Self {
+        self.spawn_with_alias(base_table, base_table.name.to_owned())
+    }
+
+    pub fn spawn_with_alias(&'b self, base_table: &'a Table, alias: String) -> Self {
         Scope {
             parent: Some(self),
             options: self.options,
             schema: self.schema,
             base_table,
-            join_tree: JoinTree::new(base_table.name.to_owned()),
+            definitions: self.definitions,
+            gathered: self.gathered,
+            namespace: self.namespace,
+            params: self
+                .params
+                .iter()
+                .map(|(name, param)| (name.clone(), param.spawned()))
+                .collect(),
+            expanding: vec![],
+            join_tree: JoinTree::new(alias),
             path_prefix: vec![],
             aliases: HashSet::new(),
             cte_naming_index: 0,
             scalar_functions: HashMap::new(),
             aggregate_functions: HashMap::new(),
+            outer_table: self.outer_table,
+            outer_join_tree: None,
         }
     }
 
+    pub fn set_outer_table(&mut self, table: &'a Table) {
+        self.outer_table = Some(table);
+    }
+
+    pub fn get_outer_table(&self) -> Option<&'a Table> {
+        self.outer_table
+    }
+
+    pub fn take_cte_state(&mut self) -> CteState {
+        CteState {
+            aliases: std::mem::take(&mut self.aliases),
+            outer_join_tree: self.outer_join_tree.take(),
+        }
+    }
+
+    pub fn put_cte_state(&mut self, state: CteState) {
+        self.aliases = state.aliases;
+        self.outer_join_tree = state.outer_join_tree;
+    }
+
+    /// Returns the joins needed for `&` references, if there were any
+    pub fn take_outer_join_tree(&mut self) -> Option<JoinTree> {
+        self.outer_join_tree.take()
+    }
+
+    /// Run `f` in a scope based on the outer record (see `outer_table`). The outer record is
+    /// aliased on first use, and any joins needed to reach related tables from it are retained for
+    /// the CTE.
+    pub fn with_outer_record<T>(
+        &mut self,
+        f: impl FnOnce(&mut Scope) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let Some(outer_table) = self.outer_table else {
+            return Err(msg::outer_reference_outside_related_table());
+        };
+        let join_tree = match self.outer_join_tree.take() {
+            Some(join_tree) => join_tree,
+            None => JoinTree::new(self.get_alias(&outer_table.name)),
+        };
+        let alias = join_tree.get_alias().to_owned();
+        let aliases = std::mem::take(&mut self.aliases);
+        let (result, join_tree, aliases) = {
+            let mut outer_scope = self.spawn_with_alias(outer_table, alias);
+            outer_scope.join_tree = join_tree;
+            outer_scope.aliases = aliases;
+            outer_scope.outer_table = None;
+            let result = f(&mut outer_scope);
+            (result, outer_scope.join_tree, outer_scope.aliases)
+        };
+        self.outer_join_tree = Some(join_tree);
+        self.aliases = aliases;
+        result
+    }
+
     pub fn with_path_prefix<T>(
         &mut self,
         path_prefix: Vec<PathPart>,
         f: impl FnOnce(&mut Self) -> T,
     ) -> T {
-        // We don't do any nested path prefixing, so the logic here simply resets the path prefix
-        // to be empty afterwards. We can make it more complex if we need to support nested path
-        // prefixing at some point.
-        self.path_prefix = path_prefix;
+        // Path prefixes can be nested (e.g. a computed column used within a column glob), so we
+        // restore the previous prefix afterwards.
+        let previous_path_prefix = std::mem::replace(&mut self.path_prefix, path_prefix);
         let return_value = f(self);
-        self.path_prefix = vec![];
+        self.path_prefix = previous_path_prefix;
         return_value
     }
 
+    /// A string literal, bound as a parameter when the query's parameters are being gathered
+    pub fn string_expr(&self, string: &str) -> SqlExpr {
+        let dialect = &self.options.dialect;
+        match self.gathered.params.borrow_mut().as_mut() {
+            Some(params) => {
+                params.push(string.to_owned());
+                SqlExpr::atom(dialect.parameter(params.len()))
+            }
+            None => SqlExpr::atom(dialect.quote_string(string)),
+        }
+    }
+
     pub fn table_column_expr(&self, table_name: &str, column_name: &str) -> SqlExpr {
         SqlExpr::atom(self.options.dialect.table_column(table_name, column_name))
     }
@@ -155,9 +352,9 @@ impl<'a, 'b> Scope<'a, 'b> {
         &mut self,
         head: &Option<Chain<LinkToOne>>,
         chain: Chain<FilteredLink>,
-        aggregate_expr_template_opt: Option<AggregateExprTemplate>,
+        value_template_opt: Option<CteValueTemplate<'a>>,
         purpose: CtePurpose,
-    ) -> Result<SqlExpr, String> {
+    ) -> Result<SqlExpr, Error> {
         let starting_reference = chain.get_first_link().get_start();
         let starting_table_id = starting_reference.table_id;
         let starting_column_id = starting_reference.column_id;
@@ -166,7 +363,7 @@ impl<'a, 'b> Scope<'a, 'b> {
         let ValueViaCte {
             select,
             value_alias,
-        } = build_cte_select(chain, aggregate_expr_template_opt, self, purpose)?;
+        } = build_cte_select(chain, value_template_opt, self, purpose)?;
         let cte_alias = self.get_cte_alias();
         let cte = Cte {
             select,
@@ -178,7 +375,32 @@ impl<'a, 'b> Scope<'a, 'b> {
         Ok(self.table_column_expr(&cte_alias, &value_alias))
     }
 
-    fn get_cte_alias(&mut self) -> String {
+    /// Join a CTE which supplies a value from the records reachable by repeatedly following `link`
+    /// from the record at the end of `head`
+    pub fn join_transitive(
+        &mut self,
+        head: &Option<Chain<LinkToOne>>,
+        link: TransitiveLink,
+        value_template: TransitiveValueTemplate<'a>,
+    ) -> Result<SqlExpr, Error> {
+        let table = self.schema.tables.get(&link.get_table_id()).unwrap();
+        let column = table.columns.get(&link.get_referenced_column_id()).unwrap();
+        let ValueViaCte {
+            select,
+            value_alias,
+        } = build_transitive_cte_select(link, value_template, self)?;
+        let cte_alias = self.get_cte_alias();
+        let cte = Cte {
+            select,
+            alias: cte_alias.clone(),
+            purpose: CtePurpose::AggregateValue,
+            join_column_name: column.name.clone(),
+        };
+        self.integrate_chain(head.as_ref(), Some(cte));
+        Ok(self.table_column_expr(&cte_alias, &value_alias))
+    }
+
+    pub fn get_cte_alias(&mut self) -> String {
         loop {
             let alias = format!("{}{}", CTE_ALIAS_PREFIX, self.cte_naming_index);
             self.cte_naming_index += 1;
@@ -193,19 +415,116 @@ impl<'a, 'b> Scope<'a, 'b> {
         get_table_by_name(self.options, self.schema, name)
     }
 
-    pub fn get_scalar_function(&self, name: &str) -> Option<&Func> {
+    pub fn get_scalar_function(&self, name: &str) -> Option<&Function> {
         self.scalar_functions.get(name).or_else(|| {
             self.parent
                 .and_then(|parent| parent.get_scalar_function(name))
         })
     }
 
-    pub fn get_aggregate_function(&self, name: &str) -> Option<&Func> {
+    pub fn get_aggregate_function(&self, name: &str) -> Option<&Function> {
         self.aggregate_functions.get(name).or_else(|| {
             self.parent
                 .and_then(|parent| parent.get_aggregate_function(name))
         })
     }
+
+    pub fn get_custom_function(
+        &self,
+        name: &str,
+        kind: CustomFunctionKind,
+    ) -> Option<&'a CustomFunction> {
+        self.options
+            .custom_functions
+            .iter()
+            .find(|f| f.kind == kind && f.name == name)
+    }
+
+    /// Names of every function of the given kind which can be called within this scope
+    pub fn get_function_names(&self, kind: CustomFunctionKind) -> Vec<String> {
+        let mut names = vec![];
+        let mut scope = Some(self);
+        while let Some(s) = scope {
+            let functions = match kind {
+                CustomFunctionKind::Scalar => &s.scalar_functions,
+                CustomFunctionKind::Aggregate => &s.aggregate_functions,
+            };
+            names.extend(functions.keys().cloned());
+            scope = s.parent;
+        }
+        let custom_functions = self.options.custom_functions.iter();
+        names.extend(
+            custom_functions
+                .filter(|f| f.kind == kind)
+                .map(|f| f.name.clone()),
+        );
+        if kind == CustomFunctionKind::Scalar {
+            let user_functions = self
+                .definitions
+                .get_function_names(self.namespace, self.base_table.id);
+            names.extend(user_functions);
+        }
+        names
+    }
+
+    pub fn get_param(&self, name: &str) -> Option<&Param> {
+        self.params.get(name)
+    }
+
+    pub fn get_constant(&self, name: &str) -> Option<&'a ScopedDefinition> {
+        self.definitions.get_constant(self.namespace, name)
+    }
+
+    /// Returns a user-defined function, preferring functions scoped to the base table.
+    pub fn get_user_function(&self, name: &str) -> Option<&'a ScopedDefinition> {
+        self.definitions
+            .get_function(self.namespace, self.base_table.id, name)
+    }
+
+    pub fn get_computed_column(
+        &self,
+        table_id: TableId,
+        column_name: &str,
+    ) -> Option<&'a ScopedDefinition> {
+        self.definitions
+            .get_column(self.namespace, table_id, column_name, self.options)
+    }
+
+    fn is_expanding(&self, key: &(NamespaceId, DefinitionName)) -> bool {
+        self.expanding.contains(key) || self.parent.is_some_and(|p| p.is_expanding(key))
+    }
+
+    /// Run `f` in the context of a user-defined definition so that names within its body are
+    /// resolved from the namespace where it was written, and `params` are available as
+    /// variables.
+    pub fn expand_definition<T>(
+        &mut self,
+        scoped: &ScopedDefinition,
+        params: HashMap<String, Param>,
+        f: impl FnOnce(&mut Self) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let key = (scoped.namespace, scoped.definition.name.clone());
+        if self.is_expanding(&key) {
+            return Err(msg::circular_definition(&scoped.definition.name));
+        }
+        self.gathered
+            .used_definitions
+            .borrow_mut()
+            .insert(key.clone());
+        self.expanding.push(key);
+        let previous_namespace = std::mem::replace(&mut self.namespace, scoped.namespace);
+        let previous_params = std::mem::replace(&mut self.params, params);
+        let mut result = f(self);
+        // Definitions from other modules (or the prelude) are located within other source code, so
+        // their errors are located where the definition is used instead
+        if scoped.namespace != self.definitions.get_root() {
+            result = result.map_err(Error::without_location);
+        }
+        self.params = previous_params;
+        self.namespace = previous_namespace;
+        self.expanding.pop();
+        result
+    }
 }
 
 fn get_table_by_name<'a>(options: &Options, schema: &'a Schema, name: &str) -> Option<&'a Table> {
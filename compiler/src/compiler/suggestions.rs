@@ -0,0 +1,35 @@
+use itertools::Itertools;
+
+use crate::{
+    schema::{Schema, Table},
+    utils::rank_suggestions,
+};
+
+/// Tables within the schema whose names are similar to `name`
+pub fn suggest_tables(name: &str, schema: &Schema) -> Vec<String> {
+    rank_suggestions(name, schema.table_lookup.keys().cloned())
+}
+
+/// Columns within `table` whose names are similar to `name`
+pub fn suggest_columns(name: &str, table: &Table) -> Vec<String> {
+    rank_suggestions(name, table.column_lookup.keys().cloned())
+}
+
+/// Parts which could follow `table` within a path and are similar to `name`, i.e. its columns
+/// along with the related tables which have many records for each of its records
+pub fn suggest_path_parts(name: &str, table: &Table, schema: &Schema) -> Vec<String> {
+    let columns = table.column_lookup.keys().cloned();
+    rank_suggestions(name, columns.chain(get_related_tables(table, schema)))
+}
+
+/// The tables which have many records for each record of `table`, written as they would be
+/// within a path (e.g. `#comments`), in alphabetical order
+pub fn get_related_tables(table: &Table, schema: &Schema) -> Vec<String> {
+    table
+        .reverse_links_to_many
+        .keys()
+        .filter_map(|id| schema.tables.get(id))
+        .map(|t| format!("#{}", t.name))
+        .sorted()
+        .collect()
+}
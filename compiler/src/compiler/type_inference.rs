@@ -0,0 +1,457 @@
+use std::collections::HashMap;
+
+use querydown_parser::ast::*;
+
+use crate::{
+    errors::{msg, Error},
+    schema::{data_type::DataType, links::Link},
+};
+
+use super::{
+    constants::{VAR_FALSE, VAR_INFINITY, VAR_NOW, VAR_NULL, VAR_TRUE},
+    definitions::ScopedDefinition,
+    expr::{get_computed_column, pop_path_expr, split_at_pick, split_at_transitive},
+    paths::{clarify_path, ClarifiedPath, ClarifiedPathTail},
+    scope::Scope,
+    types::{arithmetic_type, common_type, Accepts, ArithmeticOperator, Type},
+};
+
+/// Types of the arguments given to the user-defined function being inferred, keyed by parameter
+/// name
+type Params = HashMap<String, Type>;
+
+/// Infer the type of a Querydown expression, returning an error when it combines values of
+/// incompatible types. Only type errors are reported here. Anything which can't be resolved (e.g.
+/// an unknown column) has an unknown type, leaving the error to be reported when the expression is
+/// converted.
+pub fn infer_type(expr: &Expr, scope: &mut Scope) -> Result<Type, Error> {
+    infer(expr, &Params::new(), scope)
+}
+
+fn infer(expr: &Expr, params: &Params, scope: &mut Scope) -> Result<Type, Error> {
+    use ArithmeticOperator::*;
+    let t = match expr {
+        Expr::Number(n) if n.contains('.') => DataType::Decimal.into(),
+        Expr::Number(_) => DataType::Integer.into(),
+        Expr::Date(_) => DataType::Date.into(),
+        Expr::Duration(_) => DataType::Interval.into(),
+        Expr::String(_) => Type::StringLiteral,
+        Expr::Variable(v) => infer_variable(v, params, scope)?,
+        Expr::Path(parts) => infer_path(parts, params, scope)?,
+        Expr::ConditionSet(condition_set) => {
+            for entry in condition_set.entries.iter() {
+                infer(entry, params, scope)?;
+            }
+            DataType::Boolean.into()
+        }
+        // The conditions within the path are checked when they're converted within their CTE
+        Expr::HasQuantity(_) => DataType::Boolean.into(),
+        Expr::Outer(e) => infer_outer(e, params, scope)?,
+        Expr::Call(c) => infer_call(c, params, scope).map_err(|e| e.or_span(c.span))?,
+        Expr::Product(a, b) => infer_arithmetic(Multiply, a, b, params, scope)?,
+        Expr::Quotient(a, b) => infer_arithmetic(Divide, a, b, params, scope)?,
+        Expr::Sum(a, b) => infer_arithmetic(Add, a, b, params, scope)?,
+        Expr::Difference(a, b) => infer_arithmetic(Subtract, a, b, params, scope)?,
+        Expr::Comparison(c) => {
+            check_comparison(c, params, scope).map_err(|e| e.or_span(c.span))?;
+            DataType::Boolean.into()
+        }
+    };
+    Ok(t)
+}
+
+fn infer_variable(name: &str, params: &Params, scope: &mut Scope) -> Result<Type, Error> {
+    let t = match name {
+        VAR_NOW => DataType::Timestamp.into(),
+        VAR_INFINITY => Type::Unknown,
+        VAR_TRUE | VAR_FALSE => DataType::Boolean.into(),
+        VAR_NULL => Type::Null,
+        name => {
+            if let Some(t) = params.get(name) {
+                return Ok(*t);
+            }
+            // A parameter of a function whose body is being converted rather than inferred
+            if scope.get_param(name).is_some() {
+                return Ok(Type::Unknown);
+            }
+            let Some(constant) = scope.get_constant(name) else {
+                return Ok(Type::Unknown);
+            };
+            let body = &constant.definition.body;
+            infer_definition(constant, scope, |scope| infer(body, &Params::new(), scope))?
+        }
+    };
+    Ok(t)
+}
+
+/// Run `f` to infer the type of a definition's body. Failing to expand the definition (e.g.
+/// because it's circular) is reported during conversion.
+fn infer_definition(
+    scoped: &ScopedDefinition,
+    scope: &mut Scope,
+    f: impl FnOnce(&mut Scope) -> Result<Type, Error>,
+) -> Result<Type, Error> {
+    scope
+        .expand_definition(scoped, HashMap::new(), |scope| Ok(f(scope)))
+        .unwrap_or(Ok(Type::Unknown))
+}
+
+fn infer_path(parts: &[PathPart], params: &Params, scope: &mut Scope) -> Result<Type, Error> {
+    let mut prefixed_parts: Vec<PathPart> =
+        scope.path_prefix.iter().chain(parts).cloned().collect();
+    if let Some((_, rest)) = split_at_pick(&mut prefixed_parts) {
+        let Ok(clarified_path) = clarify_path(prefixed_parts, scope) else {
+            return Ok(Type::Unknown);
+        };
+        return infer_at_end(clarified_path, scope, |s| infer_path(&rest, params, s));
+    }
+    if let Some(rest) = split_at_transitive(&mut prefixed_parts) {
+        let Ok(clarified_path) = clarify_path(prefixed_parts, scope) else {
+            return Ok(Type::Unknown);
+        };
+        return infer_at_end(clarified_path, scope, |s| infer_path(&rest, params, s));
+    }
+    if let Some(expr) = pop_path_expr(&mut prefixed_parts) {
+        return scope.with_path_prefix(prefixed_parts, |scope| infer(&expr, params, scope));
+    }
+    if let Some((column, head)) = get_computed_column(&prefixed_parts, scope) {
+        let body = &column.definition.body;
+        return infer_definition(column, scope, |scope| {
+            scope.with_path_prefix(head.to_vec(), |scope| infer(body, &Params::new(), scope))
+        });
+    }
+    let Ok(clarified_path) = clarify_path(prefixed_parts, scope) else {
+        return Ok(Type::Unknown);
+    };
+    let schema = scope.schema;
+    let t = match (clarified_path.head, clarified_path.tail) {
+        (head, Some(ClarifiedPathTail::Column(column_name))) => {
+            let table = match head {
+                Some(chain) => schema.tables.get(&chain.get_ending_table_id()).unwrap(),
+                None => scope.get_base_table(),
+            };
+            scope
+                .options
+                .resolve_identifier(&table.column_lookup, &column_name)
+                .and_then(|column_id| table.columns.get(column_id))
+                .and_then(|column| column.data_type)
+                .into()
+        }
+        (Some(chain_to_one), None) => {
+            let (_, last_link) = chain_to_one.with_last_link_broken_off();
+            let reference = last_link.get_start();
+            let table = schema.tables.get(&reference.table_id).unwrap();
+            let column = table.columns.get(&reference.column_id).unwrap();
+            column.data_type.into()
+        }
+        // Without an aggregate function, related records are counted
+        (_, Some(ClarifiedPathTail::ChainToMany((_, None)))) => DataType::Integer.into(),
+        _ => Type::Unknown,
+    };
+    Ok(t)
+}
+
+/// Run `f` within the scope of the table at the end of the path, where the CTE which supplies the
+/// path's value converts its expressions
+fn infer_at_end(
+    clarified_path: ClarifiedPath,
+    scope: &Scope,
+    f: impl FnOnce(&mut Scope) -> Result<Type, Error>,
+) -> Result<Type, Error> {
+    let (start, end) = match (clarified_path.head, clarified_path.tail) {
+        (_, Some(ClarifiedPathTail::ChainToMany((chain, _)))) => {
+            (chain.get_starting_table_id(), chain.get_ending_table_id())
+        }
+        // A self-referential link to one, followed transitively
+        (Some(chain), None) => (chain.get_ending_table_id(), chain.get_ending_table_id()),
+        _ => return Ok(Type::Unknown),
+    };
+    let schema = scope.schema;
+    let mut end_scope = scope.spawn(schema.tables.get(&end).unwrap());
+    end_scope.set_outer_table(schema.tables.get(&start).unwrap());
+    f(&mut end_scope)
+}
+
+fn infer_outer(expr: &Expr, params: &Params, scope: &mut Scope) -> Result<Type, Error> {
+    let Some(outer_table) = scope.get_outer_table() else {
+        return Ok(Type::Unknown);
+    };
+    let mut outer_scope = scope.spawn(outer_table);
+    infer(expr, params, &mut outer_scope)
+}
+
+fn infer_call(call: &Call, params: &Params, scope: &mut Scope) -> Result<Type, Error> {
+    match call.dimension {
+        FunctionDimension::Scalar => infer_scalar_call(&call.name, &call.args, params, scope),
+        FunctionDimension::Aggregate => infer_aggregate_call(call, params, scope),
+    }
+}
+
+fn infer_scalar_call(
+    name: &str,
+    args: &[Expr],
+    params: &Params,
+    scope: &mut Scope,
+) -> Result<Type, Error> {
+    let arg_types = args
+        .iter()
+        .map(|arg| infer(arg, params, scope))
+        .collect::<Result<Vec<_>, _>>()?;
+    if let Some(function) = scope.get_user_function(name) {
+        let definition = &function.definition;
+        if definition.params.len() != arg_types.len() {
+            return Ok(Type::Unknown);
+        }
+        let function_params: Params = definition.params.iter().cloned().zip(arg_types).collect();
+        return infer_definition(function, scope, |scope| {
+            infer(&definition.body, &function_params, scope)
+        });
+    }
+    match scope.get_scalar_function(name) {
+        Some(function) => function.signature.apply(name, &arg_types),
+        // Custom functions don't declare types
+        None => Ok(Type::Unknown),
+    }
+}
+
+/// Like `convert_aggregate_call_through_pick`, the aggregate is inferred relative to the picked
+/// record when the aggregated path passes through a pick.
+fn infer_aggregate_call(call: &Call, params: &Params, scope: &mut Scope) -> Result<Type, Error> {
+    let Some(Expr::Path(parts)) = call.args.first() else {
+        return Ok(Type::Unknown);
+    };
+    let mut prefixed_parts: Vec<PathPart> =
+        scope.path_prefix.iter().chain(parts).cloned().collect();
+    if let Some((_, rest)) = split_at_pick(&mut prefixed_parts) {
+        let Ok(clarified_path) = clarify_path(prefixed_parts, scope) else {
+            return Ok(Type::Unknown);
+        };
+        let mut picked_call = call.clone();
+        picked_call.args[0] = Expr::Path(rest);
+        return infer_at_end(clarified_path, scope, |s| {
+            infer_aggregate_call(&picked_call, params, s)
+        });
+    }
+    let value_type = infer_aggregate_value(prefixed_parts, params, scope)?;
+    match scope.get_aggregate_function(&call.name) {
+        Some(function) => function.signature.apply(&call.name, &[value_type]),
+        None => Ok(Type::Unknown),
+    }
+}
+
+/// The type of the values aggregated at the end of the path
+fn infer_aggregate_value(
+    mut parts: Vec<PathPart>,
+    params: &Params,
+    scope: &mut Scope,
+) -> Result<Type, Error> {
+    if let Some(rest) = split_at_transitive(&mut parts) {
+        let Ok(clarified_path) = clarify_path(parts, scope) else {
+            return Ok(Type::Unknown);
+        };
+        return infer_at_end(clarified_path, scope, |s| infer_path(&rest, params, s));
+    }
+    let value_expr_opt = pop_path_expr(&mut parts);
+    let Ok(clarified_path) = clarify_path(parts, scope) else {
+        return Ok(Type::Unknown);
+    };
+    let value_expr = match (value_expr_opt, &clarified_path.tail) {
+        (Some(expr), Some(ClarifiedPathTail::ChainToMany((_, None)))) => expr,
+        (None, Some(ClarifiedPathTail::ChainToMany((_, Some(column_name))))) => {
+            Expr::Path(vec![PathPart::column(column_name.clone())])
+        }
+        _ => return Ok(Type::Unknown),
+    };
+    infer_at_end(clarified_path, scope, |s| infer(&value_expr, params, s))
+}
+
+fn infer_arithmetic(
+    operator: ArithmeticOperator,
+    a: &Expr,
+    b: &Expr,
+    params: &Params,
+    scope: &mut Scope,
+) -> Result<Type, Error> {
+    let left = infer(a, params, scope)?;
+    let right = infer(b, params, scope)?;
+    arithmetic_type(operator, left, right)
+}
+
+/// Check that every pair of values compared (including those within expansions and ranges) have
+/// compatible types
+fn check_comparison(
+    comparison: &Comparison,
+    params: &Params,
+    scope: &mut Scope,
+) -> Result<(), Error> {
+    let left = infer_comparison_side(&comparison.left, params, scope)?;
+    let right = infer_comparison_side(&comparison.right, params, scope)?;
+    for l in left.iter() {
+        for r in right.iter() {
+            check_operands(comparison.operator, *l, *r)?;
+        }
+    }
+    Ok(())
+}
+
+fn infer_comparison_side(
+    side: &ComparisonSide,
+    params: &Params,
+    scope: &mut Scope,
+) -> Result<Vec<Type>, Error> {
+    let exprs = match side {
+        ComparisonSide::Expr(expr) => vec![expr],
+        ComparisonSide::Expansion(condition_set) => condition_set.entries.iter().collect(),
+        ComparisonSide::Range(range) => vec![&range.lower.expr, &range.upper.expr],
+    };
+    exprs
+        .into_iter()
+        .map(|expr| infer(expr, params, scope))
+        .collect()
+}
+
+fn check_operands(operator: Operator, left: Type, right: Type) -> Result<(), Error> {
+    use Operator::*;
+    if matches!(operator, Like | NLike | Match | NMatch) {
+        for t in [left, right] {
+            if !Accepts::Text.accepts(t) {
+                return Err(msg::pattern_on_non_text(&t));
+            }
+        }
+        return Ok(());
+    }
+    match common_type(left, right) {
+        Some(_) => Ok(()),
+        None => Err(msg::incompatible_comparison(&left, &right)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        errors::msg, schema::data_type::DataType, tests::get_test_resource, Compiler, Error,
+        Options,
+    };
+
+    fn compile_with_schema(schema_file: &str, input: &str) -> Result<String, Error> {
+        let schema = get_test_resource(schema_file);
+        let compiler = Compiler::new(&schema, Options::default()).unwrap();
+        // Errors are compared without their locations, which are tested separately
+        compiler
+            .compile(input.to_owned())
+            .map_err(Error::without_location)
+    }
+
+    fn compile(input: &str) -> Result<String, Error> {
+        compile_with_schema("issue_schema.json", input)
+    }
+
+    #[test]
+    fn test_comparison_types() {
+        assert_eq!(
+            compile("#issues title:>@2020-01-01"),
+            Err(msg::incompatible_comparison(
+                &DataType::Text,
+                &DataType::Date
+            ))
+        );
+        assert_eq!(
+            compile("#issues [title description]..:5"),
+            Err(msg::incompatible_comparison(
+                &DataType::Text,
+                &DataType::Integer
+            ))
+        );
+        assert_eq!(
+            compile("#issues id:~\"1\""),
+            Err(msg::pattern_on_non_text(&DataType::Integer))
+        );
+        assert_eq!(
+            compile("#issues ++#comments{id:\"x\"}"),
+            Err(msg::incompatible_comparison(
+                &DataType::Integer,
+                &DataType::Text
+            ))
+        );
+        assert!(compile("#issues created_at:>@2020-01-01 due_date:@null").is_ok());
+        assert!(compile("#issues created_at:>@1y|ago #comments:>2").is_ok());
+        assert!(compile("#issues created_at:(@2Y|ago)..(@1Y|ago)").is_ok());
+        // String literals can be read as values of types without literals of their own
+        assert!(compile("#issues created_at:>\"2020-01-01\" $created_at:\"x\"").is_ok());
+    }
+
+    #[test]
+    fn test_arithmetic_types() {
+        assert_eq!(
+            compile("#issues $created_at + \"x\""),
+            Err(msg::invalid_operands(
+                "+",
+                &DataType::Timestamp,
+                &DataType::Text
+            ))
+        );
+        assert!(compile("#issues $created_at + @1d $id * 2.5 $due_date - created_at").is_ok());
+    }
+
+    #[test]
+    fn test_function_types() {
+        assert_eq!(
+            compile("#issues $#comments.body%sum"),
+            Err(msg::wrong_arg_type(
+                "sum",
+                "a number or duration",
+                &DataType::Text
+            ))
+        );
+        assert_eq!(
+            compile("#issues $title|year"),
+            Err(msg::wrong_arg_type(
+                "year",
+                "a date or time",
+                &DataType::Text
+            ))
+        );
+        assert_eq!(
+            compile("#issues $#comments.(body|length)%avg:>\"x\""),
+            Err(msg::incompatible_comparison(
+                &DataType::Decimal,
+                &DataType::Text
+            ))
+        );
+        assert!(compile("#issues $#comments.(body|length)%avg $title|length|abs").is_ok());
+    }
+
+    #[test]
+    fn test_user_definition_types() {
+        assert_eq!(
+            compile("@@double = @x; @x * 2 #issues $title|double"),
+            Err(msg::invalid_operands(
+                "*",
+                &DataType::Text,
+                &DataType::Integer
+            ))
+        );
+        assert_eq!(
+            compile("#issues.age = @now - created_at\n#issues age:\"x\""),
+            Err(msg::incompatible_comparison(
+                &DataType::Interval,
+                &DataType::Text
+            ))
+        );
+        assert!(compile("@@double = @x; @x * 2 #issues $id|double").is_ok());
+    }
+
+    #[test]
+    fn test_untyped_columns() {
+        let compile = |input| compile_with_schema("library_schema.json", input);
+        assert!(compile("#books title:>@2020-01-01 $title + 1 $#checkouts.id%sum").is_ok());
+        assert_eq!(
+            compile("#books $title + \"x\" * 2"),
+            Err(msg::invalid_operands(
+                "*",
+                &DataType::Text,
+                &DataType::Integer
+            ))
+        );
+    }
+}
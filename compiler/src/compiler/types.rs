@@ -0,0 +1,323 @@
+use std::fmt::Display;
+
+use crate::{
+    errors::{msg, Error},
+    schema::data_type::DataType,
+};
+
+/// The type of a Querydown expression, as far as it can be inferred before the SQL is executed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Type {
+    /// Values from columns which have no type in the schema, along with any other values whose
+    /// type can't be inferred. These are compatible with every type so that untyped schemas
+    /// compile just as they would without type checking.
+    Unknown,
+    /// `@null`, which is compatible with every type
+    Null,
+    /// A string literal. Like an untyped SQL literal, it's text which can also be compared with
+    /// values of types that have no literal of their own, e.g. `created_at:>"2020-01-01 12:00"`.
+    StringLiteral,
+    Known(DataType),
+}
+
+impl Type {
+    /// The data type, unless the type is unknown or null
+    pub fn data_type(&self) -> Option<DataType> {
+        match self {
+            Type::Known(data_type) => Some(*data_type),
+            Type::StringLiteral => Some(DataType::Text),
+            Type::Unknown | Type::Null => None,
+        }
+    }
+
+    fn is_text(&self) -> bool {
+        matches!(self, Type::Known(DataType::Text) | Type::StringLiteral)
+    }
+
+    /// True for types whose values can be written as a string literal
+    fn accepts_string_literal(&self) -> bool {
+        use DataType::*;
+        matches!(
+            self,
+            Type::Known(Text | Date | Time | Timestamp | Uuid | Json | Binary)
+        )
+    }
+
+    fn is_numeric(&self) -> bool {
+        matches!(self, Type::Known(DataType::Integer | DataType::Decimal))
+    }
+
+    fn is_temporal(&self) -> bool {
+        matches!(
+            self,
+            Type::Known(DataType::Date | DataType::Time | DataType::Timestamp)
+        )
+    }
+
+    /// True when the type could be an operand of arithmetic
+    fn is_arithmetic(&self) -> bool {
+        match self {
+            Type::Unknown | Type::Null => true,
+            Type::Known(DataType::Interval) => true,
+            t => t.is_numeric() || t.is_temporal(),
+        }
+    }
+}
+
+impl From<DataType> for Type {
+    fn from(data_type: DataType) -> Self {
+        Type::Known(data_type)
+    }
+}
+
+impl From<Option<DataType>> for Type {
+    fn from(data_type: Option<DataType>) -> Self {
+        data_type.map(Type::Known).unwrap_or(Type::Unknown)
+    }
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Unknown => write!(f, "unknown"),
+            Type::Null => write!(f, "null"),
+            Type::StringLiteral => write!(f, "{}", DataType::Text),
+            Type::Known(data_type) => write!(f, "{}", data_type),
+        }
+    }
+}
+
+/// The type which two values can both be converted to, e.g. for comparing them. `None` when the
+/// values are incompatible.
+pub fn common_type(a: Type, b: Type) -> Option<Type> {
+    use DataType::*;
+    match (a, b) {
+        (Type::Unknown, _) | (_, Type::Unknown) => Some(Type::Unknown),
+        (Type::Null, t) | (t, Type::Null) => Some(t),
+        (a, b) if a == b => Some(a),
+        (Type::StringLiteral, t) | (t, Type::StringLiteral) if t.accepts_string_literal() => {
+            Some(t)
+        }
+        (a, b) if a.is_numeric() && b.is_numeric() => Some(Decimal.into()),
+        (Type::Known(Date | Timestamp), Type::Known(Date | Timestamp)) => Some(Timestamp.into()),
+        (Type::Known(Text | Uuid), Type::Known(Text | Uuid)) => Some(Uuid.into()),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArithmeticOperator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+impl Display for ArithmeticOperator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let symbol = match self {
+            Self::Add => "+",
+            Self::Subtract => "-",
+            Self::Multiply => "*",
+            Self::Divide => "/",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+/// The type of the result of arithmetic, following the rules common to SQL databases. Division of
+/// integers varies by database, so we call it a decimal.
+pub fn arithmetic_type(
+    operator: ArithmeticOperator,
+    left: Type,
+    right: Type,
+) -> Result<Type, Error> {
+    use ArithmeticOperator::*;
+    use DataType::*;
+    let invalid = || msg::invalid_operands(&operator.to_string(), &left, &right);
+    if !left.is_arithmetic() || !right.is_arithmetic() {
+        return Err(invalid());
+    }
+    let (Type::Known(l), Type::Known(r)) = (left, right) else {
+        return Ok(Type::Unknown);
+    };
+    let data_type = match (operator, l, r) {
+        (Divide, Integer | Decimal, Integer | Decimal) => Decimal,
+        (_, Integer, Integer) => Integer,
+        (_, Integer | Decimal, Integer | Decimal) => Decimal,
+        (Add, Date, Integer) | (Add, Integer, Date) | (Subtract, Date, Integer) => Date,
+        (Subtract, Date, Date) => Integer,
+        (Add | Subtract, Date | Timestamp, Interval) | (Add, Interval, Date | Timestamp) => {
+            Timestamp
+        }
+        (Add | Subtract, Time, Interval) | (Add, Interval, Time) => Time,
+        (Subtract, Date | Timestamp, Date | Timestamp) => Interval,
+        (Add | Subtract, Interval, Interval) => Interval,
+        (Multiply, Interval, Integer | Decimal)
+        | (Multiply, Integer | Decimal, Interval)
+        | (Divide, Interval, Integer | Decimal) => Interval,
+        _ => return Err(invalid()),
+    };
+    Ok(data_type.into())
+}
+
+/// The types of arguments that a built-in function accepts and the type that it returns
+#[derive(Debug, Clone, Copy)]
+pub struct Signature {
+    /// The type accepted by each argument. The last entry applies to any further arguments.
+    pub params: &'static [Accepts],
+    pub returns: Returns,
+}
+
+pub const fn sig(params: &'static [Accepts], returns: Returns) -> Signature {
+    Signature { params, returns }
+}
+
+impl Signature {
+    /// Check the types of the arguments given to the function named `name` and return the type of
+    /// its result
+    pub fn apply(&self, name: &str, args: &[Type]) -> Result<Type, Error> {
+        for (index, arg) in args.iter().enumerate() {
+            let accepts = self.params.get(index).or(self.params.last());
+            if let Some(accepts) = accepts {
+                if !accepts.accepts(*arg) {
+                    return Err(msg::wrong_arg_type(name, accepts.describe(), arg));
+                }
+            }
+        }
+        self.returns.get_type(name, args)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Accepts {
+    Any,
+    Number,
+    Text,
+    Boolean,
+    /// A date, time, or timestamp
+    Temporal,
+    Interval,
+    NumberOrInterval,
+    TemporalOrInterval,
+}
+
+impl Accepts {
+    /// Unknown types and nulls are accepted everywhere
+    pub fn accepts(&self, t: Type) -> bool {
+        if matches!(t, Type::Unknown | Type::Null) {
+            return true;
+        }
+        let interval = Type::Known(DataType::Interval);
+        match self {
+            Accepts::Any => true,
+            Accepts::Number => t.is_numeric(),
+            Accepts::Text => t.is_text(),
+            Accepts::Boolean => t == Type::Known(DataType::Boolean),
+            Accepts::Temporal => t.is_temporal(),
+            Accepts::Interval => t == interval,
+            Accepts::NumberOrInterval => t.is_numeric() || t == interval,
+            Accepts::TemporalOrInterval => t.is_temporal() || t == interval,
+        }
+    }
+
+    fn describe(&self) -> &'static str {
+        match self {
+            Accepts::Any => "any value",
+            Accepts::Number => "a number",
+            Accepts::Text => "text",
+            Accepts::Boolean => "a boolean",
+            Accepts::Temporal => "a date or time",
+            Accepts::Interval => "a duration",
+            Accepts::NumberOrInterval => "a number or duration",
+            Accepts::TemporalOrInterval => "a date, time or duration",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Returns {
+    Type(DataType),
+    FirstArg,
+    /// The common type of all the arguments (see `common_type`)
+    CommonArg,
+    Arithmetic(ArithmeticOperator),
+    /// A moment relative to now for a duration (e.g. `@1y|ago`), or the duration between now and
+    /// a moment (e.g. `created_at|age`)
+    RelativeToNow,
+    /// A decimal for numbers, or a duration for durations
+    Average,
+    Unknown,
+}
+
+impl Returns {
+    fn get_type(&self, name: &str, args: &[Type]) -> Result<Type, Error> {
+        let first = args.first().copied().unwrap_or(Type::Unknown);
+        let interval = Type::Known(DataType::Interval);
+        let t = match self {
+            Returns::Type(data_type) => Type::Known(*data_type),
+            Returns::FirstArg => first,
+            Returns::CommonArg => {
+                let mut common = first;
+                for arg in args.iter().skip(1) {
+                    common = common_type(common, *arg)
+                        .ok_or_else(|| msg::incompatible_args(name, &common, arg))?;
+                }
+                common
+            }
+            Returns::Arithmetic(operator) => match args {
+                [a, b] => arithmetic_type(*operator, *a, *b)?,
+                _ => Type::Unknown,
+            },
+            Returns::RelativeToNow if first == interval => DataType::Timestamp.into(),
+            Returns::RelativeToNow if first.is_temporal() => interval,
+            Returns::RelativeToNow => Type::Unknown,
+            Returns::Average if first == interval => interval,
+            Returns::Average if first.is_numeric() => DataType::Decimal.into(),
+            Returns::Average | Returns::Unknown => Type::Unknown,
+        };
+        Ok(t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ArithmeticOperator::*;
+    use DataType::*;
+
+    #[test]
+    fn test_arithmetic_type() {
+        let t = |op, a: DataType, b: DataType| arithmetic_type(op, a.into(), b.into()).ok();
+        assert_eq!(t(Add, Integer, Integer), Some(Integer.into()));
+        assert_eq!(t(Divide, Integer, Integer), Some(Decimal.into()));
+        assert_eq!(t(Multiply, Integer, Decimal), Some(Decimal.into()));
+        assert_eq!(t(Add, Date, Integer), Some(Date.into()));
+        assert_eq!(t(Subtract, Timestamp, Interval), Some(Timestamp.into()));
+        assert_eq!(t(Subtract, Timestamp, Date), Some(Interval.into()));
+        assert_eq!(t(Divide, Interval, Integer), Some(Interval.into()));
+        assert_eq!(t(Add, Timestamp, Timestamp), None);
+        assert_eq!(t(Add, Text, Integer), None);
+        assert_eq!(
+            arithmetic_type(Add, Type::Unknown, Integer.into()),
+            Ok(Type::Unknown)
+        );
+        assert!(arithmetic_type(Add, Type::Unknown, Boolean.into()).is_err());
+    }
+
+    #[test]
+    fn test_common_type() {
+        assert_eq!(
+            common_type(Integer.into(), Decimal.into()),
+            Some(Decimal.into())
+        );
+        assert_eq!(common_type(Type::Null, Text.into()), Some(Text.into()));
+        assert_eq!(common_type(Type::Unknown, Text.into()), Some(Type::Unknown));
+        assert_eq!(common_type(Text.into(), Date.into()), None);
+        assert_eq!(
+            common_type(Type::StringLiteral, Timestamp.into()),
+            Some(Timestamp.into())
+        );
+        assert_eq!(common_type(Type::StringLiteral, Integer.into()), None);
+    }
+}
@@ -0,0 +1,124 @@
+use crate::{
+    errors::{msg, Error},
+    sql::Dialect,
+    ColumnOrigin,
+};
+
+/// A parameterized statement which updates the value of one cell within the results
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CellUpdate<K, V> {
+    pub sql: String,
+    /// The value to bind to the statement's first parameter
+    pub new_value: V,
+    /// The value to bind to the statement's second parameter
+    pub key_value: K,
+}
+
+/// Build an `UPDATE` statement which sets the cell's underlying column to `new_value` in the row
+/// identified by `key_value`. For columns from related records, the key value is the value of the
+/// `from` column in the last step of the origin's path.
+pub fn build_cell_update<K, V>(
+    origin: &ColumnOrigin,
+    key_value: K,
+    new_value: V,
+    dialect: &dyn Dialect,
+) -> Result<CellUpdate<K, V>, Error> {
+    let source = match origin {
+        ColumnOrigin::TableColumn(source) => source,
+        ColumnOrigin::Computed => return Err(msg::computed_cell_update()),
+        ColumnOrigin::Aggregated => return Err(msg::aggregated_cell_update()),
+    };
+    let Some(key) = &source.key else {
+        return Err(msg::cell_update_without_key(&source.table));
+    };
+    let sql = format!(
+        "UPDATE {} SET {} = {} WHERE {} = {};",
+        dialect.quote_identifier(&source.table),
+        dialect.quote_identifier(&source.column),
+        dialect.parameter(1),
+        dialect.quote_identifier(key),
+        dialect.parameter(2),
+    );
+    Ok(CellUpdate {
+        sql,
+        new_value,
+        key_value,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        errors::msg, tests::get_test_resource, CellUpdate, ColumnOrigin, Compiler, Options,
+    };
+
+    #[test]
+    fn test_cell_updates() {
+        let schema = get_test_resource("issue_schema.json");
+        let compiler = Compiler::new(&schema, Options::default()).unwrap();
+        let update = |input: &str| {
+            let output = compiler.compile_output(input.to_owned()).unwrap();
+            compiler.update_cell(&output.columns[0].origin, 7, "New value")
+        };
+        assert_eq!(
+            update("#issues $title"),
+            Ok(CellUpdate {
+                sql: r#"UPDATE "issues" SET "title" = $1 WHERE "id" = $2;"#.to_owned(),
+                new_value: "New value",
+                key_value: 7,
+            })
+        );
+        assert_eq!(
+            update("#issues $author.team.name"),
+            Ok(CellUpdate {
+                sql: r#"UPDATE "teams" SET "name" = $1 WHERE "id" = $2;"#.to_owned(),
+                new_value: "New value",
+                key_value: 7,
+            })
+        );
+        assert_eq!(
+            update("#issues $(title|length)"),
+            Err(msg::computed_cell_update())
+        );
+        assert_eq!(
+            update("#issues $#comments.id%count"),
+            Err(msg::aggregated_cell_update())
+        );
+        assert_eq!(
+            update("#issues $#comments%last(created_at).body"),
+            Err(msg::aggregated_cell_update())
+        );
+        assert_eq!(
+            update("#comments $body"),
+            Ok(CellUpdate {
+                sql: r#"UPDATE "comments" SET "body" = $1 WHERE "id" = $2;"#.to_owned(),
+                new_value: "New value",
+                key_value: 7,
+            })
+        );
+
+        // The key of a related record is found in the column which links to it
+        let output = compiler
+            .compile_output("#issues $author.team.name $author.team".to_owned())
+            .unwrap();
+        let (ColumnOrigin::TableColumn(value), ColumnOrigin::TableColumn(key)) =
+            (&output.columns[0].origin, &output.columns[1].origin)
+        else {
+            panic!("Expected both columns to come from tables");
+        };
+        let (last_step, path) = value.path.split_last().unwrap();
+        assert_eq!(key.table, last_step.from.table);
+        assert_eq!(key.column, last_step.from.column);
+        assert_eq!(key.path, path);
+
+        let schema = get_test_resource("library_schema.json");
+        let compiler = Compiler::new(&schema, Options::default()).unwrap();
+        let output = compiler
+            .compile_output("#Checkouts $Patron".to_owned())
+            .unwrap();
+        assert_eq!(
+            compiler.update_cell(&output.columns[0].origin, 7, 42),
+            Err(msg::cell_update_without_key("Checkouts"))
+        );
+    }
+}
@@ -0,0 +1,170 @@
+use std::fmt::{Display, Formatter};
+
+use crate::sql::{expr::SqlExpr, Dialect};
+
+/// Renders a call to a custom function, given the SQL of each argument.
+///
+/// For scalar functions, a piped value is the first argument. For aggregate functions, the first
+/// argument is the value being aggregated (e.g. `"comments"."created_at"` within the CTE), and any
+/// remaining arguments are those written within parentheses, converted relative to the aggregated
+/// table.
+pub type RenderFn = Box<dyn Fn(Vec<SqlExpr>, &dyn Dialect) -> Result<SqlExpr, String>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CustomFunctionKind {
+    Scalar,
+    Aggregate,
+}
+
+/// The number of arguments a custom function accepts, counting the piped or aggregated value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arity {
+    Exactly(usize),
+    AtLeast(usize),
+    Between(usize, usize),
+}
+
+impl Arity {
+    pub fn accepts(&self, count: usize) -> bool {
+        match *self {
+            Arity::Exactly(n) => count == n,
+            Arity::AtLeast(n) => count >= n,
+            Arity::Between(min, max) => (min..=max).contains(&count),
+        }
+    }
+
+    pub fn min(&self) -> usize {
+        match *self {
+            Arity::Exactly(n) | Arity::AtLeast(n) | Arity::Between(n, _) => n,
+        }
+    }
+}
+
+impl Display for Arity {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Arity::Exactly(n) => write!(f, "exactly {}", n),
+            Arity::AtLeast(n) => write!(f, "at least {}", n),
+            Arity::Between(min, max) => write!(f, "between {} and {}", min, max),
+        }
+    }
+}
+
+/// A function registered by the host application via `Options::custom_functions`
+pub struct CustomFunction {
+    pub name: String,
+    pub kind: CustomFunctionKind,
+    pub arity: Arity,
+    pub render: RenderFn,
+}
+
+impl CustomFunction {
+    pub fn scalar(
+        name: &str,
+        arity: Arity,
+        render: impl Fn(Vec<SqlExpr>, &dyn Dialect) -> Result<SqlExpr, String> + 'static,
+    ) -> Self {
+        Self {
+            name: name.to_owned(),
+            kind: CustomFunctionKind::Scalar,
+            arity,
+            render: Box::new(render),
+        }
+    }
+
+    pub fn aggregate(
+        name: &str,
+        arity: Arity,
+        render: impl Fn(Vec<SqlExpr>, &dyn Dialect) -> Result<SqlExpr, String> + 'static,
+    ) -> Self {
+        Self {
+            name: name.to_owned(),
+            kind: CustomFunctionKind::Aggregate,
+            arity,
+            render: Box::new(render),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{tests::get_test_resource, Compiler, Error, Options};
+
+    use super::*;
+
+    fn compiler(custom_functions: Vec<CustomFunction>) -> Result<Compiler, Error> {
+        let options = Options {
+            custom_functions,
+            ..Default::default()
+        };
+        Compiler::new(&get_test_resource("issue_schema.json"), options)
+    }
+
+    fn business_days() -> CustomFunction {
+        CustomFunction::scalar("business_days", Arity::Between(1, 2), |args, _| {
+            let args = args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+            Ok(SqlExpr::atom(format!("business_days({})", args.join(", "))))
+        })
+    }
+
+    fn p95() -> CustomFunction {
+        CustomFunction::aggregate("p95", Arity::Exactly(1), |args, _| {
+            Ok(SqlExpr::atom(format!(
+                "percentile_cont(0.95) WITHIN GROUP (ORDER BY {})",
+                args[0]
+            )))
+        })
+    }
+
+    fn clean(s: &str) -> String {
+        s.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    #[test]
+    fn test_custom_scalar_function() {
+        let compiler = compiler(vec![business_days()]).unwrap();
+        let sql = compiler
+            .compile("#issues $created_at|business_days(due_date)".to_owned())
+            .unwrap();
+        assert_eq!(
+            clean(&sql),
+            r#"SELECT business_days("issues"."created_at", "issues"."due_date") FROM "issues";"#
+        );
+        assert_eq!(
+            compiler
+                .compile("#issues $@@business_days".to_owned())
+                .map_err(|e| e.message),
+            Err(
+                "Function `business_days` expects between 1 and 2 argument(s) but received 0."
+                    .to_owned()
+            )
+        );
+    }
+
+    #[test]
+    fn test_custom_aggregate_function() {
+        let compiler = compiler(vec![p95()]).unwrap();
+        let sql = compiler
+            .compile("#issues $#comments.created_at%p95".to_owned())
+            .unwrap();
+        assert!(clean(&sql).contains(
+            r#"percentile_cont(0.95) WITHIN GROUP (ORDER BY "comments"."created_at") AS "v1""#
+        ));
+    }
+
+    #[test]
+    fn test_conflicts_are_reported_at_construction() {
+        let abs = CustomFunction::scalar("abs", Arity::Exactly(1), |args, _| Ok(args[0].clone()));
+        assert_eq!(
+            compiler(vec![abs]).err().map(|e| e.message),
+            Some(
+                "Custom function `abs` conflicts with a built-in function of the same name."
+                    .to_owned()
+            )
+        );
+        assert_eq!(
+            compiler(vec![p95(), p95()]).err().map(|e| e.message),
+            Some("Custom function `p95` is registered more than once.".to_owned())
+        );
+    }
+}
@@ -0,0 +1,543 @@
+use std::{fmt::Display, ops::Range};
+
+use querydown_parser::ParseError;
+use serde::Serialize;
+
+use super::msg;
+
+/// A range of characters within the querydown source code
+pub type Span = Range<usize>;
+
+/// A problem which prevented a query from compiling
+///
+/// Lists are boxed slices rather than `Vec`s to keep `Error` small, since it's returned from almost
+/// every function within the compiler.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Error {
+    pub code: ErrorCode,
+    pub message: String,
+    /// The location of the problem within the query, when known
+    pub span: Option<Span>,
+    /// Other locations within the query which help to explain the problem, e.g. where an unclosed
+    /// delimiter was opened
+    pub labels: Box<[Label]>,
+    /// Additional information, e.g. suggestions for fixing the problem
+    pub notes: Box<[String]>,
+    /// Querydown code which could replace the code at `span` to fix the problem, e.g. each of the
+    /// paths which an ambiguous path could have meant
+    pub replacements: Box<[String]>,
+    /// Further problems found alongside this one, e.g. other syntax errors found after the parser
+    /// recovered from the first
+    pub related: Box<[Error]>,
+}
+
+/// A location within the query, along with a description of its relevance to a problem
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+impl Error {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            span: None,
+            labels: Box::new([]),
+            notes: Box::new([]),
+            replacements: Box::new([]),
+            related: Box::new([]),
+        }
+    }
+
+    pub fn with_span(mut self, span: impl Into<Span>) -> Self {
+        self.span = Some(span.into());
+        self
+    }
+
+    /// Add a note suggesting names which may have been intended, when there are any
+    pub fn with_suggestions(self, suggestions: &[String]) -> Self {
+        self.with_optional_note(msg::did_you_mean(suggestions))
+    }
+
+    pub fn with_optional_note(self, note: Option<String>) -> Self {
+        match note {
+            Some(note) => self.with_note(note),
+            None => self,
+        }
+    }
+
+    /// Locate the error at `span`, unless it has already been located more precisely. This is
+    /// applied as errors propagate out of each AST node which carries a span.
+    pub fn or_span(mut self, span: impl Into<Span>) -> Self {
+        self.span.get_or_insert_with(|| span.into());
+        self
+    }
+
+    /// Remove the error's location, e.g. because it refers to source code other than the query
+    pub fn without_location(mut self) -> Self {
+        self.span = None;
+        self.labels = Box::new([]);
+        self.replacements = Box::new([]);
+        self
+    }
+
+    pub fn with_label(mut self, span: Span, message: impl Into<String>) -> Self {
+        let mut labels = self.labels.into_vec();
+        labels.push(Label {
+            span,
+            message: message.into(),
+        });
+        self.labels = labels.into_boxed_slice();
+        self
+    }
+
+    pub fn with_replacements(mut self, replacements: Vec<String>) -> Self {
+        self.replacements = replacements.into();
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        let mut notes = self.notes.into_vec();
+        notes.push(note.into());
+        self.notes = notes.into_boxed_slice();
+        self
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)?;
+        for note in &self.notes {
+            write!(f, "\n{}", note)?;
+        }
+        for error in &self.related {
+            write!(f, "\n{}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<ParseError> for Error {
+    fn from(error: ParseError) -> Self {
+        let mut errors = error.diagnostics.into_iter().map(|diagnostic| {
+            let error =
+                Error::new(ErrorCode::InvalidSyntax, diagnostic.message).with_span(diagnostic.span);
+            diagnostic.labels.into_iter().fold(error, |error, label| {
+                error.with_label(label.span, label.message)
+            })
+        });
+        match errors.next() {
+            Some(first) => Error {
+                related: errors.collect(),
+                ..first
+            },
+            None => Error::new(ErrorCode::InvalidSyntax, error.message),
+        }
+    }
+}
+
+/// Identifies the kind of problem. Codes are stable across releases so that hosts can handle
+/// specific errors without matching on messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(into = "&str")]
+pub enum ErrorCode {
+    /// The querydown code could not be parsed
+    InvalidSyntax,
+    NoCurrentTable,
+    ColNotInTable,
+    NoPathParts,
+    UnknownScalarFunction,
+    UnknownAggregateFunction,
+    UnknownVariable,
+    AggregateFnAppliedToAPathWithoutAColumn,
+    PathToManyWithColumnNameAndNoAggFn,
+    AggregateFnAppliedToPathToOne,
+    AggregateFnAppliedToANonPath,
+    MisplacedPick,
+    PickWithoutValue,
+    PathExprNotAtEnd,
+    QuantityAllWithoutConditions,
+    QuantityAllAppliedToPathToOne,
+    OuterReferenceOutsideRelatedTable,
+    RecordParamWithinPathToMany,
+    MisplacedTransitive,
+    TransitiveLinkNotSelfReferential,
+    TransitiveWithoutValue,
+    TransitivePathToManyWithoutAggregate,
+    ExpectedOneArg,
+    ExpectedTwoArgs,
+    ExpectedArgsBetween,
+    ExpectedAtLeastOneArg,
+    MultipleFkFromCol,
+    LinkingColumnNotALink,
+    NestedColumnsOnPathToOne,
+    NestedColumnWithoutName,
+    ColumnControlWithinNestedColumns,
+    NestedColumnsAllHidden,
+    ColumnGlobAfterNonFkColumn,
+    EmptyPath,
+    CompareTwoRanges,
+    CompareRangeWithoutEq,
+    UnknownTable,
+    ParamsOnNonFunction,
+    DuplicateDefinition,
+    CircularDefinition,
+    WrongArgCount,
+    NoModuleResolver,
+    ModuleNotFound,
+    InvalidModule,
+    CircularImport,
+    MissingExport,
+    InvalidPrelude,
+    CustomFunctionConflictsWithBuiltin,
+    DuplicateCustomFunction,
+    CustomAggregateWithoutValue,
+    WrongArgType,
+    TypeMismatch,
+    PercentileOutOfRange,
+    UnsupportedByDialect,
+    ComputedCellUpdate,
+    AggregatedCellUpdate,
+    CellUpdateWithoutKey,
+    SchemaNotJson,
+    SchemaUnknownTable,
+    SchemaUnknownColumn,
+    SchemaNullablePrimaryKey,
+    UnknownBaseTable,
+    PipelinesNotSupported,
+    NoPathFound,
+    AmbiguousPath,
+    InvalidPath,
+    /// A bug within the compiler
+    Internal,
+    /// Reported by the host application, e.g. from a custom function or module resolver
+    Host,
+}
+
+impl ErrorCode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCode::InvalidSyntax => "invalid_syntax",
+            ErrorCode::NoCurrentTable => "no_current_table",
+            ErrorCode::ColNotInTable => "col_not_in_table",
+            ErrorCode::NoPathParts => "no_path_parts",
+            ErrorCode::UnknownScalarFunction => "unknown_scalar_function",
+            ErrorCode::UnknownAggregateFunction => "unknown_aggregate_function",
+            ErrorCode::UnknownVariable => "unknown_variable",
+            ErrorCode::AggregateFnAppliedToAPathWithoutAColumn => {
+                "aggregate_fn_applied_to_a_path_without_a_column"
+            }
+            ErrorCode::PathToManyWithColumnNameAndNoAggFn => {
+                "path_to_many_with_column_name_and_no_agg_fn"
+            }
+            ErrorCode::AggregateFnAppliedToPathToOne => "aggregate_fn_applied_to_path_to_one",
+            ErrorCode::AggregateFnAppliedToANonPath => "aggregate_fn_applied_to_a_non_path",
+            ErrorCode::MisplacedPick => "misplaced_pick",
+            ErrorCode::PickWithoutValue => "pick_without_value",
+            ErrorCode::PathExprNotAtEnd => "path_expr_not_at_end",
+            ErrorCode::QuantityAllWithoutConditions => "quantity_all_without_conditions",
+            ErrorCode::QuantityAllAppliedToPathToOne => "quantity_all_applied_to_path_to_one",
+            ErrorCode::OuterReferenceOutsideRelatedTable => "outer_reference_outside_related_table",
+            ErrorCode::RecordParamWithinPathToMany => "record_param_within_path_to_many",
+            ErrorCode::MisplacedTransitive => "misplaced_transitive",
+            ErrorCode::TransitiveLinkNotSelfReferential => "transitive_link_not_self_referential",
+            ErrorCode::TransitiveWithoutValue => "transitive_without_value",
+            ErrorCode::TransitivePathToManyWithoutAggregate => {
+                "transitive_path_to_many_without_aggregate"
+            }
+            ErrorCode::ExpectedOneArg => "expected_one_arg",
+            ErrorCode::ExpectedTwoArgs => "expected_two_args",
+            ErrorCode::ExpectedArgsBetween => "expected_args_between",
+            ErrorCode::ExpectedAtLeastOneArg => "expected_at_least_one_arg",
+            ErrorCode::MultipleFkFromCol => "multiple_fk_from_col",
+            ErrorCode::LinkingColumnNotALink => "linking_column_not_a_link",
+            ErrorCode::NestedColumnsOnPathToOne => "nested_columns_on_path_to_one",
+            ErrorCode::NestedColumnWithoutName => "nested_column_without_name",
+            ErrorCode::ColumnControlWithinNestedColumns => "column_control_within_nested_columns",
+            ErrorCode::NestedColumnsAllHidden => "nested_columns_all_hidden",
+            ErrorCode::ColumnGlobAfterNonFkColumn => "column_glob_after_non_fk_column",
+            ErrorCode::EmptyPath => "empty_path",
+            ErrorCode::CompareTwoRanges => "compare_two_ranges",
+            ErrorCode::CompareRangeWithoutEq => "compare_range_without_eq",
+            ErrorCode::UnknownTable => "unknown_table",
+            ErrorCode::ParamsOnNonFunction => "params_on_non_function",
+            ErrorCode::DuplicateDefinition => "duplicate_definition",
+            ErrorCode::CircularDefinition => "circular_definition",
+            ErrorCode::WrongArgCount => "wrong_arg_count",
+            ErrorCode::NoModuleResolver => "no_module_resolver",
+            ErrorCode::ModuleNotFound => "module_not_found",
+            ErrorCode::InvalidModule => "invalid_module",
+            ErrorCode::CircularImport => "circular_import",
+            ErrorCode::MissingExport => "missing_export",
+            ErrorCode::InvalidPrelude => "invalid_prelude",
+            ErrorCode::CustomFunctionConflictsWithBuiltin => {
+                "custom_function_conflicts_with_builtin"
+            }
+            ErrorCode::DuplicateCustomFunction => "duplicate_custom_function",
+            ErrorCode::CustomAggregateWithoutValue => "custom_aggregate_without_value",
+            ErrorCode::WrongArgType => "wrong_arg_type",
+            ErrorCode::TypeMismatch => "type_mismatch",
+            ErrorCode::PercentileOutOfRange => "percentile_out_of_range",
+            ErrorCode::UnsupportedByDialect => "unsupported_by_dialect",
+            ErrorCode::ComputedCellUpdate => "computed_cell_update",
+            ErrorCode::AggregatedCellUpdate => "aggregated_cell_update",
+            ErrorCode::CellUpdateWithoutKey => "cell_update_without_key",
+            ErrorCode::SchemaNotJson => "schema_not_json",
+            ErrorCode::SchemaUnknownTable => "schema_unknown_table",
+            ErrorCode::SchemaUnknownColumn => "schema_unknown_column",
+            ErrorCode::SchemaNullablePrimaryKey => "schema_nullable_primary_key",
+            ErrorCode::UnknownBaseTable => "unknown_base_table",
+            ErrorCode::PipelinesNotSupported => "pipelines_not_supported",
+            ErrorCode::NoPathFound => "no_path_found",
+            ErrorCode::AmbiguousPath => "ambiguous_path",
+            ErrorCode::InvalidPath => "invalid_path",
+            ErrorCode::Internal => "internal",
+            ErrorCode::Host => "host",
+        }
+    }
+}
+
+impl From<ErrorCode> for &'static str {
+    fn from(code: ErrorCode) -> Self {
+        code.as_str()
+    }
+}
+
+impl Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{tests::get_test_resource, Compiler, ErrorCode, Label, Options};
+
+    fn compile_error(input: &str) -> super::Error {
+        let schema = get_test_resource("issue_schema.json");
+        let compiler = Compiler::new(&schema, Options::default()).unwrap();
+        compiler.compile(input.to_owned()).unwrap_err()
+    }
+
+    #[test]
+    fn test_error_codes_and_spans() {
+        let error = compile_error("#issues $title ] $id");
+        assert_eq!(error.code, ErrorCode::InvalidSyntax);
+        assert_eq!(error.span, Some(15..16));
+        let error = compile_error("#nope");
+        assert_eq!(error.code, ErrorCode::UnknownBaseTable);
+        assert_eq!(error.code.as_str(), "unknown_base_table");
+        assert_eq!(error.span, Some(0..5));
+    }
+
+    #[test]
+    fn test_errors_are_located_at_ast_nodes() {
+        let span = |input: &str| compile_error(input).span;
+        assert_eq!(span("#issues nope:1 $id"), Some(8..12));
+        assert_eq!(span("#issues $title $nope"), Some(16..20));
+        assert_eq!(span("#issues author.nope:1"), Some(15..19));
+        assert_eq!(span("#issues $author.nope|length"), Some(16..20));
+        assert_eq!(span(r"#issues $*(title nope \h)"), Some(17..21));
+        assert_eq!(span("#issues $#nope.id%count"), Some(9..14));
+        assert_eq!(span("#issues $title|nope"), Some(14..19));
+    }
+
+    #[test]
+    fn test_suggestions() {
+        let notes = |input: &str| compile_error(input).notes.into_vec();
+        assert_eq!(notes("#isues $id"), vec!["Did you mean `issues`?"]);
+        assert_eq!(notes("#issues $tilte"), vec!["Did you mean `title`?"]);
+        assert_eq!(
+            notes("#issues $created_on"),
+            vec!["Did you mean `created_at`?"]
+        );
+        assert_eq!(
+            notes("#issues $#coments.id%count"),
+            vec!["Did you mean `comments` or `clients`?"]
+        );
+        assert_eq!(
+            notes("#issues $#nope.id%count"),
+            vec!["Tables related to `issues`: `#assignments`, `#blocks`, `#comments`, `#issue_labels`, `#issues`."]
+        );
+        assert_eq!(
+            notes("#issues $title|lenght"),
+            vec!["Did you mean `length`?"]
+        );
+        assert_eq!(
+            notes("#issues $#comments.id%cuont"),
+            vec!["Did you mean `count`?"]
+        );
+        assert_eq!(notes("#issues $title|zzzzzz"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_ambiguous_paths() {
+        let error = compile_error("#assignments $id $#comments.id%count");
+        assert_eq!(error.code, ErrorCode::AmbiguousPath);
+        assert_eq!(error.span, Some(18..27));
+        let error = compile_error("#issues --#blocks");
+        assert_eq!(
+            error.message,
+            "The path to `#blocks` is ambiguous because several paths tie for being the shortest: \
+            `#blocks(blocker)`, `#blocks(blocking)`."
+        );
+    }
+
+    /// Each replacement offered for an ambiguous path compiles, and follows the links which it was
+    /// offered for
+    #[test]
+    fn test_ambiguous_path_replacements() {
+        let schema = get_test_resource("issue_schema.json");
+        let compiler = Compiler::new(&schema, Options::default()).unwrap();
+        let joins = |sql: String| {
+            sql.lines()
+                .map(str::trim)
+                .filter(|line| {
+                    line.starts_with("FROM") || line.contains("JOIN") || line.contains(r#"AS "pk""#)
+                })
+                .map(|line| {
+                    line.trim_end_matches(" ON")
+                        .trim_end_matches(',')
+                        .to_owned()
+                })
+                .collect::<Vec<_>>()
+        };
+        let replace = |input: &str| {
+            let error = compiler.compile(input.to_owned()).unwrap_err();
+            let span = error.span.unwrap();
+            error
+                .replacements
+                .iter()
+                .map(|replacement| {
+                    let query = format!(
+                        "{}{}{}",
+                        &input[..span.start],
+                        replacement,
+                        &input[span.end..]
+                    );
+                    (replacement.clone(), joins(compiler.compile(query).unwrap()))
+                })
+                .collect::<Vec<_>>()
+        };
+        let expected = |cases: &[(&str, &[&str])]| {
+            cases
+                .iter()
+                .map(|(r, j)| (r.to_string(), j.iter().map(|j| j.to_string()).collect()))
+                .collect::<Vec<(String, Vec<String>)>>()
+        };
+        assert_eq!(
+            replace("#assignments $#comments.id%count"),
+            expected(&[
+                (
+                    "issue.#comments",
+                    &[
+                        r#""comments"."issue" AS "pk""#,
+                        r#"FROM "comments""#,
+                        r#"FROM "assignments""#,
+                        r#"LEFT JOIN "issues""#,
+                        r#"LEFT JOIN "cte0""#,
+                    ]
+                ),
+                (
+                    "user.#comments",
+                    &[
+                        r#""comments"."user" AS "pk""#,
+                        r#"FROM "comments""#,
+                        r#"FROM "assignments""#,
+                        r#"LEFT JOIN "users""#,
+                        r#"LEFT JOIN "cte0""#,
+                    ]
+                ),
+            ])
+        );
+        assert_eq!(
+            replace("#blocks $#comments.id%count"),
+            expected(&[
+                (
+                    "blocker.#comments",
+                    &[
+                        r#""comments"."issue" AS "pk""#,
+                        r#"FROM "comments""#,
+                        r#"FROM "blocks""#,
+                        r#"LEFT JOIN "issues" AS "blocker""#,
+                        r#"LEFT JOIN "cte0""#,
+                    ]
+                ),
+                (
+                    "blocking.#comments",
+                    &[
+                        r#""comments"."issue" AS "pk""#,
+                        r#"FROM "comments""#,
+                        r#"FROM "blocks""#,
+                        r#"LEFT JOIN "issues" AS "blocking""#,
+                        r#"LEFT JOIN "cte0""#,
+                    ]
+                ),
+            ])
+        );
+        assert_eq!(
+            replace("#issues --#blocks"),
+            expected(&[
+                (
+                    "#blocks(blocker)",
+                    &[
+                        r#""blocks"."blocker" AS "pk""#,
+                        r#"FROM "blocks""#,
+                        r#"FROM "issues""#,
+                        r#"LEFT JOIN "cte0""#
+                    ]
+                ),
+                (
+                    "#blocks(blocking)",
+                    &[
+                        r#""blocks"."blocking" AS "pk""#,
+                        r#"FROM "blocks""#,
+                        r#"FROM "issues""#,
+                        r#"LEFT JOIN "cte0""#
+                    ]
+                ),
+            ])
+        );
+        // A table reached through a link to one is joined directly
+        assert_eq!(
+            joins(
+                compiler
+                    .compile("#issues $#assignments.#users.id%count".to_owned())
+                    .unwrap()
+            ),
+            vec![
+                r#""assignments"."issue" AS "pk""#,
+                r#"FROM "assignments""#,
+                r#"JOIN "users""#,
+                r#"FROM "issues""#,
+                r#"LEFT JOIN "cte0""#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_syntax_error_labels_and_related() {
+        let error = compile_error("#issues {title:1");
+        assert_eq!(error.message, "expected `}` to close condition set");
+        assert_eq!(error.span, Some(16..16));
+        assert_eq!(
+            error.labels.into_vec(),
+            vec![Label {
+                span: 8..9,
+                message: "condition set opened here".to_owned()
+            }]
+        );
+        let error = compile_error("#issues {a:} [b:] $id");
+        assert_eq!(error.span, Some(11..12));
+        let related_spans = error
+            .related
+            .iter()
+            .map(|e| e.span.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(related_spans, vec![Some(16..17)]);
+    }
+}
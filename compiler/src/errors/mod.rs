@@ -1 +1,4 @@
+mod error;
 pub mod msg;
+
+pub use error::*;
@@ -1,77 +1,536 @@
-pub fn no_current_table() -> String {
-    "Non-FK columns can only appear at the end of a path.".to_string()
+use std::fmt::Display;
+
+use itertools::Itertools;
+
+use super::{Error, ErrorCode};
+
+pub fn no_current_table() -> Error {
+    Error::new(
+        ErrorCode::NoCurrentTable,
+        "Non-FK columns can only appear at the end of a path.",
+    )
 }
 
-pub fn col_not_in_table(column_name: &str, table_name: &str) -> String {
-    format!("Column `{column_name}` not found within table `{table_name}`.")
+pub fn col_not_in_table(column_name: &str, table_name: &str) -> Error {
+    Error::new(
+        ErrorCode::ColNotInTable,
+        format!("Column `{column_name}` not found within table `{table_name}`."),
+    )
 }
 
-pub fn no_path_parts() -> String {
-    "Cannot build a ClarifiedPath without any path parts".to_string()
+pub fn no_path_parts() -> Error {
+    Error::new(
+        ErrorCode::NoPathParts,
+        "Cannot build a ClarifiedPath without any path parts",
+    )
 }
 
-pub fn unknown_scalar_function(function_name: &str) -> String {
-    format!("Scalar function `{}` does not exist.", function_name)
+pub fn unknown_scalar_function(function_name: &str) -> Error {
+    Error::new(
+        ErrorCode::UnknownScalarFunction,
+        format!("Scalar function `{}` does not exist.", function_name),
+    )
 }
 
-pub fn unknown_aggregate_function(function_name: &str) -> String {
-    format!("Aggregate function `{}` does not exist.", function_name)
+pub fn unknown_aggregate_function(function_name: &str) -> Error {
+    Error::new(
+        ErrorCode::UnknownAggregateFunction,
+        format!("Aggregate function `{}` does not exist.", function_name),
+    )
 }
 
-pub fn unknown_variable(variable_name: &str) -> String {
-    format!("Unknown variable `{}`.", variable_name)
+pub fn unknown_variable(variable_name: &str) -> Error {
+    Error::new(
+        ErrorCode::UnknownVariable,
+        format!("Unknown variable `{}`.", variable_name),
+    )
 }
 
-pub fn aggregate_fn_applied_to_a_path_without_a_column() -> String {
-    "A column must be specified when using an aggregate function.".to_string()
+pub fn aggregate_fn_applied_to_a_path_without_a_column() -> Error {
+    Error::new(
+        ErrorCode::AggregateFnAppliedToAPathWithoutAColumn,
+        "A column must be specified when using an aggregate function.",
+    )
 }
 
-pub fn path_to_many_with_column_name_and_no_agg_fn(column_name: &str) -> String {
-    format!(
-        "The column `{}` requires an aggregate function.",
-        column_name
+pub fn path_to_many_with_column_name_and_no_agg_fn(column_name: &str) -> Error {
+    Error::new(
+        ErrorCode::PathToManyWithColumnNameAndNoAggFn,
+        format!(
+            "The column `{}` requires an aggregate function.",
+            column_name
+        ),
+    )
+}
+
+pub fn aggregate_fn_applied_to_path_to_one() -> Error {
+    Error::new(
+        ErrorCode::AggregateFnAppliedToPathToOne,
+        "Aggregate functions can only be applied to data that joins many records.",
+    )
+}
+
+pub fn aggregate_fn_applied_to_a_non_path() -> Error {
+    Error::new(ErrorCode::AggregateFnAppliedToANonPath, "Aggregate functions must be applied directly to a path. To aggregate a computed value, put the computation in parentheses at the end of the path, e.g. `#comments.(body|length)%avg`.")
+}
+
+pub fn misplaced_pick() -> Error {
+    Error::new(ErrorCode::MisplacedPick, "`%first` and `%last` must directly follow a table with many, e.g. `#comments%last(created_at).body`.")
+}
+
+pub fn pick_without_value() -> Error {
+    Error::new(ErrorCode::PickWithoutValue, "A path through `%first` or `%last` must continue to a value, e.g. `#comments%last(created_at).body`.")
+}
+
+pub fn path_expr_not_at_end() -> Error {
+    Error::new(ErrorCode::PathExprNotAtEnd, "A parenthesized expression within a path must come at the end of the path, directly after a table or a link.")
+}
+
+pub fn quantity_all_without_conditions() -> Error {
+    Error::new(ErrorCode::QuantityAllWithoutConditions, "`**` must be applied to a path that ends with a table with many and its conditions, e.g. `**#assignments{user.team.name:\"Backend\"}`.")
+}
+
+pub fn quantity_all_applied_to_path_to_one() -> Error {
+    Error::new(
+        ErrorCode::QuantityAllAppliedToPathToOne,
+        "`**` can only be applied to a path that joins many records, e.g. `**#comments{user:@me}`.",
+    )
+}
+
+pub fn outer_reference_outside_related_table() -> Error {
+    Error::new(ErrorCode::OuterReferenceOutsideRelatedTable, "`&` refers to the record which related records belong to, so it can only be used within a path to many, e.g. `#issues ++#comments{created_at:>&due_date}`.")
+}
+
+pub fn record_param_within_path_to_many(name: &str) -> Error {
+    Error::new(
+        ErrorCode::RecordParamWithinPathToMany,
+        format!("The argument given for `@{name}` refers to the calling record, so `@{name}` can't be used within a path to many. Use `&` within the function instead, e.g. `++#comments{{user:&author}}`."),
+    )
+}
+
+pub fn misplaced_transitive() -> Error {
+    Error::new(ErrorCode::MisplacedTransitive, "`*` may appear only once within a path, directly after a self-referential link, e.g. `duplicate_of*.title`.")
+}
+
+pub fn transitive_link_not_self_referential() -> Error {
+    Error::new(ErrorCode::TransitiveLinkNotSelfReferential, "`*` must follow a link from a table to itself, e.g. `duplicate_of*.title` or `#issues(duplicate_of)*.title%list`.")
+}
+
+pub fn transitive_without_value() -> Error {
+    Error::new(
+        ErrorCode::TransitiveWithoutValue,
+        "A recursive path must continue after `*`, e.g. `duplicate_of*.title`.",
     )
 }
 
-pub fn aggregate_fn_applied_to_path_to_one() -> String {
-    "Aggregate functions can only be applied to data that joins many records.".to_string()
+pub fn transitive_path_to_many_without_aggregate() -> Error {
+    Error::new(ErrorCode::TransitivePathToManyWithoutAggregate, "A recursive path to many must end with an aggregate function, e.g. `#issues(duplicate_of)*.title%list`.")
 }
 
-pub fn aggregate_fn_applied_to_a_non_path() -> String {
-    "Aggregate functions must be applied directly to a column, without any intermediate computations. This restriction may be relaxed in future versions".to_string()
+pub fn expected_one_arg() -> Error {
+    Error::new(ErrorCode::ExpectedOneArg, "Expected exactly one argument.")
 }
 
-pub fn expected_one_arg() -> String {
-    "Expected exactly one argument.".to_string()
+pub fn expected_two_args() -> Error {
+    Error::new(
+        ErrorCode::ExpectedTwoArgs,
+        "Expected exactly two arguments.",
+    )
 }
 
-pub fn expected_two_args() -> String {
-    "Expected exactly two arguments.".to_string()
+pub fn expected_args_between(min: usize, max: usize) -> Error {
+    let message = if min == max {
+        format!("Expected exactly {} arguments.", min)
+    } else {
+        format!("Expected between {} and {} arguments.", min, max)
+    };
+    Error::new(ErrorCode::ExpectedArgsBetween, message)
 }
 
-pub fn multiple_fk_from_col() -> String {
-    "Schema has multiple foreign keys from the same column".to_string()
+pub fn expected_at_least_one_arg() -> Error {
+    Error::new(
+        ErrorCode::ExpectedAtLeastOneArg,
+        "Expected at least one argument.",
+    )
+}
+
+pub fn multiple_fk_from_col() -> Error {
+    Error::new(
+        ErrorCode::MultipleFkFromCol,
+        "Schema has multiple foreign keys from the same column",
+    )
 }
 
-pub fn column_glob_on_path_to_many() -> String {
-    "Column globs (`*`) can not be used on paths that reference many records.".to_string()
+pub fn linking_column_not_a_link(column_name: &str, table_name: &str) -> Error {
+    Error::new(ErrorCode::LinkingColumnNotALink, format!("The column `{column_name}` does not link `{table_name}` to the preceding table. To nest a single column of the related records, prefix it with `$`, e.g. `$#{table_name}(${column_name})`."))
 }
 
-pub fn column_glob_after_non_fk_column(column_name: &str) -> String {
-    format!("Column globs (`*`) can only be placed after foreign key columns. The column `{}` is not a foreign key column.", column_name)
+pub fn nested_columns_on_path_to_one() -> Error {
+    Error::new(ErrorCode::NestedColumnsOnPathToOne, "Nested columns can only be used on paths that reference many records, e.g. `$#comments(body created_at)`.")
+}
+
+pub fn nested_column_without_name() -> Error {
+    Error::new(ErrorCode::NestedColumnWithoutName, "Nested columns which are not paths to a column need an alias, e.g. `$#comments((body|length) -> length created_at)`.")
+}
+
+pub fn column_control_within_nested_columns() -> Error {
+    Error::new(
+        ErrorCode::ColumnControlWithinNestedColumns,
+        "Column control flags other than `\\h` can not be used within nested columns.",
+    )
+}
+
+pub fn nested_columns_all_hidden() -> Error {
+    Error::new(
+        ErrorCode::NestedColumnsAllHidden,
+        "At least one column must remain visible within nested columns.",
+    )
+}
+
+pub fn column_glob_after_non_fk_column(column_name: &str) -> Error {
+    Error::new(ErrorCode::ColumnGlobAfterNonFkColumn, format!("Column globs (`*`) can only be placed after foreign key columns. The column `{}` is not a foreign key column.", column_name))
 }
 
 /// If this happens, it's a bug somewhere up the stack.
 ///
 /// TODO: we should improve the ClarifiedPath data structure to make this impossible
-pub fn empty_path() -> String {
-    "Bug: Empty path.".to_string()
+pub fn empty_path() -> Error {
+    Error::new(ErrorCode::EmptyPath, "Bug: Empty path.")
+}
+
+pub fn compare_two_ranges() -> Error {
+    Error::new(ErrorCode::CompareTwoRanges, "Cannot compare two ranges.")
+}
+
+pub fn compare_range_without_eq() -> Error {
+    Error::new(
+        ErrorCode::CompareRangeWithoutEq,
+        "Range comparison must use the `:` operator.",
+    )
+}
+
+pub fn unknown_table(table_name: &str) -> Error {
+    Error::new(
+        ErrorCode::UnknownTable,
+        format!("Table `{}` does not exist.", table_name),
+    )
+}
+
+pub fn params_on_non_function(name: &impl Display) -> Error {
+    Error::new(
+        ErrorCode::ParamsOnNonFunction,
+        format!(
+            "Only functions can have parameters, but `{}` has parameters.",
+            name
+        ),
+    )
+}
+
+pub fn duplicate_definition(name: &impl Display) -> Error {
+    Error::new(
+        ErrorCode::DuplicateDefinition,
+        format!("`{}` is defined more than once.", name),
+    )
+}
+
+pub fn circular_definition(name: &impl Display) -> Error {
+    Error::new(
+        ErrorCode::CircularDefinition,
+        format!("`{}` is defined in terms of itself.", name),
+    )
+}
+
+/// `expected` is a count or a range of counts (see `Arity`)
+pub fn wrong_arg_count(function_name: &str, expected: &impl Display, actual: usize) -> Error {
+    Error::new(
+        ErrorCode::WrongArgCount,
+        format!(
+            "Function `{}` expects {} argument(s) but received {}.",
+            function_name, expected, actual
+        ),
+    )
+}
+
+pub fn no_module_resolver() -> Error {
+    Error::new(
+        ErrorCode::NoModuleResolver,
+        "Modules can't be imported because no module resolver is configured.",
+    )
+}
+
+pub fn module_not_found(path: &str) -> Error {
+    Error::new(
+        ErrorCode::ModuleNotFound,
+        format!("Module `{}` not found.", path),
+    )
+}
+
+pub fn invalid_module(path: &str, error: &impl Display) -> Error {
+    Error::new(
+        ErrorCode::InvalidModule,
+        format!("Unable to parse module `{}`: {}", path, error),
+    )
+}
+
+pub fn circular_import(paths: &[String]) -> Error {
+    let chain = paths.iter().map(|p| format!("`{}`", p)).join(" -> ");
+    Error::new(
+        ErrorCode::CircularImport,
+        format!("Circular module import: {}.", chain),
+    )
+}
+
+pub fn missing_export(name: &impl Display, module_path: &str) -> Error {
+    Error::new(
+        ErrorCode::MissingExport,
+        format!("Module `{}` does not export `{}`.", module_path, name),
+    )
+}
+
+pub fn invalid_prelude(error: &impl Display) -> Error {
+    Error::new(
+        ErrorCode::InvalidPrelude,
+        format!("Unable to parse prelude: {}", error),
+    )
+}
+
+pub fn custom_function_conflicts_with_builtin(function_name: &str) -> Error {
+    Error::new(
+        ErrorCode::CustomFunctionConflictsWithBuiltin,
+        format!(
+            "Custom function `{}` conflicts with a built-in function of the same name.",
+            function_name
+        ),
+    )
+}
+
+pub fn duplicate_custom_function(function_name: &str) -> Error {
+    Error::new(
+        ErrorCode::DuplicateCustomFunction,
+        format!(
+            "Custom function `{}` is registered more than once.",
+            function_name
+        ),
+    )
+}
+
+pub fn custom_aggregate_without_value(function_name: &str) -> Error {
+    Error::new(
+        ErrorCode::CustomAggregateWithoutValue,
+        format!(
+        "Custom aggregate function `{}` must accept at least one argument (the aggregated value).",
+        function_name
+    ),
+    )
+}
+
+pub fn wrong_arg_type(function_name: &str, expected: &str, actual: &impl Display) -> Error {
+    Error::new(
+        ErrorCode::WrongArgType,
+        format!(
+            "Function `{}` expects {} but received {}.",
+            function_name, expected, actual
+        ),
+    )
+}
+
+pub fn incompatible_args(function_name: &str, a: &impl Display, b: &impl Display) -> Error {
+    Error::new(
+        ErrorCode::TypeMismatch,
+        format!(
+            "Function `{}` received incompatible types {} and {}.",
+            function_name, a, b
+        ),
+    )
+}
+
+pub fn incompatible_comparison(left: &impl Display, right: &impl Display) -> Error {
+    Error::new(
+        ErrorCode::TypeMismatch,
+        format!("Can't compare {} with {}.", left, right),
+    )
+}
+
+pub fn invalid_operands(operator: &str, left: &impl Display, right: &impl Display) -> Error {
+    Error::new(
+        ErrorCode::TypeMismatch,
+        format!("Can't apply `{}` to {} and {}.", operator, left, right),
+    )
+}
+
+pub fn pattern_on_non_text(actual: &impl Display) -> Error {
+    Error::new(
+        ErrorCode::TypeMismatch,
+        format!("Pattern matching requires text, but {} was given.", actual),
+    )
+}
+
+pub fn percentile_out_of_range(fraction: &str) -> Error {
+    Error::new(
+        ErrorCode::PercentileOutOfRange,
+        format!(
+            "Percentile must be between 0 and 1, but `{}` was given.",
+            fraction
+        ),
+    )
+}
+
+pub fn unsupported_by_dialect(feature: &str, dialect_name: &str) -> Error {
+    Error::new(
+        ErrorCode::UnsupportedByDialect,
+        format!(
+            "{} is not supported by the {} dialect.",
+            feature, dialect_name
+        ),
+    )
+}
+
+pub fn computed_cell_update() -> Error {
+    Error::new(
+        ErrorCode::ComputedCellUpdate,
+        "Cells in computed columns can not be updated.",
+    )
+}
+
+pub fn aggregated_cell_update() -> Error {
+    Error::new(
+        ErrorCode::AggregatedCellUpdate,
+        "Cells in columns which aggregate related records can not be updated.",
+    )
+}
+
+pub fn cell_update_without_key(table_name: &str) -> Error {
+    Error::new(
+        ErrorCode::CellUpdateWithoutKey,
+        format!(
+            "Cells from `{}` can not be updated because no unique column identifies its rows.",
+            table_name
+        ),
+    )
+}
+
+pub fn schema_not_json() -> Error {
+    Error::new(ErrorCode::SchemaNotJson, "Schema input is not valid JSON.")
+}
+
+pub fn schema_unknown_table(table_name: &str) -> Error {
+    Error::new(
+        ErrorCode::SchemaUnknownTable,
+        format!("Unknown table: {}", table_name),
+    )
+}
+
+pub fn schema_unknown_column(column_name: &str) -> Error {
+    Error::new(
+        ErrorCode::SchemaUnknownColumn,
+        format!("Unknown column: {}", column_name),
+    )
+}
+
+pub fn schema_nullable_primary_key(table_name: &str, column_name: &str) -> Error {
+    Error::new(
+        ErrorCode::SchemaNullablePrimaryKey,
+        format!(
+            "Primary key column `{}` in table `{}` can not be nullable.",
+            column_name, table_name
+        ),
+    )
+}
+
+pub fn unknown_base_table(table_name: &str) -> Error {
+    Error::new(
+        ErrorCode::UnknownBaseTable,
+        format!("Base table `{}` does not exist.", table_name),
+    )
+}
+
+pub fn pipelines_not_supported() -> Error {
+    Error::new(
+        ErrorCode::PipelinesNotSupported,
+        "Pipelines not yet supported",
+    )
+}
+
+pub fn no_path_found(target: &str) -> Error {
+    Error::new(
+        ErrorCode::NoPathFound,
+        format!("No path to `{}` could be found.", target),
+    )
+}
+
+/// `paths` are the shortest paths to the target, each written as querydown code which could be
+/// used in place of the target to choose it
+pub fn ambiguous_path(target: &str, paths: Vec<String>) -> Error {
+    let list = paths.iter().map(|p| format!("`{}`", p)).join(", ");
+    Error::new(
+        ErrorCode::AmbiguousPath,
+        format!(
+            "The path to `{}` is ambiguous because several paths tie for being the shortest: {}.",
+            target, list
+        ),
+    )
+    .with_note("Specify one of these paths instead.")
+    .with_replacements(paths)
+}
+
+pub fn invalid_path(reason: &str) -> Error {
+    Error::new(ErrorCode::InvalidPath, reason)
+}
+
+/// Wraps an error from the host's module resolver
+pub fn unresolved_module(message: String) -> Error {
+    Error::new(ErrorCode::ModuleNotFound, message)
+}
+
+/// If this happens, it's a bug somewhere up the stack.
+pub fn internal(message: &str) -> Error {
+    Error::new(ErrorCode::Internal, message)
+}
+
+/// Wraps an error message supplied by the host application
+pub fn host(message: String) -> Error {
+    Error::new(ErrorCode::Host, message)
+}
+
+/// A warning about an imported definition which the query never uses
+pub fn unused_import(name: &impl Display, module_path: &str) -> String {
+    format!(
+        "`{}` is imported from module `{}` but never used.",
+        name, module_path
+    )
+}
+
+/// A warning about a module imported via `(*)` from which the query uses nothing
+pub fn unused_module(module_path: &str) -> String {
+    format!("Nothing imported from module `{}` is used.", module_path)
+}
+
+/// A warning about a result column whose type is unknown because the schema doesn't give one which
+/// Querydown recognizes
+pub fn untyped_column(table_name: &str, column_name: &str) -> String {
+    format!(
+        "The type of column `{}` within table `{}` is unknown because the schema doesn't give a \
+         recognized type for it.",
+        column_name, table_name
+    )
 }
 
-pub fn compare_two_ranges() -> String {
-    "Cannot compare two ranges.".to_string()
+/// A note suggesting names which may have been intended instead of one which doesn't exist
+pub fn did_you_mean(suggestions: &[String]) -> Option<String> {
+    let (last, rest) = suggestions.split_last()?;
+    let last = format!("`{}`", last);
+    if rest.is_empty() {
+        return Some(format!("Did you mean {}?", last));
+    }
+    let rest = rest.iter().map(|s| format!("`{}`", s)).join(", ");
+    Some(format!("Did you mean {} or {}?", rest, last))
 }
 
-pub fn compare_range_without_eq() -> String {
-    "Range comparison must use the `:` operator.".to_string()
+/// A note listing the tables which can be reached directly from a table
+pub fn related_tables(table_name: &str, related: &[String]) -> Option<String> {
+    if related.is_empty() {
+        return None;
+    }
+    let related = related.iter().map(|t| format!("`{}`", t)).join(", ");
+    Some(format!("Tables related to `{}`: {}.", table_name, related))
 }
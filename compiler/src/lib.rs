@@ -1,11 +1,23 @@
+mod column_origin;
 mod compiler;
+mod custom_functions;
 mod errors;
 mod options;
+mod output;
 mod schema;
 mod sql;
 mod tests;
 mod utils;
 
-pub use compiler::Compiler;
+pub use column_origin::{ColumnOrigin, ColumnReference, LinkStep, SourceColumn};
+pub use compiler::{CellUpdate, Compiler, ModuleResolver};
+pub use custom_functions::{Arity, CustomFunction, CustomFunctionKind, RenderFn};
+pub use errors::{Error, ErrorCode, Label, Span};
 pub use options::{IdentifierResolution, Options};
-pub use sql::Postgres;
+pub use output::{CompileOutput, QueryMetadata, ResultColumn};
+pub use schema::data_type::DataType;
+pub use schema::primitive_schema::{
+    PrimitiveColumn, PrimitiveLink, PrimitiveReference, PrimitiveSchema, PrimitiveTable,
+};
+pub use sql::expr::{SqlExpr, SqlExprPrecedence};
+pub use sql::{DatePart, DateUnit, Dialect, PadSide, Postgres, RegExFlags};
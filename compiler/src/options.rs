@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use crate::{sql::Dialect, utils::FlexMap};
+use crate::{sql::Dialect, utils::FlexMap, CustomFunction, ModuleResolver, Postgres};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum IdentifierResolution {
@@ -17,6 +17,27 @@ impl Default for IdentifierResolution {
 pub struct Options {
     pub dialect: Box<dyn Dialect>,
     pub identifier_resolution: IdentifierResolution,
+    /// Required in order to compile queries which import modules
+    pub module_resolver: Option<Box<dyn ModuleResolver>>,
+    /// Querydown source code containing definitions to make available within every query. The
+    /// prelude is parsed once, when the compiler is constructed. Definitions within a query (or
+    /// module) shadow definitions of the same name from the prelude.
+    pub prelude: Option<String>,
+    /// Scalar and aggregate functions supplied by the host application, in addition to the
+    /// built-in functions. Names may not conflict with built-in functions.
+    pub custom_functions: Vec<CustomFunction>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            dialect: Box::new(Postgres()),
+            identifier_resolution: IdentifierResolution::default(),
+            module_resolver: None,
+            prelude: None,
+            custom_functions: vec![],
+        }
+    }
 }
 
 impl Options {
@@ -0,0 +1,196 @@
+use serde::{Serialize, Serializer};
+
+use crate::{schema::data_type::DataType, sql::tree::Column, ColumnOrigin};
+
+/// Everything produced by compiling a query
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CompileOutput {
+    pub sql: String,
+    /// The columns within the results, in order
+    pub columns: Vec<ResultColumn>,
+    /// Values to bind to the parameters within `sql`, in order. These are the query's string
+    /// literals.
+    pub params: Vec<String>,
+    /// Problems which did not prevent the query from compiling, e.g. unused imports
+    pub warnings: Vec<String>,
+    pub metadata: QueryMetadata,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ResultColumn {
+    /// The name of the column within the results, when it can be determined without running the
+    /// query. This is the alias when one is given, and otherwise the name of the source column.
+    pub name: Option<String>,
+    pub alias: Option<String>,
+    /// The type of the values within the column, inferred from the schema's column types and the
+    /// functions applied to them. `None` (serialized as `"unknown"`) when it can't be inferred,
+    /// e.g. because the schema doesn't give types.
+    #[serde(rename = "type", serialize_with = "serialize_data_type")]
+    pub data_type: Option<DataType>,
+    pub origin: ColumnOrigin,
+}
+
+fn serialize_data_type<S: Serializer>(
+    data_type: &Option<DataType>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(data_type.map_or("unknown", |t| t.as_str()))
+}
+
+impl From<&Column> for ResultColumn {
+    fn from(column: &Column) -> Self {
+        let name = match (&column.alias, &column.origin) {
+            (Some(alias), _) => Some(alias.clone()),
+            (None, ColumnOrigin::TableColumn(source)) => Some(source.column.clone()),
+            (None, _) => None,
+        };
+        Self {
+            name,
+            alias: column.alias.clone(),
+            data_type: column.data_type,
+            origin: column.origin.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct QueryMetadata {
+    /// The name of the table from which each row of the results comes
+    pub base_table: String,
+    /// The name of the dialect in which `sql` is written
+    pub dialect: &'static str,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::{tests::get_test_resource, Compiler, Options};
+
+    #[test]
+    fn test_compile_output() {
+        let schema = get_test_resource("issue_schema.json");
+        let compiler = Compiler::new(&schema, Options::default()).unwrap();
+        let output = compiler
+            .compile_output("#issues $title $author.username -> author $(title|length)".to_owned())
+            .unwrap();
+        let names = output
+            .columns
+            .iter()
+            .map(|c| c.name.as_deref())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec![Some("title"), Some("author"), None]);
+        assert!(output.params.is_empty());
+        assert!(output.warnings.is_empty());
+        assert_eq!(output.metadata.base_table, "issues");
+        assert_eq!(output.metadata.dialect, "Postgres");
+        let json = serde_json::to_value(&output.columns[2]).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "name": null,
+                "alias": null,
+                "type": "integer",
+                "origin": { "kind": "computed" }
+            })
+        );
+    }
+
+    #[test]
+    fn test_result_column_types() {
+        use crate::DataType::*;
+        let compile = |schema_file: &str, input: &str| {
+            let schema = get_test_resource(schema_file);
+            let compiler = Compiler::new(&schema, Options::default()).unwrap();
+            compiler.compile_output(input.to_owned()).unwrap()
+        };
+        let types = |schema_file: &str, input: &str| {
+            let output = compile(schema_file, input);
+            output
+                .columns
+                .iter()
+                .map(|c| c.data_type)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            types(
+                "issue_schema.json",
+                "#issues $author.username $author $created_at|year $created_at - @1d $id / 2"
+            ),
+            vec![
+                Some(Text),
+                Some(Integer),
+                Some(Integer),
+                Some(Timestamp),
+                Some(Decimal)
+            ]
+        );
+        assert_eq!(
+            types(
+                "issue_schema.json",
+                "#issues $#comments.id%count $#comments.(body|length)%avg $#comments%last(id).body \
+                 $#comments.* $(created_at:>@2020-01-01)"
+            ),
+            vec![Some(Integer), Some(Decimal), Some(Text), Some(Json), Some(Boolean)]
+        );
+        assert_eq!(
+            types("issue_schema.json", "#teams $*"),
+            vec![Some(Integer), Some(Text)]
+        );
+        assert_eq!(
+            types("library_schema.json", "#books $title $#checkouts.id%count"),
+            vec![None, Some(Integer)]
+        );
+        let output = compile("library_schema.json", "#books $title");
+        let json = serde_json::to_value(&output.columns[0]).unwrap();
+        assert_eq!(json["type"], "unknown");
+    }
+
+    #[test]
+    fn test_compile_output_params() {
+        let schema = get_test_resource("issue_schema.json");
+        let compiler = Compiler::new(&schema, Options::default()).unwrap();
+        let input = r#"#issues {title:"a"} $#comments{body:"it's"}.id%count"#;
+        let output = compiler.compile_output(input.to_owned()).unwrap();
+        assert_eq!(output.params, vec!["a", "it's"]);
+        assert!(output.sql.contains(r#""issues"."title" = $1"#));
+        assert!(output.sql.contains(r#""comments"."body" = $2"#));
+        // The string form renders literals inline instead
+        let sql = compiler.compile(input.to_owned()).unwrap();
+        assert!(sql.contains(r#""issues"."title" = 'a'"#));
+    }
+
+    #[test]
+    fn test_compile_output_warnings() {
+        let modules: HashMap<String, String> =
+            [("m", "=== @a = 1 === @b = 2"), ("n", "=== @c = 3")]
+                .iter()
+                .map(|(path, source)| (path.to_string(), source.to_string()))
+                .collect();
+        let options = Options {
+            module_resolver: Some(Box::new(modules)),
+            ..Default::default()
+        };
+        let schema = get_test_resource("library_schema.json");
+        let compiler = Compiler::new(&schema, options).unwrap();
+        let output = compiler
+            .compile_output("<<< m (@a @b->d) <<< n (*) #Books $Title $@a".to_owned())
+            .unwrap();
+        assert_eq!(
+            output.warnings,
+            vec![
+                "`@d` is imported from module `m` but never used.",
+                "Nothing imported from module `n` is used.",
+                "The type of column `Title` within table `Books` is unknown because the schema \
+                 doesn't give a recognized type for it.",
+            ]
+        );
+        let output = compiler
+            .compile_output("<<< m (@a @b->d) <<< n (*) #Books $@d + @c".to_owned())
+            .unwrap();
+        assert_eq!(
+            output.warnings,
+            vec!["`@a` is imported from module `m` but never used."]
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use std::fmt::Display;
+
+use serde::Serialize;
+
+/// The type of the values within a column
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DataType {
+    Boolean,
+    Integer,
+    /// Floating point and arbitrary precision numbers
+    Decimal,
+    Text,
+    Date,
+    Time,
+    /// A date and time, with or without a time zone
+    Timestamp,
+    Interval,
+    Json,
+    Uuid,
+    Binary,
+}
+
+impl DataType {
+    /// Parse a type from a schema, which can be one of our own names (e.g. `timestamp`) or a common
+    /// SQL name (e.g. `VARCHAR(255)` or `timestamptz`). Case and parameters are ignored.
+    pub fn parse(name: &str) -> Option<Self> {
+        let name = name
+            .split('(')
+            .next()
+            .unwrap_or_default()
+            .trim()
+            .to_ascii_lowercase();
+        let data_type = match name.as_str() {
+            "boolean" | "bool" => Self::Boolean,
+            "integer" | "int" | "int2" | "int4" | "int8" | "smallint" | "bigint" | "tinyint"
+            | "mediumint" | "serial" | "smallserial" | "bigserial" => Self::Integer,
+            "decimal" | "numeric" | "real" | "float" | "float4" | "float8" | "double"
+            | "double precision" | "money" => Self::Decimal,
+            "text" | "varchar" | "char" | "character" | "character varying" | "string"
+            | "citext" | "clob" | "nvarchar" | "nchar" => Self::Text,
+            "date" => Self::Date,
+            "time" | "timetz" | "time with time zone" | "time without time zone" => Self::Time,
+            "timestamp"
+            | "timestamptz"
+            | "datetime"
+            | "timestamp with time zone"
+            | "timestamp without time zone" => Self::Timestamp,
+            "interval" => Self::Interval,
+            "json" | "jsonb" => Self::Json,
+            "uuid" => Self::Uuid,
+            "binary" | "blob" | "bytea" | "varbinary" => Self::Binary,
+            _ => return None,
+        };
+        Some(data_type)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Boolean => "boolean",
+            Self::Integer => "integer",
+            Self::Decimal => "decimal",
+            Self::Text => "text",
+            Self::Date => "date",
+            Self::Time => "time",
+            Self::Timestamp => "timestamp",
+            Self::Interval => "interval",
+            Self::Json => "json",
+            Self::Uuid => "uuid",
+            Self::Binary => "binary",
+        }
+    }
+}
+
+impl Display for DataType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_data_type() {
+        assert_eq!(DataType::parse("integer"), Some(DataType::Integer));
+        assert_eq!(DataType::parse("VARCHAR(255)"), Some(DataType::Text));
+        assert_eq!(DataType::parse("Double Precision"), Some(DataType::Decimal));
+        assert_eq!(DataType::parse("timestamptz"), Some(DataType::Timestamp));
+        assert_eq!(DataType::parse("int4[]"), None);
+        assert_eq!(DataType::parse("geometry"), None);
+    }
+}
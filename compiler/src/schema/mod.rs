@@ -1,6 +1,7 @@
 mod schema;
 
 pub mod chain;
+pub mod data_type;
 pub mod links;
 pub mod primitive_schema;
 pub use schema::*;
@@ -1,29 +1,41 @@
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize)]
+/// The schema in the JSON form accepted by `Compiler::new`
+#[derive(Debug, Deserialize, Serialize)]
 pub struct PrimitiveSchema {
     pub tables: Vec<PrimitiveTable>,
     pub links: Vec<PrimitiveLink>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct PrimitiveTable {
     pub name: String,
     pub columns: Vec<PrimitiveColumn>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct PrimitiveColumn {
     pub name: String,
+    /// The type of the column's values, e.g. `integer` or `VARCHAR(255)`. See `DataType::parse`
+    /// for the names which are recognized. Columns of other types (e.g. `int4[]` or `tsvector`) are
+    /// left untyped.
+    #[serde(default, rename = "type", skip_serializing_if = "Option::is_none")]
+    pub data_type: Option<String>,
+    /// Whether the column can contain `NULL`. When omitted, the column is assumed to be nullable
+    /// unless it's the primary key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nullable: Option<bool>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub primary_key: bool,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct PrimitiveReference {
     pub table: String,
     pub column: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct PrimitiveLink {
     pub from: PrimitiveReference,
     pub to: PrimitiveReference,
@@ -3,10 +3,13 @@ use std::collections::{
     HashMap,
 };
 
-use crate::errors::msg;
+use itertools::Itertools;
+
+use crate::errors::{msg, Error};
 
 use super::{
     chain::{Chain, ChainIntersecting},
+    data_type::DataType,
     links::{
         ForeignKey, ForwardLinkToOne, Link, LinkToOne, MultiLink, Reference, ReverseLinkToMany,
         ReverseLinkToOne,
@@ -147,22 +150,70 @@ impl Table {
             .chain(reverse_links_to_many)
             .chain(reverse_links_to_one)
     }
+
+    /// The column referenced by links from other tables, if there is exactly one. Linked columns
+    /// are unique, so its value identifies a row.
+    pub fn get_referenced_column(&self) -> Option<&Column> {
+        let reverse_links_to_many = self.reverse_links_to_many.values().flatten();
+        let reverse_links_to_one = self.reverse_links_to_one.values().flatten();
+        reverse_links_to_many
+            .map(|link| link.get_start().column_id)
+            .chain(reverse_links_to_one.map(|link| link.get_start().column_id))
+            .unique()
+            .exactly_one()
+            .ok()
+            .and_then(|column_id| self.columns.get(&column_id))
+    }
+
+    /// The primary key column, if the schema declares exactly one
+    pub fn get_primary_key(&self) -> Option<&Column> {
+        self.columns
+            .values()
+            .filter(|column| column.primary_key)
+            .exactly_one()
+            .ok()
+    }
+
+    /// A unique column which identifies each row: the primary key, or otherwise the column
+    /// referenced by links from other tables
+    pub fn get_key_column(&self) -> Option<&Column> {
+        self.get_primary_key()
+            .or_else(|| self.get_referenced_column())
+    }
 }
 
 #[derive(Debug)]
 pub struct Column {
     pub id: ColumnId,
     pub name: ColumnName,
+    /// `None` when the schema doesn't specify the type, or specifies one which we don't recognize
+    pub data_type: Option<DataType>,
+    pub nullable: bool,
+    pub primary_key: bool,
 }
 
-fn make_table(id: TableId, primitive_table: PrimitiveTable) -> Table {
+fn make_table(id: TableId, primitive_table: PrimitiveTable) -> Result<Table, Error> {
     let mut columns = HashMap::<ColumnId, Column>::new();
     let mut max_column_id: ColumnId = 0;
     for primitive_column in primitive_table.columns {
         max_column_id += 1;
+        let data_type = primitive_column
+            .data_type
+            .as_deref()
+            .and_then(DataType::parse);
+        let primary_key = primitive_column.primary_key;
+        if primary_key && primitive_column.nullable == Some(true) {
+            return Err(msg::schema_nullable_primary_key(
+                &primitive_table.name,
+                &primitive_column.name,
+            ));
+        }
         let column = Column {
             id: max_column_id,
             name: primitive_column.name,
+            data_type,
+            nullable: primitive_column.nullable.unwrap_or(!primary_key),
+            primary_key,
         };
         columns.insert(max_column_id, column);
     }
@@ -170,7 +221,7 @@ fn make_table(id: TableId, primitive_table: PrimitiveTable) -> Table {
         .iter()
         .map(|(id, column)| (column.name.clone(), *id))
         .collect();
-    Table {
+    Ok(Table {
         id,
         name: primitive_table.name,
         columns,
@@ -178,18 +229,18 @@ fn make_table(id: TableId, primitive_table: PrimitiveTable) -> Table {
         forward_links_to_one: HashMap::new(),
         reverse_links_to_one: HashMap::new(),
         reverse_links_to_many: HashMap::new(),
-    }
+    })
 }
 
 impl TryFrom<PrimitiveSchema> for Schema {
-    type Error = String;
+    type Error = Error;
 
-    fn try_from(primitive_schema: PrimitiveSchema) -> Result<Schema, String> {
+    fn try_from(primitive_schema: PrimitiveSchema) -> Result<Schema, Error> {
         let mut max_table_id: TableId = 0;
         let mut tables = HashMap::<TableId, Table>::new();
         for primitive_table in primitive_schema.tables {
             max_table_id += 1;
-            let table = make_table(max_table_id, primitive_table);
+            let table = make_table(max_table_id, primitive_table)?;
             tables.insert(max_table_id, table);
         }
 
@@ -199,23 +250,22 @@ impl TryFrom<PrimitiveSchema> for Schema {
             .collect();
 
         let foreign_keys: Vec<ForeignKey> = {
-            let get_table_by_name = |name: &String| -> Result<&Table, String> {
+            let get_table_by_name = |name: &String| -> Result<&Table, Error> {
                 let table_id = table_lookup
                     .get(name)
-                    .ok_or_else(|| format!("Unknown table: {}", name))?;
+                    .ok_or_else(|| msg::schema_unknown_table(name))?;
                 let table = tables
                     .get(table_id)
-                    .ok_or_else(|| format!("Table not found by id: {}", table_id))?;
+                    .ok_or_else(|| msg::internal("Table not found by id."))?;
                 Ok(table)
             };
-            let get_column_id_by_name =
-                |table: &Table, name: &String| -> Result<ColumnId, String> {
-                    let column_id = table
-                        .column_lookup
-                        .get(name)
-                        .ok_or_else(|| format!("Unknown column: {}", name))?;
-                    Ok(*column_id)
-                };
+            let get_column_id_by_name = |table: &Table, name: &String| -> Result<ColumnId, Error> {
+                let column_id = table
+                    .column_lookup
+                    .get(name)
+                    .ok_or_else(|| msg::schema_unknown_column(name))?;
+                Ok(*column_id)
+            };
             let mut foreign_keys: Vec<ForeignKey> = vec![];
             for primitive_link in primitive_schema.links {
                 let base_table = get_table_by_name(&primitive_link.from.table)?;
@@ -289,4 +339,59 @@ mod tests {
         let schema = Schema::try_from(primitive_schema);
         assert!(schema.is_ok())
     }
+
+    #[test]
+    fn test_column_types() {
+        let schema =
+            |json: &str| Schema::try_from(serde_json::from_str::<PrimitiveSchema>(json).unwrap());
+        let schema = schema(
+            r#"{"tables": [{"name": "t", "columns": [
+                {"name": "id", "type": "bigint", "primary_key": true},
+                {"name": "a", "type": "VARCHAR(20)", "nullable": false},
+                {"name": "b"}
+            ]}], "links": []}"#,
+        )
+        .unwrap();
+        let table = schema.tables.values().next().unwrap();
+        let column = |name: &str| table.columns.get(&table.column_lookup[name]).unwrap();
+        assert_eq!(column("id").data_type, Some(DataType::Integer));
+        assert!(!column("id").nullable);
+        assert_eq!(column("a").data_type, Some(DataType::Text));
+        assert!(!column("a").nullable);
+        assert_eq!(column("b").data_type, None);
+        assert!(column("b").nullable);
+        assert_eq!(table.get_primary_key().map(|c| c.name.as_str()), Some("id"));
+    }
+
+    #[test]
+    fn test_unrecognized_column_types() {
+        let schema = Schema::try_from(
+            serde_json::from_str::<PrimitiveSchema>(
+                r#"{"tables": [{"name": "t", "columns": [
+                    {"name": "a", "type": "geometry"},
+                    {"name": "b", "type": "int4[]"},
+                    {"name": "c", "type": "tsvector"}
+                ]}], "links": []}"#,
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let table = schema.tables.values().next().unwrap();
+        assert!(table.columns.values().all(|c| c.data_type.is_none()));
+    }
+
+    #[test]
+    fn test_invalid_column_types() {
+        let schema = |columns: &str| {
+            let json = format!(
+                r#"{{"tables": [{{"name": "t", "columns": [{}]}}], "links": []}}"#,
+                columns
+            );
+            Schema::try_from(serde_json::from_str::<PrimitiveSchema>(&json).unwrap()).map(|_| ())
+        };
+        assert_eq!(
+            schema(r#"{"name": "id", "primary_key": true, "nullable": true}"#),
+            Err(msg::schema_nullable_primary_key("t", "id"))
+        );
+    }
 }
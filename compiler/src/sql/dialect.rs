@@ -1,18 +1,60 @@
 use querydown_parser::ast::{Date, Duration};
 
+use crate::errors::{msg, Error};
+
 use super::expr::SqlExpr;
 
 pub struct RegExFlags {
     pub is_case_sensitive: bool,
 }
 
+/// A numeric component of a date or timestamp
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DatePart {
+    Year,
+    /// 1 through 4
+    Quarter,
+    /// 1 through 12
+    Month,
+    /// ISO 8601 week number, 1 through 53
+    Week,
+    /// ISO 8601 day of the week, from 1 (Monday) through 7 (Sunday)
+    DayOfWeek,
+    /// 0 through 23
+    Hour,
+}
+
+/// A unit to which a date or timestamp can be truncated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateUnit {
+    Day,
+    /// Weeks begin on Monday
+    Week,
+    Month,
+    Quarter,
+    Year,
+}
+
+/// The side of a string on which padding is added
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PadSide {
+    Start,
+    End,
+}
+
 pub trait Dialect {
+    /// A human-readable name for the dialect, used in error messages
+    fn name(&self) -> &'static str;
+
     /// Quote a table or column for use in SQL.
     fn quote_identifier(&self, ident: &str) -> String;
 
     /// Quote a string for use in SQL.
     fn quote_string(&self, string: &str) -> String;
 
+    /// Render a placeholder for the bind parameter at the given position, counting from 1
+    fn parameter(&self, position: usize) -> String;
+
     /// Render a date literal
     fn date(&self, date: &Date) -> String;
 
@@ -35,4 +77,136 @@ pub trait Dialect {
     /// * `flags` - Flags to control the behavior of the regular expression
     fn match_regex(&self, a: SqlExpr, b: SqlExpr, is_positive: bool, flags: &RegExFlags)
         -> SqlExpr;
+
+    /// Render an expression which extracts a numeric component from a date or timestamp
+    fn date_part(&self, part: DatePart, value: SqlExpr) -> SqlExpr;
+
+    /// Render an expression which truncates a date or timestamp to the beginning of the given unit
+    fn date_trunc(&self, unit: DateUnit, value: SqlExpr) -> SqlExpr;
+
+    /// Render an expression which formats a date or timestamp as a `YYYY-MM` string
+    fn year_month(&self, value: SqlExpr) -> SqlExpr;
+
+    /// Render the concatenation of strings, treating NULL values as empty strings
+    fn concat(&self, values: Vec<SqlExpr>) -> SqlExpr;
+
+    /// Render part of a string, beginning at the 1-based `start` position. Without a `length`,
+    /// the rest of the string is included.
+    fn substring(&self, value: SqlExpr, start: SqlExpr, length: Option<SqlExpr>) -> SqlExpr;
+
+    /// Render the 1-based `index`th part of a string which is split on `delimiter`
+    fn split_part(&self, value: SqlExpr, delimiter: SqlExpr, index: SqlExpr) -> SqlExpr;
+
+    /// Render a boolean expression which is true when `value` begins with `prefix`
+    fn starts_with(&self, value: SqlExpr, prefix: SqlExpr) -> SqlExpr;
+
+    /// Render a string padded to `length` characters using `fill` (a space, when omitted)
+    fn pad(&self, value: SqlExpr, length: SqlExpr, fill: Option<SqlExpr>, side: PadSide)
+        -> SqlExpr;
+
+    /// Render an aggregate expression which joins the values into a string. When `order_by` is
+    /// given, the values are joined in ascending order of that expression.
+    fn list(
+        &self,
+        value: SqlExpr,
+        separator: SqlExpr,
+        distinct: bool,
+        order_by: Option<SqlExpr>,
+    ) -> SqlExpr;
+
+    /// Render an aggregate expression which collects the values into a JSON array
+    fn json_agg(&self, value: SqlExpr, order_by: Option<SqlExpr>) -> SqlExpr;
+
+    /// Render a JSON object with the given keys and values
+    fn json_object(&self, entries: Vec<(String, SqlExpr)>) -> SqlExpr;
+
+    /// Render a list of the values visited by a recursive path, for cycle protection. This returns
+    /// an error by default, for dialects which lack native arrays.
+    fn visited_list(&self, values: Vec<SqlExpr>) -> Result<SqlExpr, Error> {
+        let _ = values;
+        Err(msg::unsupported_by_dialect("Recursive paths", self.name()))
+    }
+
+    /// Render `list` (as rendered by `visited_list`) with `value` appended
+    fn visited_list_append(&self, list: SqlExpr, value: SqlExpr) -> Result<SqlExpr, Error> {
+        let _ = (list, value);
+        Err(msg::unsupported_by_dialect("Recursive paths", self.name()))
+    }
+
+    /// Render a condition which is true when `list` (as rendered by `visited_list`) contains
+    /// `value`
+    fn visited_list_contains(&self, list: SqlExpr, value: SqlExpr) -> Result<SqlExpr, Error> {
+        let _ = (list, value);
+        Err(msg::unsupported_by_dialect("Recursive paths", self.name()))
+    }
+
+    /// Render the keywords which begin a select of only the first row for each distinct
+    /// combination of `exprs`, as ordered by the select's sorting. This returns an error by
+    /// default, for dialects which lack `DISTINCT ON`.
+    fn select_distinct_on(&self, exprs: Vec<SqlExpr>) -> Result<String, Error> {
+        let _ = exprs;
+        Err(msg::unsupported_by_dialect(
+            "Selecting a single related record (via `%first`, `%last` or `*`)",
+            self.name(),
+        ))
+    }
+
+    /// Render an aggregate expression which collects the values into a native array. This returns
+    /// an error by default, for dialects which lack native arrays.
+    fn array_agg(&self, value: SqlExpr, order_by: Option<SqlExpr>) -> Result<SqlExpr, Error> {
+        let _ = (value, order_by);
+        Err(msg::unsupported_by_dialect(
+            "The `array` function",
+            self.name(),
+        ))
+    }
+
+    // The statistical aggregates below return an error by default, for dialects which lack native
+    // support for them.
+
+    /// Render an aggregate expression for the median of the values
+    fn median(&self, value: SqlExpr) -> Result<SqlExpr, Error> {
+        let _ = value;
+        Err(msg::unsupported_by_dialect(
+            "The `median` function",
+            self.name(),
+        ))
+    }
+
+    /// Render an aggregate expression for the continuous percentile of the values, where
+    /// `fraction` is between 0 and 1
+    fn percentile(&self, value: SqlExpr, fraction: SqlExpr) -> Result<SqlExpr, Error> {
+        let _ = (value, fraction);
+        Err(msg::unsupported_by_dialect(
+            "The `percentile` function",
+            self.name(),
+        ))
+    }
+
+    /// Render an aggregate expression for the sample standard deviation of the values
+    fn stddev(&self, value: SqlExpr) -> Result<SqlExpr, Error> {
+        let _ = value;
+        Err(msg::unsupported_by_dialect(
+            "The `stddev` function",
+            self.name(),
+        ))
+    }
+
+    /// Render an aggregate expression for the sample variance of the values
+    fn variance(&self, value: SqlExpr) -> Result<SqlExpr, Error> {
+        let _ = value;
+        Err(msg::unsupported_by_dialect(
+            "The `variance` function",
+            self.name(),
+        ))
+    }
+
+    /// Render an aggregate expression for the most frequent value
+    fn mode(&self, value: SqlExpr) -> Result<SqlExpr, Error> {
+        let _ = value;
+        Err(msg::unsupported_by_dialect(
+            "The `mode` function",
+            self.name(),
+        ))
+    }
 }
@@ -4,13 +4,12 @@ use querydown_parser::ast::Conjunction;
 use super::{SqlExpr, SqlExprPrecedence};
 
 fn binary_op(a: SqlExpr, op: &str, b: SqlExpr, precedence: SqlExprPrecedence) -> SqlExpr {
+    let operand = |e: SqlExpr| match precedence {
+        SqlExprPrecedence::Comparison => e.for_non_associative_precedence(precedence),
+        _ => e.for_precedence(precedence),
+    };
     SqlExpr {
-        content: format!(
-            "{} {} {}",
-            a.for_precedence(precedence),
-            op,
-            b.for_precedence(precedence)
-        ),
+        content: format!("{} {} {}", operand(a), op, operand(b)),
         precedence,
     }
 }
@@ -57,14 +56,6 @@ pub mod agg {
         sql_func("min", [a])
     }
 
-    pub fn string_agg(a: SqlExpr) -> SqlExpr {
-        // TODO:
-        // - Let user customize the separator
-        // - Use dialect-specific string quoting logic
-        let separator = SqlExpr::atom("', '".to_string());
-        sql_func("string_agg", [a, separator])
-    }
-
     pub fn sum(a: SqlExpr) -> SqlExpr {
         sql_func("sum", [a])
     }
@@ -150,6 +141,15 @@ pub mod cmp {
             precedence: SqlExprPrecedence::Comparison,
         }
     }
+
+    /// Unlike `NOT`, this is true when the condition is `NULL`
+    pub fn is_not_true(a: SqlExpr) -> SqlExpr {
+        let a = a.for_non_associative_precedence(SqlExprPrecedence::Comparison);
+        SqlExpr {
+            content: format!("{} IS NOT TRUE", a.content),
+            precedence: SqlExprPrecedence::Comparison,
+        }
+    }
 }
 
 pub mod cond {
@@ -257,6 +257,14 @@ pub mod strings {
     pub fn char_length(a: SqlExpr) -> SqlExpr {
         sql_func("char_length", [a])
     }
+
+    pub fn trim(a: SqlExpr) -> SqlExpr {
+        sql_func("trim", [a])
+    }
+
+    pub fn replace(a: SqlExpr, from: SqlExpr, to: SqlExpr) -> SqlExpr {
+        sql_func("replace", [a, from, to])
+    }
 }
 
 pub mod value {
@@ -281,4 +289,23 @@ pub mod value {
     pub fn zero() -> SqlExpr {
         SqlExpr::atom("0".to_string())
     }
+
+    pub fn one() -> SqlExpr {
+        SqlExpr::atom("1".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nested_comparisons() {
+        let a = || SqlExpr::atom("a".to_string());
+        let b = || SqlExpr::atom("b".to_string());
+        let comparison = cmp::eq(cmp::eq(a(), b()), SqlExpr::atom("TRUE".to_string()));
+        assert_eq!(comparison.content, "(a = b) = TRUE");
+        let difference = math::subtract(math::subtract(a(), b()), a());
+        assert_eq!(difference.content, "a - b - a");
+    }
 }
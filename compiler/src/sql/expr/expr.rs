@@ -39,6 +39,15 @@ impl SqlExpr {
         }
         self
     }
+
+    /// Like `for_precedence`, but also parenthesizes expressions of equal precedence, as needed
+    /// for the operands of non-associative operators such as comparisons.
+    pub fn for_non_associative_precedence(mut self, precedence: SqlExprPrecedence) -> SqlExpr {
+        if precedence >= self.precedence {
+            self.parenthesize();
+        }
+        self
+    }
 }
 
 impl Display for SqlExpr {
@@ -1,9 +1,13 @@
 use std::fmt::Display;
 
+use itertools::Itertools;
+
 use querydown_parser::ast::{Date, Duration};
 
+use crate::errors::Error;
+
 use super::{
-    dialect::{Dialect, RegExFlags},
+    dialect::{DatePart, DateUnit, Dialect, PadSide, RegExFlags},
     expr::{build::cmp::comparison, SqlExpr},
 };
 
@@ -42,6 +46,10 @@ pub struct Postgres();
 // stored in the AST are not unintentionally processed as escape sequences by Postgres. See
 // https://www.postgresql.org/docs/current/sql-syntax-lexical.html for continued research.
 impl Dialect for Postgres {
+    fn name(&self) -> &'static str {
+        "Postgres"
+    }
+
     fn quote_identifier(&self, ident: &str) -> String {
         format!(r#""{}""#, ident.replace(r"\", r"\\").replace('"', r#"\""#))
     }
@@ -50,6 +58,10 @@ impl Dialect for Postgres {
         format!("'{}'", string.replace(r"\", r"\\").replace("'", r"\'"))
     }
 
+    fn parameter(&self, position: usize) -> String {
+        format!("${}", position)
+    }
+
     fn date(&self, date: &Date) -> String {
         format!("DATE '{}'", date.to_iso())
     }
@@ -112,4 +124,164 @@ impl Dialect for Postgres {
         };
         comparison(a, op, b)
     }
+
+    fn date_part(&self, part: DatePart, value: SqlExpr) -> SqlExpr {
+        let field = match part {
+            DatePart::Year => "YEAR",
+            DatePart::Quarter => "QUARTER",
+            DatePart::Month => "MONTH",
+            DatePart::Week => "WEEK",
+            DatePart::DayOfWeek => "ISODOW",
+            DatePart::Hour => "HOUR",
+        };
+        SqlExpr::atom(format!("EXTRACT({} FROM {})", field, value))
+    }
+
+    fn date_trunc(&self, unit: DateUnit, value: SqlExpr) -> SqlExpr {
+        let unit = match unit {
+            DateUnit::Day => "day",
+            DateUnit::Week => "week",
+            DateUnit::Month => "month",
+            DateUnit::Quarter => "quarter",
+            DateUnit::Year => "year",
+        };
+        SqlExpr::atom(format!("date_trunc('{}', {})", unit, value))
+    }
+
+    fn year_month(&self, value: SqlExpr) -> SqlExpr {
+        SqlExpr::atom(format!("to_char({}, 'YYYY-MM')", value))
+    }
+
+    fn concat(&self, values: Vec<SqlExpr>) -> SqlExpr {
+        SqlExpr::atom(format!("concat({})", values.iter().join(", ")))
+    }
+
+    fn substring(&self, value: SqlExpr, start: SqlExpr, length: Option<SqlExpr>) -> SqlExpr {
+        match length {
+            Some(length) => SqlExpr::atom(format!(
+                "substring({} FROM {} FOR {})",
+                value, start, length
+            )),
+            None => SqlExpr::atom(format!("substring({} FROM {})", value, start)),
+        }
+    }
+
+    fn split_part(&self, value: SqlExpr, delimiter: SqlExpr, index: SqlExpr) -> SqlExpr {
+        SqlExpr::atom(format!("split_part({}, {}, {})", value, delimiter, index))
+    }
+
+    fn starts_with(&self, value: SqlExpr, prefix: SqlExpr) -> SqlExpr {
+        SqlExpr::atom(format!("starts_with({}, {})", value, prefix))
+    }
+
+    fn pad(
+        &self,
+        value: SqlExpr,
+        length: SqlExpr,
+        fill: Option<SqlExpr>,
+        side: PadSide,
+    ) -> SqlExpr {
+        let name = match side {
+            PadSide::Start => "lpad",
+            PadSide::End => "rpad",
+        };
+        let args = [Some(value), Some(length), fill]
+            .into_iter()
+            .flatten()
+            .join(", ");
+        SqlExpr::atom(format!("{}({})", name, args))
+    }
+
+    fn list(
+        &self,
+        value: SqlExpr,
+        separator: SqlExpr,
+        distinct: bool,
+        order_by: Option<SqlExpr>,
+    ) -> SqlExpr {
+        let distinct = if distinct { "DISTINCT " } else { "" };
+        SqlExpr::atom(format!(
+            "string_agg({}{}, {}{})",
+            distinct,
+            value,
+            separator,
+            order_by_clause(order_by)
+        ))
+    }
+
+    fn json_agg(&self, value: SqlExpr, order_by: Option<SqlExpr>) -> SqlExpr {
+        SqlExpr::atom(format!("json_agg({}{})", value, order_by_clause(order_by)))
+    }
+
+    fn json_object(&self, entries: Vec<(String, SqlExpr)>) -> SqlExpr {
+        let args = entries
+            .into_iter()
+            .map(|(key, value)| format!("{}, {}", self.quote_string(&key), value))
+            .join(", ");
+        SqlExpr::atom(format!("json_build_object({})", args))
+    }
+
+    fn visited_list(&self, values: Vec<SqlExpr>) -> Result<SqlExpr, Error> {
+        Ok(SqlExpr::atom(format!(
+            "ARRAY[{}]",
+            values.iter().join(", ")
+        )))
+    }
+
+    fn visited_list_append(&self, list: SqlExpr, value: SqlExpr) -> Result<SqlExpr, Error> {
+        Ok(SqlExpr::atom(format!("array_append({}, {})", list, value)))
+    }
+
+    fn visited_list_contains(&self, list: SqlExpr, value: SqlExpr) -> Result<SqlExpr, Error> {
+        Ok(comparison(
+            value,
+            "=",
+            SqlExpr::atom(format!("ANY({})", list)),
+        ))
+    }
+
+    fn select_distinct_on(&self, exprs: Vec<SqlExpr>) -> Result<String, Error> {
+        Ok(format!("SELECT DISTINCT ON ({})", exprs.iter().join(", ")))
+    }
+
+    fn array_agg(&self, value: SqlExpr, order_by: Option<SqlExpr>) -> Result<SqlExpr, Error> {
+        Ok(SqlExpr::atom(format!(
+            "array_agg({}{})",
+            value,
+            order_by_clause(order_by)
+        )))
+    }
+
+    fn median(&self, value: SqlExpr) -> Result<SqlExpr, Error> {
+        self.percentile(value, SqlExpr::atom("0.5".to_string()))
+    }
+
+    fn percentile(&self, value: SqlExpr, fraction: SqlExpr) -> Result<SqlExpr, Error> {
+        Ok(SqlExpr::atom(format!(
+            "percentile_cont({}) WITHIN GROUP (ORDER BY {})",
+            fraction, value
+        )))
+    }
+
+    fn stddev(&self, value: SqlExpr) -> Result<SqlExpr, Error> {
+        Ok(SqlExpr::atom(format!("stddev_samp({})", value)))
+    }
+
+    fn variance(&self, value: SqlExpr) -> Result<SqlExpr, Error> {
+        Ok(SqlExpr::atom(format!("var_samp({})", value)))
+    }
+
+    fn mode(&self, value: SqlExpr) -> Result<SqlExpr, Error> {
+        Ok(SqlExpr::atom(format!(
+            "mode() WITHIN GROUP (ORDER BY {})",
+            value
+        )))
+    }
+}
+
+/// The `ORDER BY` clause within an aggregate function call, e.g. `string_agg(a, ', ' ORDER BY b)`
+fn order_by_clause(order_by: Option<SqlExpr>) -> String {
+    order_by
+        .map(|expr| format!(" ORDER BY {}", expr))
+        .unwrap_or_default()
 }
@@ -1,27 +1,53 @@
 use querydown_parser::ast::{NullsSort, SortDirection};
 
+use crate::{schema::data_type::DataType, ColumnOrigin};
+
 pub use super::expr::{SqlExpr, SqlExprPrecedence};
 
 #[derive(Debug)]
 pub struct Select {
     pub base_table: String,
+    /// Replaces the `SELECT` keyword in order to select only the first row of each distinct
+    /// combination of some expressions, as ordered by `sorting` (see `Dialect::select_distinct_on`)
+    pub distinct_on: Option<String>,
     pub columns: Vec<Column>,
     pub ctes: Vec<Cte>,
     pub joins: Vec<Join>,
     pub conditions: SqlExpr,
     pub sorting: Vec<SortEntry>,
     pub grouping: Vec<SqlExpr>,
+    /// Selects whose rows are appended via `UNION ALL`
+    pub union_all: Vec<Select>,
 }
 
 #[derive(Debug)]
 pub struct Column {
     pub expr: SqlExpr,
     pub alias: Option<String>,
+    /// Only meaningful for the result columns of the outermost select
+    pub origin: ColumnOrigin,
+    /// The type of the values, when it can be inferred. Like `origin`, this is only meaningful for
+    /// the result columns of the outermost select.
+    pub data_type: Option<DataType>,
 }
 
 impl Column {
     pub fn new(expr: SqlExpr, alias: Option<String>) -> Self {
-        Self { expr, alias }
+        Self::with_origin(expr, alias, ColumnOrigin::Computed)
+    }
+
+    pub fn with_origin(expr: SqlExpr, alias: Option<String>, origin: ColumnOrigin) -> Self {
+        Self {
+            expr,
+            alias,
+            origin,
+            data_type: None,
+        }
+    }
+
+    pub fn with_data_type(mut self, data_type: Option<DataType>) -> Self {
+        self.data_type = data_type;
+        self
     }
 }
 
@@ -45,9 +71,17 @@ pub enum CtePurpose {
     /// joined via a left outer join, and a WHERE clause will be added to filter out rows that
     /// have a related record.
     Exclusion,
+    /// A CTE that is used to filter the base table on the absence of related records which fail
+    /// to satisfy the conditions on the final table in the chain (i.e. all related records satisfy
+    /// them). The final conditions are negated within the CTE, and it's joined like `Exclusion`.
+    Counterexample,
     /// A CTE that is used to supply a value used by the query. Will be joined via a left outer
     /// join.
     AggregateValue,
+    /// A recursive CTE which pairs each record with the records reachable from it by repeatedly
+    /// following a self-referential link. It's used as the base table of another CTE rather than
+    /// being joined.
+    Closure,
 }
 
 #[derive(Debug)]
@@ -75,12 +109,14 @@ impl From<String> for Select {
     fn from(base_table: String) -> Self {
         Self {
             base_table,
+            distinct_on: None,
             columns: vec![],
             ctes: vec![],
             joins: vec![],
             conditions: SqlExpr::default(),
             sorting: vec![],
             grouping: vec![],
+            union_all: vec![],
         }
     }
 }
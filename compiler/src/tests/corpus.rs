@@ -10,6 +10,7 @@ fn test_corpus() {
 
     use super::get_test_resource;
 
+    use std::collections::{BTreeMap, HashMap};
     use std::path::PathBuf;
     use testcase_markdown::*;
     use toml::{from_str, map::Map, Table, Value};
@@ -19,6 +20,9 @@ fn test_corpus() {
         schema_json: String,
         identifier_resolution: IdentifierResolution,
         dialect: String,
+        /// Source code of importable modules, keyed by module path
+        modules: BTreeMap<String, String>,
+        prelude: Option<String>,
     }
 
     impl Default for Opts {
@@ -27,6 +31,8 @@ fn test_corpus() {
                 schema_json: get_test_resource("issue_schema.json"),
                 identifier_resolution: IdentifierResolution::Flexible,
                 dialect: "postgres".to_owned(),
+                modules: BTreeMap::new(),
+                prelude: None,
             }
         }
     }
@@ -56,6 +62,20 @@ fn test_corpus() {
         toml_values.get("dialect").map(|v| v.as_str())?
     }
 
+    fn get_modules(toml_values: &Map<String, Value>) -> BTreeMap<String, String> {
+        let Some(Value::Table(modules)) = toml_values.get("modules") else {
+            return BTreeMap::new();
+        };
+        modules
+            .iter()
+            .filter_map(|(path, source)| Some((path.to_owned(), source.as_str()?.to_owned())))
+            .collect()
+    }
+
+    fn get_prelude(toml_values: &Map<String, Value>) -> Option<String> {
+        toml_values.get("prelude")?.as_str().map(|s| s.to_owned())
+    }
+
     impl MergeSerialized for Opts {
         fn merge_serialized(&self, source: String) -> Result<Self, String> {
             let values = from_str::<Table>(&source).map_err(|e| e.to_string())?;
@@ -67,6 +87,13 @@ fn test_corpus() {
                 dialect: get_dialect(&values)
                     .map(|d| d.to_owned())
                     .unwrap_or_else(|| self.dialect.clone()),
+                modules: self
+                    .modules
+                    .clone()
+                    .into_iter()
+                    .chain(get_modules(&values))
+                    .collect(),
+                prelude: get_prelude(&values).or_else(|| self.prelude.clone()),
             })
         }
     }
@@ -112,6 +139,15 @@ fn test_corpus() {
                 "postgres" => Box::new(Postgres()),
                 _ => panic!("Unknown dialect"),
             },
+            module_resolver: Some(Box::new(
+                case.options
+                    .modules
+                    .clone()
+                    .into_iter()
+                    .collect::<HashMap<_, _>>(),
+            )),
+            prelude: case.options.prelude.clone(),
+            custom_functions: vec![],
         };
         // println!("{:}", case.options.schema_json);
         let compiler = Compiler::new(&case.options.schema_json, options).unwrap();
@@ -26,6 +26,13 @@ fn eq_by<T>(
     }
 }
 
+/// The form in which identifiers are compared when they're resolved flexibly
+pub fn flex_normalize(s: &str) -> String {
+    ascii_alphanumeric(s)
+        .map(|c| c.to_ascii_lowercase() as char)
+        .collect()
+}
+
 fn flex_eq(str_a: &str, str_b: &str) -> bool {
     eq_by(
         ascii_alphanumeric(str_a),
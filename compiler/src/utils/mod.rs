@@ -1,3 +1,5 @@
 mod flex_map;
+mod suggestions;
 
 pub use flex_map::*;
+pub use suggestions::*;
@@ -0,0 +1,82 @@
+use itertools::Itertools;
+
+use super::flex_normalize;
+
+const MAX_SUGGESTIONS: usize = 3;
+
+/// The candidates which are most similar to `search`, most similar first, for suggesting
+/// alternatives to a name which doesn't exist.
+///
+/// Names are compared in their flexibly-resolved form, so differences in case and punctuation
+/// (including sigils like `#`) don't count against a candidate.
+pub fn rank_suggestions(search: &str, candidates: impl IntoIterator<Item = String>) -> Vec<String> {
+    let search = flex_normalize(search);
+    // Allow roughly one typo for every three characters
+    let max_distance = (search.len() / 3).max(1);
+    candidates
+        .into_iter()
+        .unique()
+        .map(|candidate| {
+            (
+                edit_distance(&search, &flex_normalize(&candidate)),
+                candidate,
+            )
+        })
+        .filter(|(distance, _)| *distance <= max_distance)
+        .sorted()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate)
+        .collect()
+}
+
+/// The number of single-character insertions, deletions, substitutions and transpositions needed
+/// to turn one ASCII string into the other (i.e. the optimal string alignment distance)
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("", ""), 0);
+        assert_eq!(edit_distance("abc", ""), 3);
+        assert_eq!(edit_distance("title", "title"), 0);
+        assert_eq!(edit_distance("titel", "title"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_rank_suggestions() {
+        let candidates = ["title", "titles", "status", "#comments", "created_at"]
+            .map(String::from)
+            .to_vec();
+        let rank = |search: &str| rank_suggestions(search, candidates.clone());
+        assert_eq!(rank("tilte"), vec!["title"]);
+        assert_eq!(rank("titless"), vec!["titles", "title"]);
+        assert_eq!(rank("Created At"), vec!["created_at"]);
+        assert_eq!(rank("comment"), vec!["#comments"]);
+        assert_eq!(rank("nope"), Vec::<String>::new());
+    }
+}
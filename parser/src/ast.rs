@@ -1,11 +1,121 @@
-use crate::tokens::LITERAL_NULL;
+use std::fmt::{Display, Formatter};
+
+use crate::tokens::{CONST_SIGIL, FUNCTION_SIGIL, LITERAL_NULL, PATH_SEPARATOR, TABLE_SIGIL};
+
+pub use crate::span::Span;
 
 #[derive(Debug, PartialEq)]
 pub struct Query {
+    /// Imports and definitions which precede the base table
+    pub statements: Vec<Statement>,
     pub base_table: String,
+    pub base_table_span: Span,
     pub transformations: Vec<Transformation>,
 }
 
+/// A file of Querydown code which contains only imports and definitions, for use by other
+/// modules or queries.
+#[derive(Debug, PartialEq, Default)]
+pub struct Module {
+    pub statements: Vec<Statement>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Statement {
+    Import(Import),
+    Definition(Definition),
+}
+
+impl Statement {
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Import(import) => import.span,
+            Self::Definition(definition) => definition.span,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Import {
+    /// The path of the module, exactly as written. Interpreting it is up to the host application.
+    pub module: String,
+    pub items: ImportItems,
+    /// True when the imported items are re-exported from the importing module
+    pub is_exported: bool,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImportItems {
+    All,
+    Named(Vec<ImportItem>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportItem {
+    pub name: DefinitionName,
+    pub alias: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Definition {
+    pub name: DefinitionName,
+    /// Parameter names (without the `@` sigil). Only functions have parameters.
+    pub params: Vec<String>,
+    pub body: Expr,
+    pub is_exported: bool,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DefinitionName {
+    /// `@foo`
+    Constant(String),
+    /// `@@foo`
+    Function(String),
+    /// `#table.foo`
+    Column { table: String, column: String },
+    /// `#table.@@foo`
+    TableFunction { table: String, function: String },
+}
+
+impl DefinitionName {
+    /// Returns a new name which is the same as this one except that the final identifier is
+    /// replaced with `alias`.
+    pub fn with_alias(&self, alias: String) -> Self {
+        match self {
+            Self::Constant(_) => Self::Constant(alias),
+            Self::Function(_) => Self::Function(alias),
+            Self::Column { table, .. } => Self::Column {
+                table: table.clone(),
+                column: alias,
+            },
+            Self::TableFunction { table, .. } => Self::TableFunction {
+                table: table.clone(),
+                function: alias,
+            },
+        }
+    }
+}
+
+impl Display for DefinitionName {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Constant(name) => write!(f, "{CONST_SIGIL}{name}"),
+            Self::Function(name) => write!(f, "{FUNCTION_SIGIL}{name}"),
+            Self::Column { table, column } => {
+                write!(f, "{TABLE_SIGIL}{table}{PATH_SEPARATOR}{column}")
+            }
+            Self::TableFunction { table, function } => {
+                write!(
+                    f,
+                    "{TABLE_SIGIL}{table}{PATH_SEPARATOR}{FUNCTION_SIGIL}{function}"
+                )
+            }
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Default)]
 pub struct Transformation {
     pub conditions: ConditionSet,
@@ -22,6 +132,9 @@ pub enum Expr {
     Path(Vec<PathPart>),
     ConditionSet(ConditionSet),
     HasQuantity(HasQuantity),
+    /// An expression evaluated relative to the record which the surrounding related records belong
+    /// to, e.g. `&due_date` within `#issues ++#comments{created_at:>&due_date}`
+    Outer(Box<Expr>),
     Call(Call),
     Product(Box<Expr>, Box<Expr>),
     Quotient(Box<Expr>, Box<Expr>),
@@ -118,9 +231,34 @@ impl Duration {
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum PathPart {
-    Column(String),
+    Column(PathColumn),
     TableWithOne(String),
     TableWithMany(TableWithMany),
+    /// Picks one record from the preceding table with many, e.g. `%last(created_at)` within
+    /// `#comments%last(created_at).author`. The path continues as if it were a path to one.
+    Pick(Pick),
+    /// Follows the preceding self-referential link repeatedly, e.g. the `*` in
+    /// `duplicate_of*.title` or `#issues(duplicate_of)*.title%list`
+    Transitive,
+    /// A parenthesized expression at the end of a path, e.g. `#comments.(body|length)`. The
+    /// expression is evaluated relative to the table at the end of the preceding parts.
+    Expr(Box<Expr>),
+}
+
+impl PathPart {
+    /// A column which doesn't appear within the source code, e.g. one added by the compiler
+    pub fn column(name: impl Into<String>) -> Self {
+        PathPart::Column(PathColumn {
+            name: name.into(),
+            span: Span::default(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathColumn {
+    pub name: String,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -128,6 +266,21 @@ pub struct TableWithMany {
     pub table: String,
     pub condition_set: ConditionSet,
     pub linking_column: Option<String>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pick {
+    pub position: PickPosition,
+    /// The records are sorted by this expression, relative to the table with many
+    pub order_by: Box<Expr>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PickPosition {
+    First,
+    Last,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -135,6 +288,7 @@ pub struct Comparison {
     pub left: ComparisonSide,
     pub operator: Operator,
     pub right: ComparisonSide,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -213,12 +367,17 @@ pub enum Conjunction {
 pub struct HasQuantity {
     pub quantity: Quantity,
     pub path_parts: Vec<PathPart>,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum Quantity {
     AtLeastOne,
     Zero,
+    /// Every related record satisfies the conditions on the final table in the path. This is
+    /// vacuously true when there are no related records.
+    All,
+    ExactlyOne,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -227,6 +386,7 @@ pub struct Call {
     pub dimension: FunctionDimension,
     pub args: Vec<Expr>,
     pub syntax: CallSyntax,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -245,6 +405,7 @@ pub enum FunctionDimension {
 pub enum ResultColumnStatement {
     Spec(ColumnSpec),
     Glob(ColumnGlob),
+    Nested(NestedColumns),
 }
 
 #[derive(Debug, PartialEq)]
@@ -252,6 +413,7 @@ pub struct ColumnSpec {
     pub expr: Expr,
     pub alias: Option<String>,
     pub column_control: ColumnControl,
+    pub span: Span,
 }
 
 #[derive(Debug, PartialEq, Default)]
@@ -296,4 +458,14 @@ pub enum SortDirection {
 pub struct ColumnGlob {
     pub head: Vec<PathPart>,
     pub specs: Vec<ColumnSpec>,
+    pub span: Span,
+}
+
+/// A JSON array with an object for each related record, e.g. `$#comments(body author.username)`
+#[derive(Debug, PartialEq)]
+pub struct NestedColumns {
+    pub path: Vec<PathPart>,
+    pub specs: Vec<ColumnSpec>,
+    pub alias: Option<String>,
+    pub span: Span,
 }
@@ -0,0 +1,224 @@
+use std::{collections::BTreeSet, fmt::Display, ops::Range};
+
+use chumsky::error::{Simple, SimpleReason};
+
+use crate::tokens::*;
+
+/// Problems which prevented querydown code from being parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub message: String,
+    /// Each problem found, in order of position. The parser recovers from problems within
+    /// delimited sections (e.g. condition sets) so that several can be reported in one pass.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// One problem found while parsing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// A human-readable description, e.g. "expected `}` to close condition set"
+    pub message: String,
+    /// The range of characters (not bytes) within the source code at which the problem was found
+    pub span: Range<usize>,
+    /// Other locations within the source code which help to explain the problem
+    pub labels: Vec<Label>,
+}
+
+/// A location within the source code, along with a description of its relevance to a problem
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub span: Range<usize>,
+    pub message: String,
+}
+
+impl ParseError {
+    pub(crate) fn from_simple(errors: Vec<Simple<char>>, message: &str) -> Self {
+        let mut diagnostics = errors
+            .into_iter()
+            .map(Diagnostic::from_simple)
+            .collect::<Vec<_>>();
+        diagnostics.sort_by_key(|d| (d.span.start, d.span.end));
+        diagnostics.dedup();
+        Self {
+            message: message.to_string(),
+            diagnostics,
+        }
+    }
+
+    /// The location of the first problem
+    pub fn span(&self) -> Range<usize> {
+        self.diagnostics
+            .first()
+            .map(|d| d.span.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)?;
+        for diagnostic in &self.diagnostics {
+            write!(f, "\n{}", diagnostic)?;
+        }
+        Ok(())
+    }
+}
+
+impl Diagnostic {
+    fn from_simple(error: Simple<char>) -> Self {
+        let span = error.span();
+        match error.reason() {
+            SimpleReason::Unclosed {
+                span: opening_span,
+                delimiter,
+            } => {
+                let name = error
+                    .label()
+                    .unwrap_or_else(|| describe_delimiter(*delimiter));
+                let closing = error
+                    .expected()
+                    .find_map(|e| *e)
+                    .map(describe_char)
+                    .unwrap_or_else(|| "a closing delimiter".to_string());
+                Self {
+                    message: format!("expected {} to close {}", closing, name),
+                    span,
+                    labels: vec![Label {
+                        span: opening_span.clone(),
+                        message: format!("{} opened here", name),
+                    }],
+                }
+            }
+            SimpleReason::Unexpected => {
+                let found = match error.found() {
+                    Some('`') => "backtick".to_string(),
+                    Some(c) => describe_char(*c),
+                    None => "end of input".to_string(),
+                };
+                // Labels are attached to every error within a labelled parser, even when the
+                // parser didn't match, so they're only trusted for unclosed delimiters.
+                let mut message = format!("unexpected {}", found);
+                if let Some(expected) = describe_expected(error.expected()) {
+                    message.push_str(&format!(", expected {}", expected));
+                }
+                Self {
+                    message,
+                    span,
+                    labels: vec![],
+                }
+            }
+            SimpleReason::Custom(message) => Self {
+                message: message.clone(),
+                span,
+                labels: vec![],
+            },
+        }
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at {}", self.message, describe_span(&self.span))?;
+        for label in &self.labels {
+            write!(f, " ({} at {})", label.message, describe_span(&label.span))?;
+        }
+        Ok(())
+    }
+}
+
+fn describe_span(span: &Range<usize>) -> String {
+    format!("character {}", span.start + 1)
+}
+
+/// Code is quoted within backticks, which can't quote a backtick itself
+fn describe_char(c: char) -> String {
+    match c {
+        '`' => "a backtick".to_string(),
+        _ => format!("`{}`", c),
+    }
+}
+
+fn describe_delimiter(delimiter: char) -> &'static str {
+    match delimiter {
+        CONDITION_SET_AND_BRACE_L | CONDITION_SET_OR_BRACE_L => "condition set",
+        _ => "parentheses",
+    }
+}
+
+/// Lists the expected inputs, unless there are so many that the list wouldn't be helpful (e.g. any
+/// letter which could start an identifier).
+fn describe_expected<'a>(expected: impl Iterator<Item = &'a Option<char>>) -> Option<String> {
+    const MAX_EXPECTED: usize = 8;
+    let expected = expected
+        .map(|e| match e {
+            Some(c) => describe_char(*c),
+            None => "end of input".to_string(),
+        })
+        .collect::<BTreeSet<_>>();
+    if expected.is_empty() || expected.len() > MAX_EXPECTED {
+        return None;
+    }
+    let mut items = expected.into_iter().collect::<Vec<_>>();
+    let last = items.pop()?;
+    if items.is_empty() {
+        Some(last)
+    } else {
+        Some(format!("{} or {}", items.join(", "), last))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse;
+
+    #[test]
+    fn test_diagnostics() {
+        let diagnostics = |s: &str| parse(s).unwrap_err().diagnostics;
+
+        assert_eq!(
+            diagnostics("#issues {a:1 b:2"),
+            vec![Diagnostic {
+                message: "expected `}` to close condition set".to_string(),
+                span: 16..16,
+                labels: vec![Label {
+                    span: 8..9,
+                    message: "condition set opened here".to_string(),
+                }],
+            }]
+        );
+        assert_eq!(
+            diagnostics("#issues (a:1) $@@max(1 2"),
+            vec![Diagnostic {
+                message: "expected `)` to close function arguments".to_string(),
+                span: 24..24,
+                labels: vec![Label {
+                    span: 20..21,
+                    message: "function arguments opened here".to_string(),
+                }],
+            }]
+        );
+
+        // Recovery allows several problems to be reported at once
+        let spans = diagnostics("#issues {a:} b:2 [c:] $(d|e(f:)) $id")
+            .into_iter()
+            .map(|d| d.span)
+            .collect::<Vec<_>>();
+        assert_eq!(spans, vec![11..12, 20..21, 30..31]);
+
+        // Backticks can't be quoted within backticks
+        assert_eq!(
+            diagnostics("#issues $`title")[0].message,
+            "unexpected end of input, expected `\\` or a backtick"
+        );
+        assert_eq!(
+            diagnostics("#issues $title`")[0].message,
+            "unexpected backtick"
+        );
+
+        assert_eq!(
+            parse("#issues $title ] $id").unwrap_err().to_string(),
+            "Invalid querydown code\nunexpected `]` at character 16"
+        );
+    }
+}
@@ -1,14 +1,34 @@
+mod error;
 mod parser;
+mod span;
+#[cfg(test)]
+mod tests;
 
 pub mod ast;
 pub mod tokens;
 
 use chumsky::Parser;
-use parser::query;
+use parser::{module, query};
 
-pub fn parse(input: &str) -> Result<ast::Query, String> {
-    query()
-        .parse(input)
-        // TODO_ERR improve error handling
-        .map_err(|_| "Invalid querydown code".to_string())
+pub use error::{Diagnostic, Label, ParseError};
+pub use span::{LineColumn, Span};
+
+pub fn parse(input: &str) -> Result<ast::Query, ParseError> {
+    finish(query().parse_recovery(input), "Invalid querydown code")
+}
+
+pub fn parse_module(input: &str) -> Result<ast::Module, ParseError> {
+    finish(module().parse_recovery(input), "Invalid querydown module")
+}
+
+/// Output recovered from invalid code is discarded because it doesn't represent what the user
+/// intended.
+fn finish<T>(
+    (output, errors): (Option<T>, Vec<chumsky::error::Simple<char>>),
+    message: &str,
+) -> Result<T, ParseError> {
+    match output {
+        Some(output) if errors.is_empty() => Ok(output),
+        _ => Err(ParseError::from_simple(errors, message)),
+    }
 }
@@ -3,7 +3,7 @@ use chumsky::{prelude::*, text::*};
 use crate::ast::*;
 use crate::tokens::*;
 
-use super::expr::{expr, path_to_one};
+use super::expr::{expr, path};
 use super::utils::*;
 
 pub fn result_columns() -> impl Psr<Vec<ResultColumnStatement>> {
@@ -15,14 +15,16 @@ pub fn result_columns() -> impl Psr<Vec<ResultColumnStatement>> {
 fn result_column_statement() -> impl Psr<ResultColumnStatement> {
     just(COLUMN_SPEC_PREFIX)
         .then(whitespace())
+        // Boxing the alternatives keeps the parser's stack frames small
         .ignore_then(choice((
-            column_glob().map(ResultColumnStatement::Glob),
-            column_spec().map(ResultColumnStatement::Spec),
+            column_glob().map(ResultColumnStatement::Glob).boxed(),
+            nested_columns().map(ResultColumnStatement::Nested).boxed(),
+            column_spec().map(ResultColumnStatement::Spec).boxed(),
         )))
 }
 
 fn column_glob() -> impl Psr<ColumnGlob> {
-    let head = path_to_one()
+    let head = path(expr())
         .then_ignore(just(PATH_SEPARATOR))
         .or_not()
         .map(|p| p.unwrap_or_default());
@@ -39,18 +41,37 @@ fn column_glob() -> impl Psr<ColumnGlob> {
 
     head.then_ignore(just(COLUMN_GLOB))
         .then(specs)
-        .map(|(head, specs)| ColumnGlob { head, specs })
+        .map_with_span(|(head, specs), span| ColumnGlob {
+            head,
+            specs,
+            span: span.into(),
+        })
+}
+
+/// E.g. `#comments(body author.username) -> comments`. A single identifier in parentheses is a
+/// linking column, so specs may be prefixed with `$` to nest a single column, e.g. `#comments($body)`.
+fn nested_columns() -> impl Psr<NestedColumns> {
+    let specs = just(COLUMN_SPEC_PREFIX)
+        .or_not()
+        .ignore_then(column_spec())
+        .padded()
+        .repeated()
+        .at_least(1)
+        .delimited_by(just(NESTED_COLUMNS_BRACE_L), just(NESTED_COLUMNS_BRACE_R));
+    path(expr())
+        .then(specs)
+        .then(column_alias().or_not())
+        .map_with_span(|((path, specs), alias), span| NestedColumns {
+            path,
+            specs,
+            alias,
+            span: span.into(),
+        })
 }
 
 fn column_spec() -> impl Psr<ColumnSpec> {
     expr()
-        .then(
-            whitespace()
-                .then(just(COLUMN_ALIAS_PREFIX))
-                .then(whitespace())
-                .ignore_then(db_identifier())
-                .or_not(),
-        )
+        .then(column_alias().or_not())
         .then(
             whitespace()
                 .ignore_then(
@@ -60,13 +81,21 @@ fn column_spec() -> impl Psr<ColumnSpec> {
                 )
                 .or_not(),
         )
-        .map(|((expr, alias), ctrl)| ColumnSpec {
+        .map_with_span(|((expr, alias), ctrl), span| ColumnSpec {
             expr,
             alias,
             column_control: ctrl.unwrap_or_default(),
+            span: span.into(),
         })
 }
 
+fn column_alias() -> impl Psr<String> {
+    whitespace()
+        .then(just(COLUMN_ALIAS_PREFIX))
+        .then(whitespace())
+        .ignore_then(db_identifier())
+}
+
 fn column_control() -> impl Psr<ColumnControl> {
     #[derive(Clone)]
     enum Flag {
@@ -151,6 +180,7 @@ fn column_control() -> impl Psr<ColumnControl> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tests::WithoutSpans;
 
     #[test]
     fn test_parse_column_control() {
@@ -172,16 +202,18 @@ mod tests {
     #[test]
     fn test_parse_column_spec() {
         assert_eq!(
-            column_spec().parse("8"),
+            column_spec().parse("8").without_spans(),
             Ok(ColumnSpec {
+                span: Span::default(),
                 column_control: ColumnControl::default(),
                 expr: Expr::Number("8".to_string()),
                 alias: None,
             })
         );
         assert_eq!(
-            column_spec().parse(r"foo->bar\s1d"),
+            column_spec().parse(r"foo->bar\s1d").without_spans(),
             Ok(ColumnSpec {
+                span: Span::default(),
                 column_control: ColumnControl {
                     sort: Some(SortSpec {
                         ordinal: Some(1),
@@ -192,7 +224,7 @@ mod tests {
                     is_partition_by: false,
                     is_hidden: false,
                 },
-                expr: Expr::Path(vec![PathPart::Column("foo".to_string())]),
+                expr: Expr::Path(vec![PathPart::column("foo")]),
                 alias: Some("bar".to_string()),
             })
         );
@@ -201,26 +233,28 @@ mod tests {
     #[test]
     fn test_parse_result_columns() {
         assert_eq!(
-            result_columns().parse(r"$* $a.b.*(c \h d\s) $foo $bar->B \g"),
+            result_columns()
+                .parse(r"$* $a.b.*(c \h d\s) $foo $bar->B \g")
+                .without_spans(),
             Ok(vec![
                 ResultColumnStatement::Glob(ColumnGlob::default()),
                 ResultColumnStatement::Glob(ColumnGlob {
-                    head: vec![
-                        PathPart::Column("a".to_string()),
-                        PathPart::Column("b".to_string()),
-                    ],
+                    span: Span::default(),
+                    head: vec![PathPart::column("a"), PathPart::column("b"),],
                     specs: vec![
                         ColumnSpec {
+                            span: Span::default(),
                             column_control: ColumnControl {
                                 sort: None,
                                 group: None,
                                 is_partition_by: false,
                                 is_hidden: true,
                             },
-                            expr: Expr::Path(vec![PathPart::Column("c".to_string())]),
+                            expr: Expr::Path(vec![PathPart::column("c")]),
                             alias: None,
                         },
                         ColumnSpec {
+                            span: Span::default(),
                             column_control: ColumnControl {
                                 sort: Some(SortSpec {
                                     ordinal: None,
@@ -231,27 +265,100 @@ mod tests {
                                 is_partition_by: false,
                                 is_hidden: false,
                             },
-                            expr: Expr::Path(vec![PathPart::Column("d".to_string())]),
+                            expr: Expr::Path(vec![PathPart::column("d")]),
                             alias: None,
                         },
                     ]
                 }),
                 ResultColumnStatement::Spec(ColumnSpec {
+                    span: Span::default(),
                     column_control: ColumnControl::default(),
-                    expr: Expr::Path(vec![PathPart::Column("foo".to_string())]),
+                    expr: Expr::Path(vec![PathPart::column("foo")]),
                     alias: None,
                 }),
                 ResultColumnStatement::Spec(ColumnSpec {
+                    span: Span::default(),
                     column_control: ColumnControl {
                         sort: None,
                         group: Some(GroupSpec { ordinal: None }),
                         is_partition_by: false,
                         is_hidden: false,
                     },
-                    expr: Expr::Path(vec![PathPart::Column("bar".to_string())]),
+                    expr: Expr::Path(vec![PathPart::column("bar")]),
                     alias: Some("B".to_string()),
                 }),
             ])
         );
     }
+
+    #[test]
+    fn test_parse_nested_columns() {
+        let column = |name: &str| ColumnSpec {
+            span: Span::default(),
+            column_control: ColumnControl::default(),
+            expr: Expr::Path(vec![PathPart::column(name)]),
+            alias: None,
+        };
+        let comments = || {
+            PathPart::TableWithMany(TableWithMany {
+                span: Span::default(),
+                table: "comments".to_string(),
+                linking_column: None,
+                condition_set: ConditionSet::default(),
+            })
+        };
+        assert_eq!(
+            result_columns()
+                .parse(r"$#comments.* $#comments(body user.username) -> c")
+                .without_spans(),
+            Ok(vec![
+                ResultColumnStatement::Glob(ColumnGlob {
+                    span: Span::default(),
+                    head: vec![comments()],
+                    specs: vec![],
+                }),
+                ResultColumnStatement::Nested(NestedColumns {
+                    span: Span::default(),
+                    path: vec![comments()],
+                    specs: vec![
+                        column("body"),
+                        ColumnSpec {
+                            span: Span::default(),
+                            column_control: ColumnControl::default(),
+                            expr: Expr::Path(vec![
+                                PathPart::column("user"),
+                                PathPart::column("username"),
+                            ]),
+                            alias: None,
+                        },
+                    ],
+                    alias: Some("c".to_string()),
+                }),
+            ])
+        );
+        assert_eq!(
+            result_columns().parse("$#comments($body)").without_spans(),
+            Ok(vec![ResultColumnStatement::Nested(NestedColumns {
+                span: Span::default(),
+                path: vec![comments()],
+                specs: vec![column("body")],
+                alias: None,
+            })])
+        );
+        // A single identifier in parentheses is a linking column
+        assert_eq!(
+            result_columns().parse("$#comments(issue)").without_spans(),
+            Ok(vec![ResultColumnStatement::Spec(ColumnSpec {
+                span: Span::default(),
+                column_control: ColumnControl::default(),
+                expr: Expr::Path(vec![PathPart::TableWithMany(TableWithMany {
+                    span: Span::default(),
+                    table: "comments".to_string(),
+                    linking_column: Some("issue".to_string()),
+                    condition_set: ConditionSet::default(),
+                })]),
+                alias: None,
+            })])
+        );
+    }
 }
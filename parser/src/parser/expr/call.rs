@@ -0,0 +1,30 @@
+use chumsky::{prelude::*, text::*};
+
+use crate::ast::*;
+use crate::parser::utils::*;
+use crate::tokens::*;
+
+/// Parenthesized arguments which follow a function name, e.g. `(2 "foo")`
+pub fn call_args(expr: impl Psr<Expr>) -> impl Psr<Vec<Expr>> {
+    delimited(
+        expr.padded().repeated(),
+        COMPOSITION_ARGUMENT_BRACE_L,
+        COMPOSITION_ARGUMENT_BRACE_R,
+        Vec::new,
+    )
+    .labelled("function arguments")
+}
+
+/// A function called without piping, e.g. `@@max(a b)`
+pub fn standalone_call(expr: impl Psr<Expr>) -> impl Psr<Call> {
+    exactly(FUNCTION_SIGIL)
+        .ignore_then(ident())
+        .then(call_args(expr).or_not())
+        .map_with_span(|(name, args), span| Call {
+            name,
+            dimension: FunctionDimension::Scalar,
+            args: args.unwrap_or_default(),
+            syntax: CallSyntax::Standalone,
+            span: span.into(),
+        })
+}
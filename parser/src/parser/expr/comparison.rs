@@ -27,10 +27,11 @@ pub fn comparison(
 
     left.then(operator().padded())
         .then(right)
-        .map(|((left, operator), right)| Comparison {
+        .map_with_span(|((left, operator), right), span| Comparison {
             left,
             operator,
             right,
+            span: span.into(),
         })
 }
 
@@ -19,11 +19,10 @@ fn specific_condition_set(
         Conjunction::And => (CONDITION_SET_AND_BRACE_L, CONDITION_SET_AND_BRACE_R),
         Conjunction::Or => (CONDITION_SET_OR_BRACE_L, CONDITION_SET_OR_BRACE_R),
     };
-    expr.padded()
-        .repeated()
-        .delimited_by(just(brace_l), just(brace_r))
+    delimited(expr.padded().repeated(), brace_l, brace_r, Vec::new)
         .map(move |entries| ConditionSet {
             conjunction,
             entries,
         })
+        .labelled("condition set")
 }
@@ -5,8 +5,8 @@ use crate::parser::utils::*;
 use crate::tokens::*;
 
 use super::{
-    comparison::comparison, condition_set::condition_set, date::date, duration::duration,
-    has_quantity::has_quantity, number::number, path::path, pipe::pipe,
+    call::standalone_call, comparison::comparison, condition_set::condition_set, date::date,
+    duration::duration, has_quantity::has_quantity, number::number, path::path, pipe::pipe,
 };
 
 pub fn expr() -> impl Psr<Expr> {
@@ -38,9 +38,11 @@ pub fn expr() -> impl Psr<Expr> {
             date().map(Expr::Date),
             duration().map(Expr::Duration),
             string().map(Expr::String),
+            standalone_call(prec_comparison.clone()).map(Expr::Call),
             variable().map(Expr::Variable),
             path(prec_comparison.clone()).map(Expr::Path),
             has_quantity(prec_comparison.clone()).map(Expr::HasQuantity),
+            outer(prec_comparison.clone()),
             condition_set(prec_comparison.clone()).map(Expr::ConditionSet),
             parenthetical(prec_comparison.clone()),
         ));
@@ -68,13 +70,22 @@ fn variable() -> impl Psr<String> {
     just(CONST_SIGIL).ignore_then(ident())
 }
 
+fn outer(e: impl Psr<Expr>) -> impl Psr<Expr> {
+    let inner = choice((path(e.clone()).map(Expr::Path), parenthetical(e)));
+    just(OUTER_RECORD_SIGIL)
+        .ignore_then(inner)
+        .map(|e| Expr::Outer(Box::new(e)))
+}
+
 fn string() -> impl Psr<String> {
     quoted(STRING_QUOTE_SINGLE).or(quoted(STRING_QUOTE_DOUBLE))
 }
 
 fn parenthetical(e: impl Psr<Expr>) -> impl Psr<Expr> {
-    e.padded()
-        .delimited_by(just(EXPR_PAREN_L), just(EXPR_PAREN_R))
+    delimited(e.padded(), EXPR_PAREN_L, EXPR_PAREN_R, || {
+        Expr::Path(vec![])
+    })
+    .labelled("parentheses")
 }
 
 fn multiplication(e: impl Psr<Expr>) -> impl Psr<Expr> {
@@ -100,6 +111,7 @@ fn addition(e: impl Psr<Expr>) -> impl Psr<Expr> {
 #[cfg(test)]
 mod tests {
     use crate::ast::*;
+    use crate::tests::WithoutSpans;
     use chumsky::prelude::*;
 
     use super::expr;
@@ -107,7 +119,7 @@ mod tests {
     #[test]
     fn test_parse_expr() {
         let parser = expr().then_ignore(end());
-        let p = |s: &str| parser.parse(s);
+        let p = |s: &str| parser.parse(s).without_spans();
 
         assert_eq!(p("8"), Ok(Expr::Number("8".to_string())));
         assert_eq!(
@@ -129,15 +141,14 @@ mod tests {
         assert_eq!(p("\"foo\""), Ok(Expr::String("foo".to_string())));
         assert_eq!(p("@foo"), Ok(Expr::Variable("foo".to_string())));
         assert_eq!(p("@null"), Ok(Expr::Variable("null".to_string())));
-        assert_eq!(
-            p("foo"),
-            Ok(Expr::Path(vec![PathPart::Column("foo".to_string())]))
-        );
+        assert_eq!(p("foo"), Ok(Expr::Path(vec![PathPart::column("foo")])));
         assert_eq!(
             p("++#foo"),
             Ok(Expr::HasQuantity(HasQuantity {
+                span: Span::default(),
                 quantity: Quantity::AtLeastOne,
                 path_parts: vec![PathPart::TableWithMany(TableWithMany {
+                    span: Span::default(),
                     table: "foo".to_string(),
                     condition_set: ConditionSet::default(),
                     linking_column: None
@@ -147,15 +158,16 @@ mod tests {
         assert_eq!(
             p("++#foo{a:2}"),
             Ok(Expr::HasQuantity(HasQuantity {
+                span: Span::default(),
                 quantity: Quantity::AtLeastOne,
                 path_parts: vec![PathPart::TableWithMany(TableWithMany {
+                    span: Span::default(),
                     table: "foo".to_string(),
                     condition_set: ConditionSet {
                         conjunction: Conjunction::And,
                         entries: vec![Expr::Comparison(Box::new(Comparison {
-                            left: ComparisonSide::Expr(Expr::Path(vec![PathPart::Column(
-                                "a".to_string()
-                            )])),
+                            span: Span::default(),
+                            left: ComparisonSide::Expr(Expr::Path(vec![PathPart::column("a")])),
                             operator: Operator::Eq,
                             right: ComparisonSide::Expr(Expr::Number("2".to_string())),
                         }))]
@@ -164,13 +176,57 @@ mod tests {
                 })]
             }))
         );
+        assert_eq!(
+            p("**#foo{a}"),
+            Ok(Expr::HasQuantity(HasQuantity {
+                span: Span::default(),
+                quantity: Quantity::All,
+                path_parts: vec![PathPart::TableWithMany(TableWithMany {
+                    span: Span::default(),
+                    table: "foo".to_string(),
+                    condition_set: ConditionSet {
+                        conjunction: Conjunction::And,
+                        entries: vec![Expr::Path(vec![PathPart::column("a")])]
+                    },
+                    linking_column: None
+                })]
+            }))
+        );
+        assert_eq!(
+            p("==#foo"),
+            Ok(Expr::HasQuantity(HasQuantity {
+                span: Span::default(),
+                quantity: Quantity::ExactlyOne,
+                path_parts: vec![PathPart::TableWithMany(TableWithMany {
+                    span: Span::default(),
+                    table: "foo".to_string(),
+                    condition_set: ConditionSet::default(),
+                    linking_column: None
+                })]
+            }))
+        );
+        assert_eq!(
+            p("&a.b"),
+            Ok(Expr::Outer(Box::new(Expr::Path(vec![
+                PathPart::column("a"),
+                PathPart::column("b"),
+            ]))))
+        );
+        assert_eq!(
+            p("&(a + 1)"),
+            Ok(Expr::Outer(Box::new(Expr::Sum(
+                Box::new(Expr::Path(vec![PathPart::column("a")])),
+                Box::new(Expr::Number("1".to_string())),
+            ))))
+        );
+        assert!(p("&8").is_err());
         assert_eq!(
             p("[a b]"),
             Ok(Expr::ConditionSet(ConditionSet {
                 conjunction: Conjunction::Or,
                 entries: vec![
-                    Expr::Path(vec![PathPart::Column("a".to_string())]),
-                    Expr::Path(vec![PathPart::Column("b".to_string())]),
+                    Expr::Path(vec![PathPart::column("a")]),
+                    Expr::Path(vec![PathPart::column("b")]),
                 ]
             }))
         );
@@ -179,12 +235,49 @@ mod tests {
             Ok(Expr::ConditionSet(ConditionSet {
                 conjunction: Conjunction::And,
                 entries: vec![
-                    Expr::Path(vec![PathPart::Column("a".to_string())]),
-                    Expr::Path(vec![PathPart::Column("b".to_string())]),
+                    Expr::Path(vec![PathPart::column("a")]),
+                    Expr::Path(vec![PathPart::column("b")]),
                 ]
             }))
         );
 
+        assert_eq!(
+            p("#foo.(a * b)"),
+            Ok(Expr::Path(vec![
+                PathPart::TableWithMany(TableWithMany {
+                    span: Span::default(),
+                    table: "foo".to_string(),
+                    condition_set: ConditionSet::default(),
+                    linking_column: None
+                }),
+                PathPart::Expr(Box::new(Expr::Product(
+                    Box::new(Expr::Path(vec![PathPart::column("a")])),
+                    Box::new(Expr::Path(vec![PathPart::column("b")]))
+                )))
+            ]))
+        );
+        assert!(p("#foo.(a).b").is_err());
+        assert_eq!(
+            p("#foo%last(a).b"),
+            Ok(Expr::Path(vec![
+                PathPart::TableWithMany(TableWithMany {
+                    span: Span::default(),
+                    table: "foo".to_string(),
+                    condition_set: ConditionSet::default(),
+                    linking_column: None
+                }),
+                PathPart::Pick(Pick {
+                    span: Span::default(),
+                    position: PickPosition::Last,
+                    order_by: Box::new(Expr::Path(vec![PathPart::column("a")])),
+                }),
+                PathPart::column("b"),
+            ]))
+        );
+        assert!(matches!(p("#foo%lastly(a)"), Ok(Expr::Call(_))));
+        assert!(matches!(p("a*b"), Ok(Expr::Product(_, _))));
+        assert!(matches!(p("a*.b"), Ok(Expr::Path(_))));
+
         assert_eq!(
             p("5*7"),
             Ok(Expr::Product(
@@ -284,7 +377,9 @@ mod tests {
         assert_eq!(
             p("(1:2):3"),
             Ok(Expr::Comparison(Box::new(Comparison {
+                span: Span::default(),
                 left: ComparisonSide::Expr(Expr::Comparison(Box::new(Comparison {
+                    span: Span::default(),
                     left: ComparisonSide::Expr(Expr::Number("1".to_string())),
                     operator: Operator::Eq,
                     right: ComparisonSide::Expr(Expr::Number("2".to_string())),
@@ -297,7 +392,8 @@ mod tests {
         assert_eq!(
             p("x:@a..@b"),
             Ok(Expr::Comparison(Box::new(Comparison {
-                left: ComparisonSide::Expr(Expr::Path(vec![PathPart::Column("x".to_string())])),
+                span: Span::default(),
+                left: ComparisonSide::Expr(Expr::Path(vec![PathPart::column("x")])),
                 operator: Operator::Eq,
                 right: ComparisonSide::Range(Range {
                     lower: RangeBound {
@@ -315,7 +411,8 @@ mod tests {
         assert_eq!(
             p("x:@a<..<@b"),
             Ok(Expr::Comparison(Box::new(Comparison {
-                left: ComparisonSide::Expr(Expr::Path(vec![PathPart::Column("x".to_string())])),
+                span: Span::default(),
+                left: ComparisonSide::Expr(Expr::Path(vec![PathPart::column("x")])),
                 operator: Operator::Eq,
                 right: ComparisonSide::Range(Range {
                     lower: RangeBound {
@@ -333,16 +430,19 @@ mod tests {
         assert_eq!(
             p("1|a|b(2)|c(3 4)"),
             Ok(Expr::Call(Call {
+                span: Span::default(),
                 name: "c".to_string(),
                 dimension: FunctionDimension::Scalar,
                 syntax: CallSyntax::Piped,
                 args: vec![
                     Expr::Call(Call {
+                        span: Span::default(),
                         name: "b".to_string(),
                         dimension: FunctionDimension::Scalar,
                         syntax: CallSyntax::Piped,
                         args: vec![
                             Expr::Call(Call {
+                                span: Span::default(),
                                 name: "a".to_string(),
                                 dimension: FunctionDimension::Scalar,
                                 syntax: CallSyntax::Piped,
@@ -357,13 +457,45 @@ mod tests {
             }))
         );
 
+        assert_eq!(
+            p("@@max(a 0)|abs"),
+            Ok(Expr::Call(Call {
+                span: Span::default(),
+                name: "abs".to_string(),
+                dimension: FunctionDimension::Scalar,
+                syntax: CallSyntax::Piped,
+                args: vec![Expr::Call(Call {
+                    span: Span::default(),
+                    name: "max".to_string(),
+                    dimension: FunctionDimension::Scalar,
+                    syntax: CallSyntax::Standalone,
+                    args: vec![
+                        Expr::Path(vec![PathPart::column("a")]),
+                        Expr::Number("0".to_string()),
+                    ],
+                })],
+            }))
+        );
+
+        assert_eq!(
+            p("@@is_open"),
+            Ok(Expr::Call(Call {
+                span: Span::default(),
+                name: "is_open".to_string(),
+                dimension: FunctionDimension::Scalar,
+                syntax: CallSyntax::Standalone,
+                args: vec![],
+            }))
+        );
+
         assert_eq!(
             p("[a b] ..! 2 + foo * @bar | baz"),
             Ok(Expr::Comparison(Box::new(Comparison {
+                span: Span::default(),
                 left: ComparisonSide::Expansion(ConditionSet {
                     entries: vec![
-                        Expr::Path(vec![PathPart::Column("a".to_string())]),
-                        Expr::Path(vec![PathPart::Column("b".to_string())]),
+                        Expr::Path(vec![PathPart::column("a")]),
+                        Expr::Path(vec![PathPart::column("b")]),
                     ],
                     conjunction: Conjunction::Or,
                 }),
@@ -371,8 +503,9 @@ mod tests {
                 right: ComparisonSide::Expr(Expr::Sum(
                     Box::new(Expr::Number("2".to_string())),
                     Box::new(Expr::Product(
-                        Box::new(Expr::Path(vec![PathPart::Column("foo".to_string())])),
+                        Box::new(Expr::Path(vec![PathPart::column("foo")])),
                         Box::new(Expr::Call(Call {
+                            span: Span::default(),
                             name: "baz".to_string(),
                             dimension: FunctionDimension::Scalar,
                             syntax: CallSyntax::Piped,
@@ -10,12 +10,15 @@ pub fn has_quantity(expr: impl Psr<Expr>) -> impl Psr<HasQuantity> {
     let quantity = choice((
         exactly(HAS_QUANTITY_AT_LEAST_ONE).to(Quantity::AtLeastOne),
         exactly(HAS_QUANTITY_ZERO).to(Quantity::Zero),
+        exactly(HAS_QUANTITY_ALL).to(Quantity::All),
+        exactly(HAS_QUANTITY_EXACTLY_ONE).to(Quantity::ExactlyOne),
     ));
     quantity
         .then_ignore(whitespace())
         .then(path(expr))
-        .map(|(quantity, path_parts)| HasQuantity {
+        .map_with_span(|(quantity, path_parts), span| HasQuantity {
             quantity,
             path_parts,
+            span: span.into(),
         })
 }
@@ -1,3 +1,4 @@
+mod call;
 mod comparison;
 mod condition_set;
 mod date;
@@ -9,4 +10,4 @@ mod path;
 mod pipe;
 
 pub use expr::expr;
-pub use path::path_to_one;
+pub use path::path;
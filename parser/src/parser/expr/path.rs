@@ -7,36 +7,68 @@ use crate::tokens::*;
 use super::condition_set::condition_set;
 
 pub fn path(expr: impl Psr<Expr>) -> impl Psr<Vec<PathPart>> {
-    path_part(expr.clone()).chain(
-        whitespace()
-            .then(just(PATH_SEPARATOR))
-            .ignore_then(path_part(expr))
-            .repeated(),
-    )
+    path_segment(expr.clone())
+        .chain(
+            whitespace()
+                .then(just(PATH_SEPARATOR))
+                .ignore_then(path_segment(expr.clone()))
+                .repeated()
+                .flatten(),
+        )
+        .chain(just(PATH_SEPARATOR).ignore_then(path_expr(expr)).or_not())
 }
 
-pub fn path_to_one() -> impl Psr<Vec<PathPart>> {
-    path_part_to_one().chain(
-        whitespace()
-            .then(just(PATH_SEPARATOR))
-            .ignore_then(path_part_to_one())
-            .repeated(),
-    )
-}
-
-fn path_part(expr: impl Psr<Expr>) -> impl Psr<PathPart> {
+/// The parts between two path separators. This is usually one part, but a column may be followed
+/// by a transitive marker, and a table with many may be followed by a pick or a transitive marker.
+fn path_segment(expr: impl Psr<Expr>) -> impl Psr<Vec<PathPart>> {
     choice((
-        db_identifier().map(PathPart::Column),
-        table_with_many(expr).map(PathPart::TableWithMany),
-        table_with_one().map(PathPart::TableWithOne),
+        db_identifier()
+            .map_with_span(|name, span| {
+                vec![PathPart::Column(PathColumn {
+                    name,
+                    span: span.into(),
+                })]
+            })
+            .chain(transitive().or_not()),
+        table_with_many(expr.clone())
+            .map(PathPart::TableWithMany)
+            .chain(choice((pick(expr).map(PathPart::Pick), transitive())).or_not()),
+        table_with_one().map(|t| vec![PathPart::TableWithOne(t)]),
     ))
 }
 
-fn path_part_to_one() -> impl Psr<PathPart> {
-    choice((
-        db_identifier().map(PathPart::Column),
-        table_with_one().map(PathPart::TableWithOne),
-    ))
+/// E.g. the `*` in `duplicate_of*.title`. To distinguish it from multiplication, it must be
+/// directly followed by a path separator or an aggregate pipe.
+fn transitive() -> impl Psr<PathPart> {
+    just(PATH_TRANSITIVE)
+        .then_ignore(one_of([PATH_SEPARATOR, COMPOSITION_PIPE_AGGREGATE]).rewind())
+        .to(PathPart::Transitive)
+}
+
+/// E.g. `%first(created_at)`
+fn pick(expr: impl Psr<Expr>) -> impl Psr<Pick> {
+    let position = choice((
+        exactly(PATH_PICK_FIRST).to(PickPosition::First),
+        exactly(PATH_PICK_LAST).to(PickPosition::Last),
+    ));
+    just(PATH_PICK_PREFIX)
+        .ignore_then(position)
+        .then(
+            expr.padded()
+                .delimited_by(just(EXPR_PAREN_L), just(EXPR_PAREN_R)),
+        )
+        .map_with_span(|(position, order_by), span| Pick {
+            position,
+            order_by: Box::new(order_by),
+            span: span.into(),
+        })
+}
+
+/// An expression evaluated relative to the table at the end of a path, e.g. `.(price * quantity)`
+fn path_expr(expr: impl Psr<Expr>) -> impl Psr<PathPart> {
+    expr.padded()
+        .delimited_by(just(EXPR_PAREN_L), just(EXPR_PAREN_R))
+        .map(|e| PathPart::Expr(Box::new(e)))
 }
 
 fn table_with_one() -> impl Psr<String> {
@@ -48,21 +80,22 @@ fn table_with_many(expr: impl Psr<Expr>) -> impl Psr<TableWithMany> {
         just(TABLE_WITH_MANY_COLUMN_BRACE_L).then(whitespace()),
         whitespace().then(just(TABLE_WITH_MANY_COLUMN_BRACE_R)),
     );
-    just(TABLE_SIGIL).ignore_then(
-        db_identifier()
-            .then(column.or_not())
-            .then(condition_set(expr).or_not())
-            .map(|((table, column), cs)| TableWithMany {
-                table,
-                condition_set: cs.unwrap_or_default(),
-                linking_column: column,
-            }),
-    )
+    just(TABLE_SIGIL)
+        .ignore_then(db_identifier())
+        .then(column.or_not())
+        .then(condition_set(expr).or_not())
+        .map_with_span(|((table, column), cs), span| TableWithMany {
+            table,
+            condition_set: cs.unwrap_or_default(),
+            linking_column: column,
+            span: span.into(),
+        })
 }
 
 #[cfg(test)]
 mod tests {
     use crate::parser::utils::*;
+    use crate::tests::WithoutSpans;
 
     use super::*;
 
@@ -80,56 +113,80 @@ mod tests {
     #[test]
     fn test_parse_path() {
         assert_eq!(
-            simple_path().parse("foo"),
-            Ok(vec![PathPart::Column("foo".to_string())])
+            simple_path().parse("foo").without_spans(),
+            Ok(vec![PathPart::column("foo")])
         );
         assert_eq!(
-            simple_path().parse("foo.bar"),
-            Ok(vec![
-                PathPart::Column("foo".to_string()),
-                PathPart::Column("bar".to_string()),
-            ])
+            simple_path().parse("foo.bar").without_spans(),
+            Ok(vec![PathPart::column("foo"), PathPart::column("bar"),])
         );
         assert_eq!(
-            simple_path().parse("#foo"),
+            simple_path().parse("#foo").without_spans(),
             Ok(vec![PathPart::TableWithMany(TableWithMany {
+                span: Span::default(),
                 table: "foo".to_string(),
                 linking_column: None,
                 condition_set: ConditionSet::default(),
             })])
         );
         assert_eq!(
-            simple_path().parse("#foo(bar)"),
+            simple_path().parse("#foo(bar)").without_spans(),
             Ok(vec![PathPart::TableWithMany(TableWithMany {
+                span: Span::default(),
                 table: "foo".to_string(),
                 linking_column: Some("bar".to_string()),
                 condition_set: ConditionSet::default(),
             })])
         );
         assert_eq!(
-            simple_path().parse(">>clients.start_date"),
+            simple_path().parse(">>clients.start_date").without_spans(),
             Ok(vec![
                 PathPart::TableWithOne("clients".to_string()),
-                PathPart::Column("start_date".to_string()),
+                PathPart::column("start_date"),
             ])
         );
         assert_eq!(
-            simple_path().parse("foo.bar.#baz(a).#bat.>>spam.eggs"),
+            simple_path()
+                .parse("foo.bar.#baz(a).#bat.>>spam.eggs")
+                .without_spans(),
             Ok(vec![
-                PathPart::Column("foo".to_string()),
-                PathPart::Column("bar".to_string()),
+                PathPart::column("foo"),
+                PathPart::column("bar"),
                 PathPart::TableWithMany(TableWithMany {
+                    span: Span::default(),
                     table: "baz".to_string(),
                     linking_column: Some("a".to_string()),
                     condition_set: ConditionSet::default(),
                 }),
                 PathPart::TableWithMany(TableWithMany {
+                    span: Span::default(),
                     table: "bat".to_string(),
                     linking_column: None,
                     condition_set: ConditionSet::default(),
                 }),
                 PathPart::TableWithOne("spam".to_string()),
-                PathPart::Column("eggs".to_string()),
+                PathPart::column("eggs"),
+            ])
+        );
+        assert_eq!(
+            simple_path().parse("foo*.bar").without_spans(),
+            Ok(vec![
+                PathPart::column("foo"),
+                PathPart::Transitive,
+                PathPart::column("bar"),
+            ])
+        );
+        assert_eq!(
+            simple_path().parse("#foo(bar)*.baz").without_spans(),
+            Ok(vec![
+                PathPart::TableWithMany(TableWithMany {
+                    span: Span::default(),
+                    table: "foo".to_string(),
+                    linking_column: Some("bar".to_string()),
+                    condition_set: ConditionSet::default(),
+                }),
+                PathPart::Transitive,
+                PathPart::column("baz"),
             ])
         );
 
@@ -141,5 +198,7 @@ mod tests {
         assert!(simple_path().parse("foo. #bar").is_err(),);
         assert!(simple_path().parse("foo(bar)").is_err(),);
         assert!(simple_path().parse("foo.bar(baz)").is_err(),);
+        assert!(simple_path().parse("foo*").is_err(),);
+        assert!(simple_path().parse("foo*bar").is_err(),);
     }
 }
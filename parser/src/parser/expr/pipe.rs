@@ -4,10 +4,10 @@ use crate::ast::*;
 use crate::parser::utils::*;
 use crate::tokens::*;
 
+use super::call::call_args;
+
 pub fn pipe(arg0_expr: impl Psr<Expr>, extra_args_expr: impl Psr<Expr>) -> impl Psr<Expr> {
-    let args = just(COMPOSITION_ARGUMENT_BRACE_L)
-        .ignore_then(extra_args_expr.padded().repeated())
-        .then_ignore(just(COMPOSITION_ARGUMENT_BRACE_R));
+    let args = call_args(extra_args_expr);
 
     let dimension = choice((
         just(COMPOSITION_PIPE_SCALAR).to(FunctionDimension::Scalar),
@@ -16,13 +16,17 @@ pub fn pipe(arg0_expr: impl Psr<Expr>, extra_args_expr: impl Psr<Expr>) -> impl
 
     arg0_expr
         .then(
+            // The span of a piped call covers only the pipe, since the first argument may be
+            // arbitrarily long. Boxing keeps the parser's type small enough to compile.
             dimension
                 .padded()
                 .then(ident())
                 .then(args.or_not())
+                .map_with_span(|segment, span| (segment, Span::from(span)))
+                .boxed()
                 .repeated(),
         )
-        .foldl(|arg0, ((dimension, name), extra_args)| {
+        .foldl(|arg0, (((dimension, name), extra_args), span)| {
             let args = vec![arg0]
                 .into_iter()
                 .chain(extra_args.unwrap_or_default().into_iter())
@@ -32,6 +36,7 @@ pub fn pipe(arg0_expr: impl Psr<Expr>, extra_args_expr: impl Psr<Expr>) -> impl
                 dimension,
                 syntax: CallSyntax::Piped,
                 args,
+                span,
             })
         })
 }
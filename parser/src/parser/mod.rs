@@ -1,6 +1,8 @@
 mod column_layout;
 mod expr;
+mod module;
 mod query;
 mod utils;
 
+pub use module::module;
 pub use query::query;
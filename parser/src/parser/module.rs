@@ -0,0 +1,192 @@
+use chumsky::{prelude::*, text::*};
+
+use crate::ast::*;
+use crate::tokens::*;
+
+use super::expr::expr;
+use super::utils::*;
+
+pub fn module() -> impl Psr<Module> {
+    statements()
+        .then_ignore(end())
+        .map(|statements| Module { statements })
+}
+
+/// Zero or more imports and definitions, along with any whitespace that surrounds them
+pub fn statements() -> impl Psr<Vec<Statement>> {
+    whitespace().ignore_then(statement().then_ignore(whitespace()).repeated())
+}
+
+fn statement() -> impl Psr<Statement> {
+    let export = exactly(MODULE_EXPORT).then(whitespace());
+    let import = exactly(MODULE_IMPORT).then(whitespace());
+    choice((
+        import
+            .ignore_then(import_body(false))
+            .map(Statement::Import),
+        export
+            .clone()
+            .ignore_then(import_body(true))
+            .map(Statement::Import),
+        export.or_not().then(definition_body()).map_with_span(
+            |(export, (name, params, body)), span| {
+                Statement::Definition(Definition {
+                    name,
+                    params,
+                    body,
+                    is_exported: export.is_some(),
+                    span: span.into(),
+                })
+            },
+        ),
+    ))
+}
+
+fn import_body(is_exported: bool) -> impl Psr<Import> {
+    let item = definition_name()
+        .then(
+            whitespace()
+                .then(exactly(MODULE_IMPORT_ALIAS_PREFIX))
+                .then(whitespace())
+                .ignore_then(db_identifier())
+                .or_not(),
+        )
+        .map(|(name, alias)| ImportItem { name, alias });
+    let items = choice((
+        just(MODULE_IMPORT_ALL).padded().to(ImportItems::All),
+        item.padded().repeated().map(ImportItems::Named),
+    ))
+    .delimited_by(just(MODULE_IMPORT_BRACE_L), just(MODULE_IMPORT_BRACE_R));
+    module_path()
+        .then_ignore(whitespace())
+        .then(items)
+        .map_with_span(move |(module, items), span| Import {
+            module,
+            items,
+            is_exported,
+            span: span.into(),
+        })
+}
+
+fn module_path() -> impl Psr<String> {
+    filter(|c: &char| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | '/'))
+        .repeated()
+        .at_least(1)
+        .collect::<String>()
+}
+
+fn definition_body() -> impl Psr<(DefinitionName, Vec<String>, Expr)> {
+    let param = just(CONST_SIGIL).ignore_then(ident()).padded();
+    let params = param
+        .repeated()
+        .at_least(1)
+        .then_ignore(just(DEFINITION_PARAMS_TERMINATOR))
+        .then_ignore(whitespace());
+    definition_name()
+        .then_ignore(just(DEFINITION_ASSIGNMENT).padded())
+        .then(params.or_not())
+        .then(expr())
+        .map(|((name, params), body)| (name, params.unwrap_or_default(), body))
+}
+
+fn definition_name() -> impl Psr<DefinitionName> {
+    let function_name = exactly(FUNCTION_SIGIL).ignore_then(ident());
+    let table_member = just(TABLE_SIGIL)
+        .ignore_then(db_identifier())
+        .then_ignore(just(PATH_SEPARATOR));
+    choice((
+        table_member
+            .clone()
+            .then(function_name.clone())
+            .map(|(table, function)| DefinitionName::TableFunction { table, function }),
+        table_member
+            .then(db_identifier())
+            .map(|(table, column)| DefinitionName::Column { table, column }),
+        function_name.map(DefinitionName::Function),
+        just(CONST_SIGIL)
+            .ignore_then(ident())
+            .map(DefinitionName::Constant),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::WithoutSpans;
+
+    #[test]
+    fn test_parse_module() {
+        let input = r#"
+            <<< foo/bar(#issues.@@involves->has @limit)
+            === ./baz(*)
+            @user_id = 1234
+            === @@plus_one = @v; @v + 1
+            #users.age = birth_date|age
+        "#;
+        assert_eq!(
+            module().parse(input).without_spans(),
+            Ok(Module {
+                statements: vec![
+                    Statement::Import(Import {
+                        span: Span::default(),
+                        module: "foo/bar".to_string(),
+                        items: ImportItems::Named(vec![
+                            ImportItem {
+                                name: DefinitionName::TableFunction {
+                                    table: "issues".to_string(),
+                                    function: "involves".to_string(),
+                                },
+                                alias: Some("has".to_string()),
+                            },
+                            ImportItem {
+                                name: DefinitionName::Constant("limit".to_string()),
+                                alias: None,
+                            },
+                        ]),
+                        is_exported: false,
+                    }),
+                    Statement::Import(Import {
+                        span: Span::default(),
+                        module: "./baz".to_string(),
+                        items: ImportItems::All,
+                        is_exported: true,
+                    }),
+                    Statement::Definition(Definition {
+                        span: Span::default(),
+                        name: DefinitionName::Constant("user_id".to_string()),
+                        params: vec![],
+                        body: Expr::Number("1234".to_string()),
+                        is_exported: false,
+                    }),
+                    Statement::Definition(Definition {
+                        span: Span::default(),
+                        name: DefinitionName::Function("plus_one".to_string()),
+                        params: vec!["v".to_string()],
+                        body: Expr::Sum(
+                            Box::new(Expr::Variable("v".to_string())),
+                            Box::new(Expr::Number("1".to_string())),
+                        ),
+                        is_exported: true,
+                    }),
+                    Statement::Definition(Definition {
+                        span: Span::default(),
+                        name: DefinitionName::Column {
+                            table: "users".to_string(),
+                            column: "age".to_string(),
+                        },
+                        params: vec![],
+                        body: Expr::Call(Call {
+                            span: Span::default(),
+                            name: "age".to_string(),
+                            dimension: FunctionDimension::Scalar,
+                            args: vec![Expr::Path(vec![PathPart::column("birth_date")])],
+                            syntax: CallSyntax::Piped,
+                        }),
+                        is_exported: false,
+                    }),
+                ],
+            })
+        );
+        assert!(module().parse("#issues").is_err());
+    }
+}
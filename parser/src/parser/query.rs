@@ -4,25 +4,30 @@ use crate::ast::*;
 use crate::tokens::*;
 
 use super::utils::*;
-use super::{column_layout::result_columns, expr::expr};
+use super::{column_layout::result_columns, expr::expr, module::statements};
 
 pub fn query() -> impl Psr<Query> {
-    let base_table = just(TABLE_SIGIL).ignore_then(db_identifier());
+    let base_table = just(TABLE_SIGIL)
+        .ignore_then(db_identifier())
+        .map_with_span(|name, span| (name, Span::from(span)));
     let transformations = transformation().separated_by(
         whitespace()
             .then(exactly(TRANSFORMATION_DELIMITER))
             .then(whitespace()),
     );
-    whitespace().ignore_then(
-        base_table
-            .then_ignore(whitespace())
-            .then(transformations)
-            .then_ignore(whitespace().then(end()))
-            .map(|(base_table, transformations)| Query {
+    statements()
+        .then(base_table)
+        .then_ignore(whitespace())
+        .then(transformations)
+        .then_ignore(whitespace().then(end()))
+        .map(
+            |((statements, (base_table, base_table_span)), transformations)| Query {
+                statements,
                 base_table,
+                base_table_span,
                 transformations,
-            }),
-    )
+            },
+        )
 }
 
 fn transformation() -> impl Psr<Transformation> {
@@ -45,34 +50,36 @@ fn top_level_condition_set() -> impl Psr<ConditionSet> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tests::WithoutSpans;
 
     #[test]
     fn test_parse_query() {
         assert_eq!(
-            query().parse("#foo a:1 b:2 $c"),
+            query().parse("#foo a:1 b:2 $c").without_spans(),
             Ok(Query {
+                statements: vec![],
                 base_table: "foo".to_string(),
+                base_table_span: Span::default(),
                 transformations: vec![Transformation {
                     conditions: ConditionSet {
                         conjunction: Conjunction::And,
                         entries: vec![
                             Expr::Comparison(Box::new(Comparison {
-                                left: ComparisonSide::Expr(Expr::Path(vec![PathPart::Column(
-                                    "a".to_string()
-                                )])),
+                                span: Span::default(),
+                                left: ComparisonSide::Expr(Expr::Path(vec![PathPart::column("a")])),
                                 operator: Operator::Eq,
                                 right: ComparisonSide::Expr(Expr::Number("1".to_string())),
                             })),
                             Expr::Comparison(Box::new(Comparison {
-                                left: ComparisonSide::Expr(Expr::Path(vec![PathPart::Column(
-                                    "b".to_string()
-                                )])),
+                                span: Span::default(),
+                                left: ComparisonSide::Expr(Expr::Path(vec![PathPart::column("b")])),
                                 operator: Operator::Eq,
                                 right: ComparisonSide::Expr(Expr::Number("2".to_string())),
                             })),
                         ],
                     },
                     result_columns: vec![ResultColumnStatement::Spec(ColumnSpec {
+                        span: Span::default(),
                         alias: None,
                         column_control: ColumnControl {
                             sort: None,
@@ -80,10 +87,40 @@ mod tests {
                             is_partition_by: false,
                             is_hidden: false
                         },
-                        expr: Expr::Path(vec![PathPart::Column("c".to_string())])
+                        expr: Expr::Path(vec![PathPart::column("c")])
                     })],
                 }],
             })
         );
     }
+
+    #[test]
+    fn test_query_spans() {
+        let query = query().parse("#issues title:1 $author.name|upper").unwrap();
+        assert_eq!(query.base_table_span.range(), 0..7);
+        let transformation = &query.transformations[0];
+        let Expr::Comparison(comparison) = &transformation.conditions.entries[0] else {
+            panic!("expected a comparison");
+        };
+        assert_eq!(comparison.span.range(), 8..15);
+        let ResultColumnStatement::Spec(spec) = &transformation.result_columns[0] else {
+            panic!("expected a column spec");
+        };
+        assert_eq!(spec.span.range(), 17..34);
+        let Expr::Call(call) = &spec.expr else {
+            panic!("expected a call");
+        };
+        assert_eq!(call.span.range(), 28..34);
+        let Expr::Path(parts) = &call.args[0] else {
+            panic!("expected a path");
+        };
+        let spans = parts
+            .iter()
+            .map(|part| match part {
+                PathPart::Column(column) => column.span.range(),
+                _ => panic!("expected a column"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(spans, vec![17..23, 24..28]);
+    }
 }
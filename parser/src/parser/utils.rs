@@ -80,3 +80,40 @@ pub fn escape(quote: char) -> impl Psr<char> {
             )),
     )
 }
+
+/// `inner` enclosed by `open` and `close`, recovering from problems so that parsing can continue
+/// and any further problems can be reported in the same pass.
+///
+/// - When the input ends before `close`, the section is treated as closed.
+/// - When `inner` fails, we skip to the matching `close` and produce `fallback` instead.
+pub fn delimited<T: 'static>(
+    inner: impl Psr<T>,
+    open: char,
+    close: char,
+    fallback: impl Fn() -> T + Clone + 'static,
+) -> impl Psr<T> {
+    let delimiters = [
+        (EXPR_PAREN_L, EXPR_PAREN_R),
+        (CONDITION_SET_AND_BRACE_L, CONDITION_SET_AND_BRACE_R),
+        (CONDITION_SET_OR_BRACE_L, CONDITION_SET_OR_BRACE_R),
+    ];
+    let closing = just(close).to(true).or(end().rewind().to(false));
+    just(open)
+        .map_with_span(|_, span| span)
+        .then(inner)
+        .then(closing)
+        .validate(move |((open_span, inner), is_closed), span, emit| {
+            if !is_closed {
+                let end = span.end..span.end;
+                emit(Simple::unclosed_delimiter(
+                    open_span, open, end, close, None,
+                ));
+            }
+            inner
+        })
+        .recover_with(nested_delimiters(open, close, delimiters, move |_| {
+            fallback()
+        }))
+        // Boxing keeps the parser's stack frames small
+        .boxed()
+}
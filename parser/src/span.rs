@@ -0,0 +1,89 @@
+use std::ops::Range;
+
+/// The location of an AST node within the source code, as a range of characters (not bytes).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// A 1-based line and column within the source code. Columns are counted in characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LineColumn {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn range(&self) -> Range<usize> {
+        self.start..self.end
+    }
+
+    /// The range of bytes within `source` which this span covers, for slicing the source
+    pub fn byte_range(&self, source: &str) -> Range<usize> {
+        let byte_offset = |char_offset: usize| {
+            source
+                .char_indices()
+                .nth(char_offset)
+                .map(|(i, _)| i)
+                .unwrap_or(source.len())
+        };
+        byte_offset(self.start)..byte_offset(self.end)
+    }
+
+    pub fn start_line_column(&self, source: &str) -> LineColumn {
+        line_column(source, self.start)
+    }
+
+    pub fn end_line_column(&self, source: &str) -> LineColumn {
+        line_column(source, self.end)
+    }
+}
+
+fn line_column(source: &str, char_offset: usize) -> LineColumn {
+    let mut position = LineColumn { line: 1, column: 1 };
+    for c in source.chars().take(char_offset) {
+        if c == '\n' {
+            position.line += 1;
+            position.column = 1;
+        } else {
+            position.column += 1;
+        }
+    }
+    position
+}
+
+impl From<Range<usize>> for Span {
+    fn from(range: Range<usize>) -> Self {
+        Self {
+            start: range.start,
+            end: range.end,
+        }
+    }
+}
+
+impl From<Span> for Range<usize> {
+    fn from(span: Span) -> Self {
+        span.range()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_span_positions() {
+        let source = "#issues\n  $tïtle $id";
+        let span = Span::from(11..16);
+        assert_eq!(&source[span.byte_range(source)], "tïtle");
+        assert_eq!(
+            span.start_line_column(source),
+            LineColumn { line: 2, column: 4 }
+        );
+        assert_eq!(
+            span.end_line_column(source),
+            LineColumn { line: 2, column: 9 }
+        );
+    }
+}
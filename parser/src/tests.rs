@@ -0,0 +1,187 @@
+use crate::ast::*;
+
+/// Resets every span within an AST node so that parsed nodes can be compared with nodes written
+/// out by hand, which use `Span::default()`.
+pub trait WithoutSpans {
+    fn without_spans(self) -> Self;
+}
+
+impl<T: WithoutSpans, E> WithoutSpans for Result<T, E> {
+    fn without_spans(self) -> Self {
+        self.map(T::without_spans)
+    }
+}
+
+impl<T: WithoutSpans> WithoutSpans for Option<T> {
+    fn without_spans(self) -> Self {
+        self.map(T::without_spans)
+    }
+}
+
+impl<T: WithoutSpans> WithoutSpans for Vec<T> {
+    fn without_spans(self) -> Self {
+        self.into_iter().map(T::without_spans).collect()
+    }
+}
+
+impl<T: WithoutSpans> WithoutSpans for Box<T> {
+    fn without_spans(self) -> Self {
+        Box::new((*self).without_spans())
+    }
+}
+
+impl WithoutSpans for Query {
+    fn without_spans(self) -> Self {
+        Query {
+            statements: self.statements.without_spans(),
+            base_table_span: Span::default(),
+            transformations: self.transformations.without_spans(),
+            ..self
+        }
+    }
+}
+
+impl WithoutSpans for Module {
+    fn without_spans(self) -> Self {
+        Module {
+            statements: self.statements.without_spans(),
+        }
+    }
+}
+
+impl WithoutSpans for Statement {
+    fn without_spans(self) -> Self {
+        match self {
+            Statement::Import(import) => Statement::Import(Import {
+                span: Span::default(),
+                ..import
+            }),
+            Statement::Definition(definition) => Statement::Definition(Definition {
+                body: definition.body.without_spans(),
+                span: Span::default(),
+                ..definition
+            }),
+        }
+    }
+}
+
+impl WithoutSpans for Transformation {
+    fn without_spans(self) -> Self {
+        Transformation {
+            conditions: self.conditions.without_spans(),
+            result_columns: self.result_columns.without_spans(),
+        }
+    }
+}
+
+impl WithoutSpans for Expr {
+    fn without_spans(self) -> Self {
+        match self {
+            Expr::Path(parts) => Expr::Path(parts.without_spans()),
+            Expr::ConditionSet(cs) => Expr::ConditionSet(cs.without_spans()),
+            Expr::HasQuantity(h) => Expr::HasQuantity(HasQuantity {
+                path_parts: h.path_parts.without_spans(),
+                span: Span::default(),
+                ..h
+            }),
+            Expr::Outer(e) => Expr::Outer(e.without_spans()),
+            Expr::Call(c) => Expr::Call(Call {
+                args: c.args.without_spans(),
+                span: Span::default(),
+                ..c
+            }),
+            Expr::Product(a, b) => Expr::Product(a.without_spans(), b.without_spans()),
+            Expr::Quotient(a, b) => Expr::Quotient(a.without_spans(), b.without_spans()),
+            Expr::Sum(a, b) => Expr::Sum(a.without_spans(), b.without_spans()),
+            Expr::Difference(a, b) => Expr::Difference(a.without_spans(), b.without_spans()),
+            Expr::Comparison(c) => Expr::Comparison(Box::new(Comparison {
+                left: c.left.without_spans(),
+                operator: c.operator,
+                right: c.right.without_spans(),
+                span: Span::default(),
+            })),
+            Expr::Number(_)
+            | Expr::Date(_)
+            | Expr::Duration(_)
+            | Expr::String(_)
+            | Expr::Variable(_) => self,
+        }
+    }
+}
+
+impl WithoutSpans for ComparisonSide {
+    fn without_spans(self) -> Self {
+        match self {
+            ComparisonSide::Expr(e) => ComparisonSide::Expr(e.without_spans()),
+            ComparisonSide::Expansion(cs) => ComparisonSide::Expansion(cs.without_spans()),
+            ComparisonSide::Range(range) => ComparisonSide::Range(Range {
+                lower: RangeBound {
+                    expr: range.lower.expr.without_spans(),
+                    ..range.lower
+                },
+                upper: RangeBound {
+                    expr: range.upper.expr.without_spans(),
+                    ..range.upper
+                },
+            }),
+        }
+    }
+}
+
+impl WithoutSpans for ConditionSet {
+    fn without_spans(self) -> Self {
+        ConditionSet {
+            entries: self.entries.without_spans(),
+            ..self
+        }
+    }
+}
+
+impl WithoutSpans for PathPart {
+    fn without_spans(self) -> Self {
+        match self {
+            PathPart::Column(column) => PathPart::column(column.name),
+            PathPart::TableWithMany(t) => PathPart::TableWithMany(TableWithMany {
+                condition_set: t.condition_set.without_spans(),
+                span: Span::default(),
+                ..t
+            }),
+            PathPart::Pick(pick) => PathPart::Pick(Pick {
+                order_by: pick.order_by.without_spans(),
+                span: Span::default(),
+                ..pick
+            }),
+            PathPart::Expr(e) => PathPart::Expr(e.without_spans()),
+            PathPart::TableWithOne(_) | PathPart::Transitive => self,
+        }
+    }
+}
+
+impl WithoutSpans for ResultColumnStatement {
+    fn without_spans(self) -> Self {
+        match self {
+            ResultColumnStatement::Spec(spec) => ResultColumnStatement::Spec(spec.without_spans()),
+            ResultColumnStatement::Glob(glob) => ResultColumnStatement::Glob(ColumnGlob {
+                head: glob.head.without_spans(),
+                specs: glob.specs.without_spans(),
+                span: Span::default(),
+            }),
+            ResultColumnStatement::Nested(nested) => ResultColumnStatement::Nested(NestedColumns {
+                path: nested.path.without_spans(),
+                specs: nested.specs.without_spans(),
+                span: Span::default(),
+                ..nested
+            }),
+        }
+    }
+}
+
+impl WithoutSpans for ColumnSpec {
+    fn without_spans(self) -> Self {
+        ColumnSpec {
+            expr: self.expr.without_spans(),
+            span: Span::default(),
+            ..self
+        }
+    }
+}
@@ -10,6 +10,8 @@ pub(crate) const COLUMN_GLOB_ADJUSTMENT_BRACE_L: char = '(';
 pub(crate) const COLUMN_GLOB_ADJUSTMENT_BRACE_R: char = ')';
 pub(crate) const COLUMN_GLOB: char = '*';
 pub(crate) const COLUMN_SPEC_PREFIX: char = '$';
+pub(crate) const NESTED_COLUMNS_BRACE_L: char = '(';
+pub(crate) const NESTED_COLUMNS_BRACE_R: char = ')';
 pub(crate) const COMPARE_EQ: &str = ":";
 pub(crate) const COMPARE_GT: &str = ":>";
 pub(crate) const COMPARE_GTE: &str = ":>=";
@@ -33,16 +35,32 @@ pub(crate) const CONDITION_SET_OR_BRACE_L: char = '[';
 pub(crate) const CONDITION_SET_OR_BRACE_R: char = ']';
 pub(crate) const CONST_SIGIL: char = '@';
 pub(crate) const DB_IDENTIFIER_QUOTE: char = '`';
+pub(crate) const DEFINITION_ASSIGNMENT: char = '=';
+pub(crate) const DEFINITION_PARAMS_TERMINATOR: char = ';';
 pub(crate) const EXPR_PAREN_L: char = '(';
 pub(crate) const EXPR_PAREN_R: char = ')';
 pub(crate) const EXPR_DIVIDE: char = '/';
 pub(crate) const EXPR_TIMES: char = '*';
 pub(crate) const EXPR_PLUS: char = '+';
 pub(crate) const EXPR_MINUS: char = '-';
+pub(crate) const FUNCTION_SIGIL: &str = "@@";
+pub(crate) const HAS_QUANTITY_ALL: &str = "**";
 pub(crate) const HAS_QUANTITY_AT_LEAST_ONE: &str = "++";
+pub(crate) const HAS_QUANTITY_EXACTLY_ONE: &str = "==";
 pub(crate) const HAS_QUANTITY_ZERO: &str = "--";
 pub(crate) const LITERAL_NULL: &str = "null";
+pub(crate) const MODULE_EXPORT: &str = "===";
+pub(crate) const MODULE_IMPORT: &str = "<<<";
+pub(crate) const MODULE_IMPORT_ALIAS_PREFIX: &str = "->";
+pub(crate) const MODULE_IMPORT_ALL: char = '*';
+pub(crate) const MODULE_IMPORT_BRACE_L: char = '(';
+pub(crate) const MODULE_IMPORT_BRACE_R: char = ')';
+pub(crate) const OUTER_RECORD_SIGIL: char = '&';
+pub(crate) const PATH_PICK_FIRST: &str = "first";
+pub(crate) const PATH_PICK_LAST: &str = "last";
+pub(crate) const PATH_PICK_PREFIX: char = '%';
 pub(crate) const PATH_SEPARATOR: char = '.';
+pub(crate) const PATH_TRANSITIVE: char = '*';
 pub(crate) const PATH_TO_TABLE_WITH_ONE_PREFIX: &str = ">>";
 pub(crate) const STRING_ESCAPE_PREFIX: char = '\\';
 pub(crate) const STRING_QUOTE_DOUBLE: char = '"';